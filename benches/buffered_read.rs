@@ -0,0 +1,68 @@
+/*
+* 多线程并发读取截断写文件缓冲数据的基准，比较不同线程数下的吞吐量，用于观察读取缓冲数据时的竞争：
+* cargo bench --bench buffered_read
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::process;
+use std::sync::Arc;
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{SafeFile, FILE_RUNTIME};
+
+// 每个线程读取的次数
+const READS_PER_THREAD: usize = 1024;
+
+// 在FILE_RUNTIME上执行异步任务并返回结果
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 多个线程同时反复读取同一个截断写文件的缓冲数据
+fn concurrent_buffered_read(c: &mut Criterion) {
+    let path = env::temp_dir().join(format!("pi_rt_file.bench.{}.buffered_read", process::id()));
+    let copy = path.clone();
+    let file = block_on(async move {
+        let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await.unwrap();
+        file.write(0, Arc::from(vec![1u8; 4096]), WriteOptions::Flush).await.unwrap();
+        file
+    });
+
+    let mut group = c.benchmark_group("buffered_read");
+    for threads in [1usize, 4, 8].iter() {
+        group.throughput(Throughput::Elements((threads * READS_PER_THREAD) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(threads), threads, |b, &threads| {
+            b.iter(|| {
+                let workers: Vec<_> = (0..threads)
+                    .map(|_| {
+                        let file = file.clone();
+                        thread::spawn(move || {
+                            block_on(async move {
+                                for i in 0..READS_PER_THREAD {
+                                    file.read((i % 4000) as u64, 64).await.unwrap();
+                                }
+                            })
+                        })
+                    })
+                    .collect();
+                for worker in workers {
+                    worker.join().unwrap();
+                }
+            });
+        });
+    }
+    group.finish();
+    drop(file);
+    let _ = fs::remove_file(path);
+}
+
+criterion_group!(benches, concurrent_buffered_read);
+criterion_main!(benches);
@@ -0,0 +1,162 @@
+/*
+* 读写路径的基准测试，每个场景是一个独立的分组，可以按分组名单独运行，例如：
+* cargo bench --bench file_io -- small_read
+*/
+use std::env;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::future::join_all;
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{remove_file, temp_file, SafeFile, TempSafeFile, FILE_RUNTIME};
+
+// 读基准使用的文件大小
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+// 顺序写每次写入的字节数
+const WRITE_CHUNK: usize = 4096;
+// 顺序写每轮写入的次数
+const WRITE_COUNT: usize = 256;
+
+// 在FILE_RUNTIME上执行异步任务并等待完成
+fn block_on<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    FILE_RUNTIME.block_on(future).unwrap();
+}
+
+// 创建写入了指定字节数的临时文件
+fn data_file(size: usize) -> TempSafeFile {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    block_on(async move {
+        let file = temp_file().await.unwrap();
+        let data: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        file.write(0, Arc::from(data), WriteOptions::Flush).await.unwrap();
+        sender.send(file).unwrap();
+    });
+    receiver.recv().unwrap()
+}
+
+// 获取系统临时目录下本进程唯一的路径
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("pi_rt_file.bench.{}.{}", process::id(), name))
+}
+
+// 从文件不同位置读取小块数据
+fn small_read(c: &mut Criterion) {
+    let file = data_file(FILE_SIZE);
+    let mut group = c.benchmark_group("small_read");
+    for size in [64usize, 512, 4096] {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            let mut pos = 0u64;
+            b.iter(|| {
+                let file = (*file).clone();
+                let at = pos;
+                pos = (pos + 7919 * size as u64) % (FILE_SIZE - size) as u64;
+                block_on(async move {
+                    file.read(at, size).await.unwrap();
+                });
+            });
+        });
+    }
+    group.finish();
+}
+
+// 从文件头读取大块数据
+fn large_read(c: &mut Criterion) {
+    let file = data_file(FILE_SIZE);
+    let mut group = c.benchmark_group("large_read");
+    group.sample_size(20);
+    for size in [1024 * 1024usize, FILE_SIZE] {
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter(|| {
+                let file = (*file).clone();
+                block_on(async move {
+                    file.read(0, size).await.unwrap();
+                });
+            });
+        });
+    }
+    group.finish();
+}
+
+// 从文件头开始按固定大小顺序写入
+fn sequential_write(c: &mut Criterion) {
+    let file = data_file(0);
+    let chunk: Arc<[u8]> = Arc::from(vec![1u8; WRITE_CHUNK]);
+    let mut group = c.benchmark_group("sequential_write");
+    group.throughput(Throughput::Bytes((WRITE_CHUNK * WRITE_COUNT) as u64));
+    group.bench_function(BenchmarkId::from_parameter(WRITE_CHUNK), |b| {
+        b.iter(|| {
+            let file = (*file).clone();
+            let chunk = chunk.clone();
+            block_on(async move {
+                for i in 0..WRITE_COUNT {
+                    file.write((i * WRITE_CHUNK) as u64, chunk.clone(), WriteOptions::None)
+                        .await
+                        .unwrap();
+                }
+            });
+        });
+    });
+    group.finish();
+}
+
+// 以截断写方式反复覆写整个文件，每次写入后刷新到磁盘
+fn truncate_write(c: &mut Criterion) {
+    let path = temp_path("truncate");
+    let mut group = c.benchmark_group("truncate_write");
+    for size in [4096usize, 64 * 1024, 1024 * 1024] {
+        let data: Arc<[u8]> = Arc::from(vec![2u8; size]);
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| {
+                let path = path.clone();
+                let data = data.clone();
+                block_on(async move {
+                    let file = SafeFile::open(path, AsyncFileOptions::TruncateWrite).await.unwrap();
+                    file.write(0, data, WriteOptions::Flush).await.unwrap();
+                });
+            });
+        });
+    }
+    group.finish();
+    block_on(async move {
+        let _ = remove_file(path).await;
+    });
+}
+
+// 多个任务同时打开同一路径并读取，测量共享句柄和锁的竞争
+fn contention(c: &mut Criterion) {
+    let file = data_file(FILE_SIZE);
+    let path = file.path().to_path_buf();
+    let mut group = c.benchmark_group("contention");
+    for tasks in [4usize, 16, 64] {
+        group.throughput(Throughput::Elements(tasks as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(tasks), &tasks, |b, &tasks| {
+            b.iter(|| {
+                let path = path.clone();
+                block_on(async move {
+                    join_all((0..tasks).map(|i| {
+                        let path = path.clone();
+                        async move {
+                            let file = SafeFile::open(path, AsyncFileOptions::ReadWrite).await.unwrap();
+                            file.read((i * 4096) as u64, 4096).await.unwrap();
+                        }
+                    }))
+                    .await;
+                });
+            });
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, small_read, large_read, sequential_write, truncate_write, contention);
+criterion_main!(benches);
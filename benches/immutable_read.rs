@@ -0,0 +1,70 @@
+/*
+* 只读文件不加锁读与可读写文件加读锁读的对比基准，读取都命中缓存，差异主要来自加锁：
+* cargo bench --bench immutable_read
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::process;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{SafeFile, FILE_RUNTIME};
+
+// 基准使用的文件大小
+const FILE_SIZE: usize = 64 * 1024;
+// 每批并发读取的次数
+const BATCH: usize = 64;
+// 每次读取的字节数
+const READ_SIZE: usize = 256;
+
+// 在FILE_RUNTIME上执行异步任务并返回结果
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 分别以只读和可读写方式打开同一内容的文件，每批读取不同位置的小块数据
+fn immutable_read(c: &mut Criterion) {
+    let immutable = env::temp_dir().join(format!("pi_rt_file.bench.{}.immutable", process::id()));
+    let locked = env::temp_dir().join(format!("pi_rt_file.bench.{}.locked", process::id()));
+    let data: Vec<u8> = (0..FILE_SIZE).map(|i| i as u8).collect();
+    fs::write(&immutable, &data).unwrap();
+    fs::write(&locked, &data).unwrap();
+    let (a, b) = (immutable.clone(), locked.clone());
+    let files = block_on(async move {
+        let immutable = SafeFile::open(a, AsyncFileOptions::OnlyRead).await.unwrap();
+        let locked = SafeFile::open(b, AsyncFileOptions::ReadWrite).await.unwrap();
+        // 先读全数据填充缓存
+        immutable.read(0, FILE_SIZE).await.unwrap();
+        locked.read(0, FILE_SIZE).await.unwrap();
+        [("no_lock", immutable), ("rw_lock", locked)]
+    });
+
+    let mut group = c.benchmark_group("immutable_read");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for (name, file) in files.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), file, |b, file| {
+            b.iter(|| {
+                let file = file.clone();
+                block_on(async move {
+                    for i in 0..BATCH {
+                        let pos = (i * 7919 * READ_SIZE % (FILE_SIZE - READ_SIZE)) as u64;
+                        file.read(pos, READ_SIZE).await.unwrap();
+                    }
+                })
+            });
+        });
+    }
+    group.finish();
+    drop(files);
+    let _ = fs::remove_file(immutable);
+    let _ = fs::remove_file(locked);
+}
+
+criterion_group!(benches, immutable_read);
+criterion_main!(benches);
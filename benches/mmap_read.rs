@@ -0,0 +1,65 @@
+/*
+* 内存映射读与系统调用读的对比基准，需要启用mmap特性：
+* cargo bench --features mmap --bench mmap_read
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::process;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{CacheOptions, SafeFile, FILE_RUNTIME};
+
+// 基准使用的文件大小
+const FILE_SIZE: usize = 16 * 1024 * 1024;
+// 每次读取的字节数
+const READ_SIZE: usize = 4096;
+
+// 在FILE_RUNTIME上执行异步任务并返回结果
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 从文件不同位置读取小块数据，分别经由内存映射和系统调用
+fn mmap_read(c: &mut Criterion) {
+    let mapped = env::temp_dir().join(format!("pi_rt_file.bench.{}.mmap", process::id()));
+    let plain = env::temp_dir().join(format!("pi_rt_file.bench.{}.syscall", process::id()));
+    let data: Vec<u8> = (0..FILE_SIZE).map(|i| i as u8).collect();
+    fs::write(&mapped, &data).unwrap();
+    fs::write(&plain, &data).unwrap();
+    let (a, b) = (mapped.clone(), plain.clone());
+    let files = block_on(async move {
+        // 普通打开的文件关闭读缓存，每次读取都经由系统调用
+        let cache = CacheOptions { enable: false, ..Default::default() };
+        let mapped = SafeFile::open_mmap(a).await.unwrap();
+        let plain = SafeFile::open_with(b, AsyncFileOptions::OnlyRead, cache).await.unwrap();
+        [("mmap", mapped), ("syscall", plain)]
+    });
+
+    let mut group = c.benchmark_group("mmap_read");
+    group.throughput(Throughput::Bytes(READ_SIZE as u64));
+    for (name, file) in files.iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(name), file, |b, file| {
+            let mut pos = 0u64;
+            b.iter(|| {
+                let file = file.clone();
+                let at = pos;
+                pos = (pos + 7919 * READ_SIZE as u64) % (FILE_SIZE - READ_SIZE) as u64;
+                block_on(async move { file.read(at, READ_SIZE).await.unwrap() })
+            });
+        });
+    }
+    group.finish();
+    drop(files);
+    let _ = fs::remove_file(mapped);
+    let _ = fs::remove_file(plain);
+}
+
+criterion_group!(benches, mmap_read);
+criterion_main!(benches);
@@ -0,0 +1,80 @@
+/*
+* 多线程并发打开文件的基准，比较不同线程数下打开已共享句柄的吞吐量，用于观察全局表的锁竞争：
+* cargo bench --bench open_table
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process;
+use std::thread;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{SafeFile, FILE_RUNTIME};
+
+// 每个线程打开的不同路径数
+const PATHS_PER_THREAD: usize = 32;
+
+// 在FILE_RUNTIME上执行异步任务并返回结果
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 多个线程同时反复打开各自的一组路径，路径的句柄已被持有，每次打开只查找全局表
+fn concurrent_open(c: &mut Criterion) {
+    let mut group = c.benchmark_group("open_table");
+    for threads in [1usize, 4, 8].iter() {
+        let paths: Vec<Vec<PathBuf>> = (0..*threads)
+            .map(|t| {
+                (0..PATHS_PER_THREAD)
+                    .map(|i| env::temp_dir().join(format!("pi_rt_file.bench.{}.open.{}.{}", process::id(), t, i)))
+                    .collect()
+            })
+            .collect();
+        let all: Vec<PathBuf> = paths.iter().flatten().cloned().collect();
+        let copy = all.clone();
+        let held = block_on(async move {
+            let mut held = Vec::new();
+            for path in copy {
+                held.push(SafeFile::open(path, AsyncFileOptions::ReadWrite).await.unwrap());
+            }
+            held
+        });
+
+        group.throughput(Throughput::Elements((threads * PATHS_PER_THREAD) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(threads), &paths, |b, paths| {
+            b.iter(|| {
+                let workers: Vec<_> = paths
+                    .iter()
+                    .cloned()
+                    .map(|paths| {
+                        thread::spawn(move || {
+                            block_on(async move {
+                                for path in paths {
+                                    SafeFile::open(path, AsyncFileOptions::ReadWrite).await.unwrap();
+                                }
+                            })
+                        })
+                    })
+                    .collect();
+                for worker in workers {
+                    worker.join().unwrap();
+                }
+            });
+        });
+        drop(held);
+        for path in all {
+            let _ = fs::remove_file(path);
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, concurrent_open);
+criterion_main!(benches);
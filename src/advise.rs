@@ -0,0 +1,84 @@
+use std::fs::File;
+use std::io::Result;
+
+/*
+* 文件访问模式的建议，对应POSIX_FADV_*，只影响内核的预读和页缓存策略，不影响读写结果
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    Normal,     //无特别建议
+    Sequential, //顺序访问，内核会加大预读
+    Random,     //随机访问，内核会关闭预读
+    NoReuse,    //数据只访问一次
+    WillNeed,   //即将访问，内核会提前读入页缓存
+    DontNeed,   //不再访问，内核可以释放页缓存
+}
+
+// 为文件的指定范围设置访问模式的建议，长度为0表示到文件尾
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+pub(crate) fn fadvise(file: &File, offset: u64, len: u64, advice: Advice) -> Result<()> {
+    use std::convert::TryFrom;
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::io::AsRawFd;
+
+    let advice = match advice {
+        Advice::Normal => libc::POSIX_FADV_NORMAL,
+        Advice::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+        Advice::Random => libc::POSIX_FADV_RANDOM,
+        Advice::NoReuse => libc::POSIX_FADV_NOREUSE,
+        Advice::WillNeed => libc::POSIX_FADV_WILLNEED,
+        Advice::DontNeed => libc::POSIX_FADV_DONTNEED,
+    };
+    let (offset, len) = match (libc::off_t::try_from(offset), libc::off_t::try_from(len)) {
+        (Ok(offset), Ok(len)) => (offset, len),
+        _ => return Err(Error::new(ErrorKind::InvalidInput, "range overflow")),
+    };
+    // 失败时直接返回错误码，不设置errno
+    match unsafe { libc::posix_fadvise(file.as_raw_fd(), offset, len, advice) } {
+        0 => Ok(()),
+        code => Err(Error::from_raw_os_error(code)),
+    }
+}
+
+// 其它平台不支持访问模式的建议，忽略
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+pub(crate) fn fadvise(_file: &File, _offset: u64, _len: u64, _advice: Advice) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_path;
+    use std::fs;
+
+    #[test]
+    fn every_advice_is_accepted() {
+        let path = test_path("advise_all");
+        fs::write(&path, b"0123456789").unwrap();
+        let file = File::open(&path).unwrap();
+        for advice in [
+            Advice::Normal,
+            Advice::Sequential,
+            Advice::Random,
+            Advice::NoReuse,
+            Advice::WillNeed,
+            Advice::DontNeed,
+        ] {
+            assert!(fadvise(&file, 0, 0, advice).is_ok(), "{:?}", advice);
+        }
+        assert_eq!(fs::read(&path).unwrap(), b"0123456789");
+        let _ = fs::remove_file(path);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn overflowing_range_is_invalid() {
+        let path = test_path("advise_overflow");
+        fs::write(&path, b"0123456789").unwrap();
+        let file = File::open(&path).unwrap();
+        let e = fadvise(&file, u64::MAX, 0, Advice::Normal).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+use pi_async_rt::rt::AsyncRuntimeExt;
+
+use crate::{offset_pos, SafeFile, FILE_RUNTIME};
+
+/*
+* 安全文件的同步读适配器，通过FILE_RUNTIME阻塞的执行异步读，内部维护读取位置，每次读取后前进
+* 不能在FILE_RUNTIME的工作线程中调用，否则可能死锁
+*/
+#[derive(Debug, Clone)]
+pub struct BlockingSafeFile {
+    file: SafeFile,
+    pos: u64,
+}
+
+impl BlockingSafeFile {
+    // 从文件头开始读取指定的安全文件
+    pub fn new(file: SafeFile) -> Self {
+        BlockingSafeFile { file, pos: 0 }
+    }
+
+    // 获取当前读取位置
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    // 获取内部的安全文件
+    pub fn into_inner(self) -> SafeFile {
+        self.file
+    }
+
+    // 在FILE_RUNTIME上阻塞的执行指定的异步操作，阻塞执行的结果需要实现Default，以Option包装
+    fn block_on<F, T>(&self, op: &str, future: F) -> Result<T>
+    where
+        F: Future<Output = Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        match FILE_RUNTIME.block_on(async move { Some(future.await) })? {
+            Some(r) => r,
+            None => Err(Error::other(format!(
+                "{} file failed, file: {:?}, reason: block on runtime failed",
+                op,
+                self.file.path()
+            ))),
+        }
+    }
+}
+
+impl Read for BlockingSafeFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let file = self.file.clone();
+        let pos = self.pos;
+        let len = buf.len();
+        let data = self.block_on("Read", async move { file.read(pos, len).await })?;
+        buf[..data.len()].copy_from_slice(&data);
+        self.pos += data.len() as u64;
+        Ok(data.len())
+    }
+}
+
+impl Seek for BlockingSafeFile {
+    fn seek(&mut self, position: SeekFrom) -> Result<u64> {
+        let pos = match position {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => {
+                // 文件长度包括截断写文件未落地的缓冲数据
+                let file = self.file.clone();
+                offset_pos(self.block_on("Seek", async move { file.len().await })?, offset)
+            }
+            SeekFrom::Current(offset) => offset_pos(self.pos, offset),
+        };
+        match pos {
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Seek file failed, file: {:?}, reason: invalid seek to a negative or overflowing position", self.file.path()),
+            )),
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+    use std::sync::Arc;
+    use std::{fs, io};
+
+    #[test]
+    fn copy_reads_whole_file() {
+        let path = test_path("blocking_copy");
+        let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&path, &data).unwrap();
+        let copy = path.clone();
+        let file = block_on(async move { SafeFile::open(copy, AsyncFileOptions::OnlyRead).await }).unwrap();
+        let mut reader = BlockingSafeFile::new(file);
+        let mut out = Vec::new();
+        assert_eq!(io::copy(&mut reader, &mut out).unwrap(), data.len() as u64);
+        assert_eq!(out, data);
+        assert_eq!(reader.position(), data.len() as u64);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_and_seek_from_sync_code() {
+        let path = test_path("blocking");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let file = block_on(async move { SafeFile::open(copy, AsyncFileOptions::OnlyRead).await }).unwrap();
+        let mut reader = BlockingSafeFile::new(file);
+        let mut buf = [0; 4];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"0123");
+        assert_eq!(reader.seek(SeekFrom::End(-3)).unwrap(), 7);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"789");
+        assert_eq!(reader.seek(SeekFrom::Current(-11)).unwrap_err().kind(), ErrorKind::InvalidInput);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn seek_from_end_counts_buffered_data() {
+        let path = test_path("blocking_buffered");
+        let copy = path.clone();
+        let file = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"abc"[..]), WriteOptions::None).await?;
+            Ok::<_, io::Error>(file)
+        })
+        .unwrap();
+        // 模拟尚未落地的缓冲数据，文件长度以缓冲数据为准
+        file.0.set_buff(Arc::from(&b"abcdef"[..]), 1);
+        let mut reader = BlockingSafeFile::new(file);
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 4);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"ef");
+        drop(reader);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+use crate::{offset_pos, FileCursor, SafeFile};
+
+// 默认的预读缓冲区大小
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/*
+* 带预读缓冲区的顺序读取器，每次从游标读取一整个缓冲区，之后的小读取直接从缓冲区返回，减少对文件的读取次数
+* 缓冲区中的数据是读取时的快照，之后对文件的写入在重新填充缓冲区前不可见
+*/
+#[derive(Debug)]
+pub struct BufferedReader {
+    cursor: FileCursor, //游标的位置在缓冲区数据之后
+    buf: Vec<u8>,       //预读的数据
+    offset: usize,      //缓冲区中已消费的字节数
+    capacity: usize,    //每次预读的字节数
+}
+
+impl BufferedReader {
+    // 以默认的缓冲区大小，从游标的当前位置开始读取
+    pub fn new(cursor: FileCursor) -> Self {
+        BufferedReader::with_capacity(cursor, DEFAULT_CAPACITY)
+    }
+
+    // 以指定的缓冲区大小，从游标的当前位置开始读取，缓冲区大小至少为1
+    pub fn with_capacity(cursor: FileCursor, capacity: usize) -> Self {
+        BufferedReader {
+            cursor,
+            buf: Vec::new(),
+            offset: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    // 获取当前读取位置，不包括已预读但未消费的数据
+    pub fn position(&self) -> u64 {
+        self.cursor.position() - self.buffered().len() as u64
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        self.cursor.file()
+    }
+
+    // 获取已预读但未消费的数据
+    pub fn buffered(&self) -> &[u8] {
+        &self.buf[self.offset..]
+    }
+
+    // 从当前位置开始异步读指定字节，不足时重新填充缓冲区，只在读到文件尾时返回少于指定字节的数据
+    // 缓冲区为空且读取不小于缓冲区大小时，直接从文件读取
+    pub async fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(len);
+        while data.len() < len {
+            if self.buffered().is_empty() {
+                let rest = len - data.len();
+                if rest >= self.capacity {
+                    let r = self.cursor.read(rest).await?;
+                    data.extend_from_slice(&r);
+                    break;
+                }
+                if !self.fill().await? {
+                    break;
+                }
+            }
+            let count = self.buffered().len().min(len - data.len());
+            data.extend_from_slice(&self.buf[self.offset..self.offset + count]);
+            self.offset += count;
+        }
+        Ok(data)
+    }
+
+    // 从当前位置开始异步读到指定字节为止，返回的数据包括该字节，读到文件尾仍未找到则返回剩余的所有数据
+    pub async fn read_until(&mut self, byte: u8) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            if self.buffered().is_empty() && !self.fill().await? {
+                return Ok(data);
+            }
+            let buffered = self.buffered();
+            match buffered.iter().position(|b| *b == byte) {
+                Some(index) => {
+                    data.extend_from_slice(&buffered[..=index]);
+                    self.offset += index + 1;
+                    return Ok(data);
+                }
+                None => {
+                    data.extend_from_slice(buffered);
+                    self.offset = self.buf.len();
+                }
+            }
+        }
+    }
+
+    // 异步定位到指定位置，返回新的位置，并丢弃已预读的数据，相对文件尾定位时以文件的当前长度为准
+    pub async fn seek(&mut self, position: SeekFrom) -> Result<u64> {
+        let pos = match position {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => offset_pos(self.file().len().await?, offset),
+            SeekFrom::Current(offset) => offset_pos(self.position(), offset),
+        };
+        let pos = match pos {
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Seek file failed, file: {:?}, reason: invalid seek to a negative or overflowing position", self.file().path()),
+                ))
+            }
+            Some(pos) => pos,
+        };
+        self.buf.clear();
+        self.offset = 0;
+        self.cursor.seek(SeekFrom::Start(pos)).await
+    }
+
+    // 从游标读取一整个缓冲区，读到文件尾则返回false
+    async fn fill(&mut self) -> Result<bool> {
+        self.buf = self.cursor.read(self.capacity).await?;
+        self.offset = 0;
+        Ok(!self.buf.is_empty())
+    }
+}
+
+impl SafeFile {
+    //获取从文件头开始读取的带预读缓冲区的读取器，缓冲区大小为0则使用默认大小
+    pub fn buffered_reader(&self, capacity: usize) -> BufferedReader {
+        match capacity {
+            0 => BufferedReader::new(self.cursor()),
+            capacity => BufferedReader::with_capacity(self.cursor(), capacity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+
+    #[test]
+    fn read_until_splits_lines_across_fills() {
+        let path = test_path("buf_reader_lines");
+        fs::write(&path, b"line1\nline2\nlast").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            // 缓冲区小于一行，需要多次填充
+            let mut reader = file.buffered_reader(4);
+            let mut lines = Vec::new();
+            loop {
+                let line = reader.read_until(b'\n').await?;
+                if line.is_empty() {
+                    break;
+                }
+                lines.push(line);
+            }
+            Ok::<_, Error>((lines, reader.position()))
+        })
+        .unwrap();
+        assert_eq!(r.0, vec![b"line1\n".to_vec(), b"line2\n".to_vec(), b"last".to_vec()]);
+        assert_eq!(r.1, 16);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_and_seek_track_position() {
+        let path = test_path("buf_reader_seek");
+        fs::write(&path, b"0123456789abcdef").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let mut reader = file.buffered_reader(8);
+            let first = (reader.read(3).await?, reader.position(), reader.buffered().len());
+            // 相对当前位置定位时不计入已预读的数据
+            reader.seek(SeekFrom::Current(-1)).await?;
+            let back = (reader.read(2).await?, reader.buffered().len());
+            // 先消费已预读的数据，不足时重新填充
+            let large = (reader.read(10).await?, reader.buffered().len());
+            reader.seek(SeekFrom::End(-2)).await?;
+            let tail = (reader.read(8).await?, reader.position());
+            let negative = reader.seek(SeekFrom::End(-17)).await.map_err(|e| e.kind());
+            Ok::<_, Error>((first, back, large, tail, negative))
+        })
+        .unwrap();
+        assert_eq!(r.0, (b"012".to_vec(), 3, 5));
+        assert_eq!(r.1, (b"23".to_vec(), 6));
+        assert_eq!(r.2, (b"456789abcd".to_vec(), 2));
+        assert_eq!(r.3, (b"ef".to_vec(), 16));
+        assert_eq!(r.4, Err(ErrorKind::InvalidInput));
+        let _ = fs::remove_file(path);
+    }
+}
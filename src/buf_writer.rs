@@ -0,0 +1,206 @@
+use std::io::{Error, ErrorKind, Result};
+use std::mem;
+use std::sync::Arc;
+
+use pi_async_file::file::WriteOptions;
+use pi_async_rt::rt::AsyncRuntime;
+
+use crate::{LockType, SafeFile, FILE_RUNTIME};
+
+// 默认的写缓冲区大小
+const DEFAULT_CAPACITY: usize = 8 * 1024;
+
+/*
+* 带写缓冲区的顺序写入器，小的写入先累积在缓冲区中，缓冲区满或显式刷新时合并为一次顺序写入，减少对文件的写入次数
+* 缓冲区中的数据按写入顺序连续写入，在刷新前对文件的其它句柄不可见，释放时会在FILE_RUNTIME上异步写入未刷新的数据
+*/
+#[derive(Debug)]
+pub struct BufferedWriter {
+    file: SafeFile,
+    pos: u64,        //缓冲区数据在文件中的起始位置
+    buf: Vec<u8>,    //未刷新的数据
+    capacity: usize, //缓冲区大小
+}
+
+impl BufferedWriter {
+    // 以默认的缓冲区大小，从文件的指定位置开始写入，截断写文件每次写入都会替换全部数据，不支持
+    pub fn new(file: SafeFile, pos: u64) -> Result<Self> {
+        BufferedWriter::with_capacity(file, pos, DEFAULT_CAPACITY)
+    }
+
+    // 以指定的缓冲区大小，从文件的指定位置开始写入，缓冲区大小至少为1，截断写文件不支持
+    pub fn with_capacity(file: SafeFile, pos: u64, capacity: usize) -> Result<Self> {
+        if let LockType::Lock(_) = file.0.lock {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Buffered write file failed, file: {:?}, reason: truncate write file", file.path()),
+            ));
+        }
+        Ok(BufferedWriter {
+            file,
+            pos,
+            buf: Vec::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+        })
+    }
+
+    // 获取当前写入位置，包括未刷新的数据，追加写文件的实际写入位置总是文件尾
+    pub fn position(&self) -> u64 {
+        self.pos + self.buf.len() as u64
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 获取未刷新的字节数
+    pub fn buffered_len(&self) -> usize {
+        self.buf.len()
+    }
+
+    // 异步写入指定数据，缓冲区放不下时先刷新，数据不小于缓冲区大小时直接写入文件
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        if self.buf.len() + data.len() > self.capacity {
+            self.flush().await?;
+        }
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= self.capacity {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    // 异步将未刷新的数据写入文件，写入失败时保留未写入的数据，之后可以重试
+    pub async fn flush(&mut self) -> Result<()> {
+        while !self.buf.is_empty() {
+            let r = self
+                .file
+                .write(self.pos, Arc::from(&self.buf[..]), WriteOptions::None)
+                .await?;
+            if r == 0 {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    format!("Buffered write file failed, file: {:?}, pos: {}, reason: write zero", self.file.path(), self.pos),
+                ));
+            }
+            self.buf.drain(..r);
+            self.pos += r as u64;
+        }
+        Ok(())
+    }
+
+    // 异步刷新未刷新的数据，并获取内部的安全文件
+    pub async fn into_inner(mut self) -> Result<SafeFile> {
+        self.flush().await?;
+        Ok(self.file.clone())
+    }
+}
+
+impl Drop for BufferedWriter {
+    //释放时在FILE_RUNTIME上异步写入未刷新的数据，写入失败则输出错误
+    fn drop(&mut self) {
+        if self.buf.is_empty() {
+            return;
+        }
+        let mut writer = BufferedWriter {
+            file: self.file.clone(),
+            pos: self.pos,
+            buf: mem::take(&mut self.buf),
+            capacity: self.capacity,
+        };
+        let r = FILE_RUNTIME.spawn(async move {
+            if let Err(e) = writer.flush().await {
+                log::warn!("Buffered write file failed, file: {:?}, reason: {:?}", writer.file.path(), e);
+                // 已输出错误，放弃未写入的数据
+                writer.buf.clear();
+            }
+        });
+        if let Err(e) = r {
+            log::warn!("Buffered write file failed, file: {:?}, reason: {:?}", self.file.path(), e);
+        }
+    }
+}
+
+impl SafeFile {
+    //获取从指定位置开始写入的带写缓冲区的写入器，缓冲区大小为0则使用默认大小，截断写文件不支持
+    pub fn buffered_writer(&self, pos: u64, capacity: usize) -> Result<BufferedWriter> {
+        match capacity {
+            0 => BufferedWriter::new(self.clone(), pos),
+            capacity => BufferedWriter::with_capacity(self.clone(), pos, capacity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+    use std::sync::atomic::Ordering;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn small_writes_coalesce() {
+        let path = test_path("buf_writer_coalesce");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let start = file.0.gen.load(Ordering::Acquire);
+            let mut writer = file.buffered_writer(0, 8)?;
+            for data in [&b"ab"[..], b"cd", b"ef"] {
+                writer.write(data).await?;
+            }
+            // 缓冲区未满时不写入文件
+            let pending = (file.0.gen.load(Ordering::Acquire) - start, writer.buffered_len(), writer.position());
+            // 放不下时先刷新已有的6字节，之后的8字节填满缓冲区后立即刷新
+            writer.write(b"ghij").await?;
+            writer.write(b"klmnopqr").await?;
+            let file = writer.into_inner().await?;
+            Ok::<_, Error>((pending, file.0.gen.load(Ordering::Acquire) - start, file.read(0, 32).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, (0, 6, 6));
+        // 每次版本增加对应一次实际写入
+        assert_eq!(r.1, 3);
+        assert_eq!(r.2, b"abcdefghijklmnopqr");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn drop_flushes_pending_data() {
+        let path = test_path("buf_writer_drop");
+        let copy = path.clone();
+        block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let mut writer = file.buffered_writer(0, 0)?;
+            writer.write(b"unflushed").await?;
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        // 释放时在运行时上异步写入
+        for _ in 0..100 {
+            if fs::read(&path).unwrap_or_default() == b"unflushed" {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(fs::read(&path).unwrap(), b"unflushed");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_file_is_rejected() {
+        let path = test_path("buf_writer_truncate");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            Ok::<_, Error>(file.buffered_writer(0, 0).map(|_| ()).map_err(|e| e.kind()))
+        })
+        .unwrap();
+        assert_eq!(r, Err(ErrorKind::InvalidInput));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,166 @@
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use pi_async_file::file::AsyncFileOptions;
+
+use crate::{atomic_write, run_sync, SafeFile, READ_CHUNK_SIZE};
+
+// BLAKE3哈希的十六进制字符数
+const HASH_HEX_LEN: usize = 64;
+
+/*
+* 按内容存储数据，文件名为数据的BLAKE3哈希，文件不存在时原子写入，返回文件路径
+* 并发存储相同内容时各自原子重命名，内容相同，不会损坏文件
+*/
+pub async fn store_cas<P>(dir: P, data: Arc<[u8]>) -> Result<PathBuf>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let path = dir.as_ref().join(blake3::hash(&data).to_hex().as_str());
+    let check = path.clone();
+    if run_sync(move || Ok(fs::symlink_metadata(check).is_ok())).await? {
+        return Ok(path);
+    }
+    atomic_write(path.clone(), data).await?;
+    Ok(path)
+}
+
+/*
+* 读取按内容存储的数据，并校验数据的哈希，哈希格式无效则返回InvalidInput错误，校验失败则返回InvalidData错误
+*/
+pub async fn load_cas<P>(dir: P, hash: &str) -> Result<Vec<u8>>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    if hash.len() != HASH_HEX_LEN || !hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Load cas failed, dir: {:?}, hash: {:?}, reason: invalid hash", dir.as_ref(), hash),
+        ));
+    }
+    let path = dir.as_ref().join(hash);
+    let file = SafeFile::open(path.clone(), AsyncFileOptions::OnlyRead).await?;
+    let data = file.read_to_end().await?;
+    if blake3::hash(&data).to_hex().as_str() != hash {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Load cas failed, file: {:?}, reason: hash mismatch", path),
+        ));
+    }
+    Ok(data)
+}
+
+impl SafeFile {
+    //读取指定位置和长度的数据，并返回数据的BLAKE3哈希
+    pub async fn read_hashed(&self, pos: u64, len: usize) -> Result<(Vec<u8>, [u8; 32])> {
+        let data = self.read(pos, len).await?;
+        let hash = blake3::hash(&data);
+        Ok((data, *hash.as_bytes()))
+    }
+
+    //从文件头开始按READ_CHUNK_SIZE分块读取整个文件并计算BLAKE3哈希，不会一次加载整个文件
+    pub async fn hash_whole_file(&self) -> Result<[u8; 32]> {
+        let mut hasher = blake3::Hasher::new();
+        let mut pos = 0u64;
+        loop {
+            let data = self.read(pos, READ_CHUNK_SIZE).await?;
+            hasher.update(&data);
+            if data.len() < READ_CHUNK_SIZE {
+                // 不足一块，则已读到文件尾
+                return Ok(*hasher.finalize().as_bytes());
+            }
+            pos += data.len() as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+
+    #[test]
+    fn store_load_and_verify() {
+        let dir = test_path("cas");
+        fs::create_dir(&dir).unwrap();
+        let copy = dir.clone();
+        let (path, loaded, invalid) = block_on(async move {
+            let path = store_cas(copy.clone(), Arc::from(&b"content"[..])).await?;
+            // 相同内容存储到同一文件
+            let again = store_cas(copy.clone(), Arc::from(&b"content"[..])).await?;
+            let hash = path.file_name().unwrap().to_str().unwrap().to_string();
+            let loaded = load_cas(copy.clone(), &hash).await?;
+            let invalid = load_cas(copy, "not a hash").await.map_err(|e| e.kind());
+            Ok::<_, Error>(((path, again), loaded, invalid))
+        })
+        .unwrap();
+        assert_eq!(path.0, path.1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert_eq!(loaded, b"content");
+        assert_eq!(invalid, Err(ErrorKind::InvalidInput));
+        // 被篡改的内容校验失败
+        fs::write(&path.0, b"tampered").unwrap();
+        let hash = path.0.file_name().unwrap().to_str().unwrap().to_string();
+        let r = block_on(async move { load_cas(dir, &hash).await.map_err(|e| e.kind()) });
+        assert_eq!(r, Err(ErrorKind::InvalidData));
+        let _ = fs::remove_dir_all(path.0.parent().unwrap());
+    }
+
+    #[test]
+    fn concurrent_stores_keep_one_file() {
+        let dir = test_path("cas_concurrent");
+        fs::create_dir(&dir).unwrap();
+        let data: Arc<[u8]> = Arc::from(vec![7u8; 64 * 1024]);
+        let paths: Vec<_> = (0..8)
+            .map(|_| {
+                let (dir, data) = (dir.clone(), data.clone());
+                std::thread::spawn(move || block_on(async move { store_cas(dir, data).await }).unwrap())
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .collect();
+        assert!(paths.iter().all(|p| *p == paths[0]));
+        // 没有残留的临时文件
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        assert_eq!(fs::read(&paths[0]).unwrap(), &data[..]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn hashes_match_reference() {
+        let path = test_path("cas_hashed");
+        let data: Vec<u8> = (0..READ_CHUNK_SIZE * 2 + 100).map(|i| (i % 251) as u8).collect();
+        fs::write(&path, &data).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            Ok::<_, Error>((file.read_hashed(0, 3).await?, file.hash_whole_file().await?))
+        })
+        .unwrap();
+        assert_eq!(&(r.0).0, &data[..3]);
+        assert_eq!((r.0).1, *blake3::hash(&data[..3]).as_bytes());
+        assert_eq!(r.1, *blake3::hash(&data).as_bytes());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_hashed_matches_known_digest() {
+        let path = test_path("cas_known");
+        fs::write(&path, b"abc").unwrap();
+        let copy = path.clone();
+        let (data, hash) = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            file.read_hashed(0, 3).await
+        })
+        .unwrap();
+        assert_eq!(data, b"abc");
+        assert_eq!(
+            blake3::Hash::from(hash).to_hex().as_str(),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        let _ = fs::remove_file(path);
+    }
+}
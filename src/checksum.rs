@@ -0,0 +1,76 @@
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+
+use crc::{Crc, CRC_32_ISO_HDLC};
+use pi_async_file::file::WriteOptions;
+
+use crate::SafeFile;
+
+// 标准CRC32算法，与zlib和gzip一致
+const CRC32: Crc<u32> = Crc::<u32>::new(&CRC_32_ISO_HDLC);
+
+/*
+* 计算指定数据的CRC32
+*/
+pub fn crc32(data: &[u8]) -> u32 {
+    CRC32.checksum(data)
+}
+
+impl SafeFile {
+    //读取指定位置和长度的数据，并校验数据的CRC32，不一致则返回错误
+    pub async fn read_verified(&self, pos: u64, len: usize, expected_crc: u32) -> Result<Vec<u8>> {
+        let data = self.read(pos, len).await?;
+        let crc = crc32(&data);
+        if crc != expected_crc {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Verify file failed, file: {:?}, pos: {}, len: {}, reason: crc mismatch, expected: {:#010x}, actual: {:#010x}",
+                    self.path(),
+                    pos,
+                    data.len(),
+                    expected_crc,
+                    crc
+                ),
+            ));
+        }
+        Ok(data)
+    }
+
+    //写入数据，返回写入的长度和已写入数据的CRC32
+    pub async fn write_crc(&self, pos: u64, buf: Arc<[u8]>, options: WriteOptions) -> Result<(usize, u32)> {
+        let r = self.write(pos, buf.clone(), options).await?;
+        Ok((r, crc32(&buf[..r.min(buf.len())])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+
+    #[test]
+    fn crc32_matches_zlib() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn read_verified_rejects_mismatch() {
+        let path = test_path("checksum");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let (len, crc) = file.write_crc(0, Arc::from(&b"payload"[..]), WriteOptions::Flush).await?;
+            let ok = file.read_verified(0, len, crc).await?;
+            let bad = file.read_verified(0, len, crc ^ 1).await.map_err(|e| e.kind());
+            Ok::<_, Error>((ok, bad))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"payload");
+        assert_eq!(r.1, Err(ErrorKind::InvalidData));
+        let _ = fs::remove_file(path);
+    }
+}
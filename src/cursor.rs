@@ -0,0 +1,94 @@
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+
+use crate::{offset_pos, SafeFile};
+
+/*
+* 安全文件的游标，内部维护读取位置，每次读取后前进，同一文件的多个游标位置相互独立
+*/
+#[derive(Debug, Clone)]
+pub struct FileCursor {
+    file: SafeFile,
+    pos: u64,
+}
+
+impl FileCursor {
+    // 获取当前读取位置
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 从当前位置开始异步读指定字节，并前进实际读取的字节数，读到文件尾则返回空
+    pub async fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        let data = self.file.read(self.pos, len).await?;
+        self.pos += data.len() as u64;
+        Ok(data)
+    }
+
+    // 异步定位到指定位置，返回新的位置，相对文件尾定位时以文件的当前长度为准
+    pub async fn seek(&mut self, position: SeekFrom) -> Result<u64> {
+        let pos = match position {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => offset_pos(self.file.len().await?, offset),
+            SeekFrom::Current(offset) => offset_pos(self.pos, offset),
+        };
+        match pos {
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Seek file failed, file: {:?}, reason: invalid seek to a negative or overflowing position", self.file.path()),
+            )),
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+        }
+    }
+}
+
+impl SafeFile {
+    //获取从文件头开始读取的游标
+    pub fn cursor(&self) -> FileCursor {
+        FileCursor {
+            file: self.clone(),
+            pos: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+
+    #[test]
+    fn cursors_advance_independently() {
+        let path = test_path("cursor");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let (mut a, mut b) = (file.cursor(), file.cursor());
+            let first = (a.read(3).await?, a.read(3).await?, b.read(2).await?);
+            // 向回移动后重新读取
+            let back = a.seek(SeekFrom::Current(-4)).await?;
+            let reread = a.read(4).await?;
+            let end = a.seek(SeekFrom::End(-2)).await?;
+            let tail = a.read(10).await?;
+            let invalid = b.seek(SeekFrom::Current(-5)).await.map_err(|e| e.kind());
+            Ok::<_, Error>((first, (back, reread), (end, tail), invalid, b.position()))
+        })
+        .unwrap();
+        assert_eq!(r.0, (b"012".to_vec(), b"345".to_vec(), b"01".to_vec()));
+        assert_eq!(r.1, (2, b"2345".to_vec()));
+        assert_eq!(r.2, (8, b"89".to_vec()));
+        assert_eq!(r.3, Err(ErrorKind::InvalidInput));
+        assert_eq!(r.4, 2);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io::{Error, ErrorKind, Result};
+use std::sync::Arc;
+
+use pi_async_file::file::WriteOptions;
+use pi_hash::XHashMap;
+
+use crate::SafeFile;
+
+// 差异比较时的块大小
+const DIFF_BLOCK_SIZE: usize = 4096;
+
+/*
+* 由旧文件生成新文件的差异操作
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffOp {
+    Copy { offset: u64, len: usize }, //复制旧文件指定位置和长度的数据
+    Insert(Vec<u8>),                  //插入旧文件中没有的数据
+}
+
+/*
+* 块的滚动校验和，窗口滑动一个字节时可以在常数时间内更新
+*/
+struct Rolling {
+    a: u32,
+    b: u32,
+}
+
+impl Rolling {
+    // 计算指定块的校验和
+    fn new(block: &[u8]) -> Self {
+        let len = block.len() as u32;
+        let mut r = Rolling { a: 0, b: 0 };
+        for (i, &x) in block.iter().enumerate() {
+            r.a = r.a.wrapping_add(x as u32);
+            r.b = r.b.wrapping_add((len - i as u32).wrapping_mul(x as u32));
+        }
+        r
+    }
+
+    // 窗口滑动一个字节，移出块头的字节，移入块尾的新字节
+    fn roll(&mut self, out: u8, inp: u8, len: usize) {
+        self.a = self.a.wrapping_sub(out as u32).wrapping_add(inp as u32);
+        self.b = self.b.wrapping_sub((len as u32).wrapping_mul(out as u32)).wrapping_add(self.a);
+    }
+
+    // 获取校验和
+    fn digest(&self) -> u32 {
+        (self.b << 16) | (self.a & 0xffff)
+    }
+}
+
+/*
+* 以固定大小的块和滚动校验和比较两个文件，返回由旧文件生成新文件的复制和插入操作
+* 校验和相同的块会再比较内容，因此操作总能准确重建新文件
+*/
+pub async fn diff(old: &SafeFile, new: &SafeFile) -> Result<Vec<DiffOp>> {
+    let old = old.read_to_end().await?;
+    let new = new.read_to_end().await?;
+
+    let mut blocks: XHashMap<u32, Vec<usize>> = XHashMap::default();
+    for (index, block) in old.chunks_exact(DIFF_BLOCK_SIZE).enumerate() {
+        blocks.entry(Rolling::new(block).digest()).or_default().push(index);
+    }
+
+    let mut ops = Vec::new();
+    let mut literal = 0;
+    let mut pos = 0;
+    let mut next = None; //上一个复制的块之后的块，优先匹配以合并复制
+    let mut rolling = None;
+    while pos + DIFF_BLOCK_SIZE <= new.len() {
+        let window = &new[pos..pos + DIFF_BLOCK_SIZE];
+        let sum = rolling.get_or_insert_with(|| Rolling::new(window));
+        let found = blocks.get(&sum.digest()).and_then(|indexes| {
+            let is_match = |index: &usize| &old[index * DIFF_BLOCK_SIZE..(index + 1) * DIFF_BLOCK_SIZE] == window;
+            match next {
+                Some(n) if literal == pos && indexes.contains(&n) && is_match(&n) => Some(n),
+                _ => indexes.iter().copied().find(is_match),
+            }
+        });
+        match found {
+            Some(index) => {
+                if literal < pos {
+                    ops.push(DiffOp::Insert(new[literal..pos].to_vec()));
+                }
+                let offset = (index * DIFF_BLOCK_SIZE) as u64;
+                match ops.last_mut() {
+                    // 与上一个复制连续，则合并
+                    Some(DiffOp::Copy { offset: last, len }) if literal == pos && *last + *len as u64 == offset => {
+                        *len += DIFF_BLOCK_SIZE;
+                    }
+                    _ => ops.push(DiffOp::Copy {
+                        offset,
+                        len: DIFF_BLOCK_SIZE,
+                    }),
+                }
+                pos += DIFF_BLOCK_SIZE;
+                literal = pos;
+                next = Some(index + 1);
+                rolling = None;
+            }
+            None => {
+                if pos + DIFF_BLOCK_SIZE < new.len() {
+                    sum.roll(new[pos], new[pos + DIFF_BLOCK_SIZE], DIFF_BLOCK_SIZE);
+                }
+                pos += 1;
+            }
+        }
+    }
+    if literal < new.len() {
+        ops.push(DiffOp::Insert(new[literal..].to_vec()));
+    }
+    Ok(ops)
+}
+
+/*
+* 按差异操作从旧文件生成新文件，写入输出文件并截断多余的数据，返回新文件的长度
+*/
+pub async fn apply(base: &SafeFile, ops: &[DiffOp], out: &SafeFile) -> Result<u64> {
+    let mut pos = 0u64;
+    for op in ops {
+        let data: Arc<[u8]> = match op {
+            DiffOp::Copy { offset, len } => {
+                let data = base.read(*offset, *len).await?;
+                if data.len() < *len {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Apply diff failed, file: {:?}, offset: {}, len: {}, reason: copy out of range", base.path(), offset, len),
+                    ));
+                }
+                Arc::from(data)
+            }
+            DiffOp::Insert(data) => Arc::from(data.as_slice()),
+        };
+        out.write(pos, data.clone(), WriteOptions::None).await?;
+        pos += data.len() as u64;
+    }
+    out.set_len(pos).await?;
+    Ok(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+
+    #[test]
+    fn apply_rebuilds_new_file() {
+        let (old, new, out) = (test_path("diff.old"), test_path("diff.new"), test_path("diff.out"));
+        let base: Vec<u8> = (0..DIFF_BLOCK_SIZE * 3).map(|i| (i * 7 % 251) as u8).collect();
+        let mut changed = base.clone();
+        changed[DIFF_BLOCK_SIZE + 5] ^= 1;
+        changed.splice(10..10, b"inserted".iter().copied());
+        changed.truncate(changed.len() - 100);
+        fs::write(&old, &base).unwrap();
+        fs::write(&new, &changed).unwrap();
+        fs::write(&out, vec![1; base.len() * 2]).unwrap();
+        let paths = (old.clone(), new.clone(), out.clone());
+        let r = block_on(async move {
+            let old = SafeFile::open(paths.0, AsyncFileOptions::OnlyRead).await?;
+            let new = SafeFile::open(paths.1, AsyncFileOptions::OnlyRead).await?;
+            let out = SafeFile::open(paths.2, AsyncFileOptions::ReadWrite).await?;
+            let ops = diff(&old, &new).await?;
+            let len = apply(&old, &ops, &out).await?;
+            Ok::<_, Error>((ops, len))
+        })
+        .unwrap();
+        // 未修改的块以复制操作重用
+        assert!(r.0.iter().any(|op| matches!(op, DiffOp::Copy { .. })));
+        assert_eq!(r.1, changed.len() as u64);
+        assert_eq!(fs::read(&out).unwrap(), changed);
+        for path in [old, new, out].iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn rolling_matches_fresh_digest() {
+        let data: Vec<u8> = (0..64u32).map(|i| (i * 31 % 256) as u8).collect();
+        let len = 16;
+        let mut rolling = Rolling::new(&data[..len]);
+        for start in 1..=data.len() - len {
+            rolling.roll(data[start - 1], data[start + len - 1], len);
+            assert_eq!(rolling.digest(), Rolling::new(&data[start..start + len]).digest());
+        }
+    }
+}
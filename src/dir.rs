@@ -0,0 +1,332 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, FileType};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::{copy_file, create_dir_all, run_sync};
+
+/*
+* 目录条目，文件类型在读取目录时获取，不跟随符号链接
+*/
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    path: PathBuf,
+    file_name: OsString,
+    file_type: FileType,
+}
+
+impl DirEntry {
+    // 获取条目的完整路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 获取条目的文件名
+    pub fn file_name(&self) -> &OsStr {
+        &self.file_name
+    }
+
+    // 获取条目的文件类型
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    // 是否是目录
+    pub fn is_dir(&self) -> bool {
+        self.file_type.is_dir()
+    }
+
+    // 是否是文件
+    pub fn is_file(&self) -> bool {
+        self.file_type.is_file()
+    }
+
+    // 是否是符号链接
+    pub fn is_symlink(&self) -> bool {
+        self.file_type.is_symlink()
+    }
+}
+
+/*
+* 异步读取目录的所有条目，按文件名排序
+*/
+pub async fn read_dir<P>(path: P) -> Result<Vec<DirEntry>>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || read_dir_sync(path.as_ref())).await
+}
+
+// 同步读取目录的所有条目，按文件名排序
+fn read_dir_sync(path: &Path) -> Result<Vec<DirEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        entries.push(DirEntry {
+            path: entry.path(),
+            file_name: entry.file_name(),
+            file_type: entry.file_type()?,
+        });
+    }
+    entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+    Ok(entries)
+}
+
+/*
+* 遍历目录的选项
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WalkOptions {
+    pub follow_links: bool,       //是否跟随指向目录的符号链接，跟随时会跳过已访问的目录以避免循环
+    pub max_depth: Option<usize>, //最大深度，根目录的直接子条目深度为1，None表示不限制
+}
+
+/*
+* 异步递归遍历目录，返回根目录下所有文件和目录的路径，不包括根目录，不跟随符号链接
+*/
+pub async fn walk_dir<P>(root: P) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    walk_dir_with(root, WalkOptions::default()).await
+}
+
+/*
+* 以指定选项异步递归遍历目录，返回根目录下所有文件和目录的路径，不包括根目录
+*/
+pub async fn walk_dir_with<P>(root: P, options: WalkOptions) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || {
+        let root = root.as_ref();
+        let mut visited = HashSet::new();
+        if options.follow_links {
+            visited.insert(fs::canonicalize(root)?);
+        }
+        let mut paths = Vec::new();
+        walk_dir_sync(root, 1, &options, &mut visited, &mut paths)?;
+        Ok(paths)
+    })
+    .await
+}
+
+// 同步递归遍历目录
+fn walk_dir_sync(
+    dir: &Path,
+    depth: usize,
+    options: &WalkOptions,
+    visited: &mut HashSet<PathBuf>,
+    paths: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in read_dir_sync(dir)? {
+        let is_dir = if entry.is_symlink() {
+            options.follow_links && fs::metadata(&entry.path).map(|m| m.is_dir()).unwrap_or(false)
+        } else {
+            entry.is_dir()
+        };
+        paths.push(entry.path.clone());
+        if !is_dir || options.max_depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+        if options.follow_links && !visited.insert(fs::canonicalize(&entry.path)?) {
+            // 已访问过的目录，跳过以避免循环
+            continue;
+        }
+        walk_dir_sync(&entry.path, depth + 1, options, visited, paths)?;
+    }
+    Ok(())
+}
+
+/*
+* 异步递归复制目录，在目标目录下重建源目录的结构并复制所有文件，指向目录的符号链接不跟随，重建为指向同一位置的符号链接
+* 目标文件已存在时，overwrite为true则覆盖，否则返回AlreadyExists错误
+*/
+pub async fn copy_dir<P>(from: P, to: P, overwrite: bool) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let from = from.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+    let (src, dst) = (from.clone(), to.clone());
+    let nested = run_sync(move || Ok(canonicalize_existing(&dst)?.starts_with(fs::canonicalize(src)?))).await?;
+    if nested {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Copy dir failed, from: {:?}, to: {:?}, reason: destination inside source", from, to),
+        ));
+    }
+
+    create_dir_all(to.clone()).await?;
+
+    let mut dirs = vec![(from, to)];
+    while let Some((src, dst)) = dirs.pop() {
+        for entry in read_dir(src).await? {
+            let target = dst.join(entry.file_name());
+            if entry.is_dir() {
+                create_dir_all(target.clone()).await?;
+                dirs.push((entry.path, target));
+                continue;
+            }
+            if !overwrite {
+                let check = target.clone();
+                if run_sync(move || Ok(fs::symlink_metadata(check).is_ok())).await? {
+                    return Err(Error::new(
+                        ErrorKind::AlreadyExists,
+                        format!("Copy dir failed, from: {:?}, to: {:?}, reason: destination already exists", entry.path, target),
+                    ));
+                }
+            }
+            if entry.is_symlink() {
+                let link = entry.path.clone();
+                if run_sync(move || Ok(fs::metadata(link).map(|m| m.is_dir()).unwrap_or(false))).await? {
+                    run_sync(move || copy_link(&entry.path, &target)).await?;
+                    continue;
+                }
+            }
+            copy_file(entry.path, target).await?;
+        }
+    }
+    Ok(())
+}
+
+// 在目标路径重建指向与源符号链接相同位置的符号链接，目标路径已存在时先移除
+fn copy_link(link: &Path, target: &Path) -> Result<()> {
+    let dest = fs::read_link(link)?;
+    if fs::symlink_metadata(target).is_ok() {
+        fs::remove_file(target)?;
+    }
+    #[cfg(unix)]
+    return std::os::unix::fs::symlink(dest, target);
+    #[cfg(windows)]
+    return std::os::windows::fs::symlink_dir(dest, target);
+}
+
+// 规范化可能尚不存在的路径，只规范化已存在的最长前缀，再拼接剩余部分
+fn canonicalize_existing(path: &Path) -> Result<PathBuf> {
+    let mut rest = Vec::new();
+    let mut cur = path;
+    loop {
+        match fs::canonicalize(cur) {
+            Ok(mut r) => {
+                r.extend(rest.iter().rev());
+                return Ok(r);
+            }
+            Err(e) => match (cur.parent(), cur.file_name()) {
+                (Some(parent), Some(name)) => {
+                    rest.push(name.to_os_string());
+                    cur = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+                }
+                _ => return Err(e),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+
+    #[test]
+    fn read_dir_lists_sorted_entries() {
+        let root = test_path("read_dir");
+        fs::create_dir_all(root.join("b_dir")).unwrap();
+        fs::write(root.join("c_file"), b"c").unwrap();
+        fs::write(root.join("a_file"), b"a").unwrap();
+        let copy = root.clone();
+        let r = block_on(async move { read_dir(copy).await }).unwrap();
+        let names = r.iter().map(|e| e.file_name().to_string_lossy().into_owned()).collect::<Vec<_>>();
+        assert_eq!(names, ["a_file", "b_dir", "c_file"]);
+        assert_eq!(r.iter().map(|e| e.is_dir()).collect::<Vec<_>>(), [false, true, false]);
+        assert_eq!(r.iter().map(|e| e.is_file()).collect::<Vec<_>>(), [true, false, true]);
+        assert_eq!(r[1].path(), root.join("b_dir"));
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn walk_dir_visits_each_path_once() {
+        let root = test_path("walk_dir");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::create_dir_all(root.join("d")).unwrap();
+        fs::write(root.join("top"), b"1").unwrap();
+        fs::write(root.join("a/b/mid"), b"2").unwrap();
+        fs::write(root.join("a/b/c/leaf"), b"3").unwrap();
+        // 指向上级目录的符号链接，跟随时会形成循环
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(root.join("a"), root.join("a/b/loop")).unwrap();
+        let copy = root.clone();
+        let r = block_on(async move {
+            let all = walk_dir(copy.clone()).await?;
+            let shallow = walk_dir_with(copy.clone(), WalkOptions { max_depth: Some(1), ..Default::default() }).await?;
+            let follow = walk_dir_with(copy, WalkOptions { follow_links: true, max_depth: None }).await?;
+            Ok::<_, std::io::Error>((all, shallow, follow))
+        })
+        .unwrap();
+        let mut expect = vec!["a", "a/b", "a/b/c", "a/b/c/leaf", "a/b/mid", "d", "top"];
+        if cfg!(unix) {
+            expect.push("a/b/loop");
+        }
+        let mut expect = expect.into_iter().map(|p| root.join(p)).collect::<Vec<_>>();
+        expect.sort();
+        for paths in [&r.0, &r.2].iter() {
+            let mut paths = paths.to_vec();
+            paths.sort();
+            assert_eq!(paths, expect);
+        }
+        assert_eq!(r.1, ["a", "d", "top"].iter().map(|p| root.join(p)).collect::<Vec<_>>());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn copy_dir_recreates_tree() {
+        let root = test_path("copy_dir");
+        fs::create_dir_all(root.join("src/a/b")).unwrap();
+        fs::create_dir_all(root.join("src/empty")).unwrap();
+        fs::write(root.join("src/top"), b"top").unwrap();
+        fs::write(root.join("src/a/b/deep"), b"deep").unwrap();
+        let copy = root.clone();
+        let r = block_on(async move {
+            let (src, dst) = (copy.join("src"), copy.join("dst"));
+            copy_dir(src.clone(), dst.clone(), false).await?;
+            let conflict = copy_dir(src.clone(), dst.clone(), false).await.map_err(|e| e.kind());
+            fs::write(src.join("top"), b"changed")?;
+            copy_dir(src.clone(), dst.clone(), true).await?;
+            let nested = copy_dir(src.clone(), src.join("a/nested"), true).await.map_err(|e| e.kind());
+            Ok::<_, std::io::Error>((conflict, nested))
+        })
+        .unwrap();
+        assert_eq!(r, (Err(ErrorKind::AlreadyExists), Err(ErrorKind::InvalidInput)));
+        assert_eq!(fs::read(root.join("dst/top")).unwrap(), b"changed");
+        assert_eq!(fs::read(root.join("dst/a/b/deep")).unwrap(), b"deep");
+        assert!(root.join("dst/empty").is_dir());
+        assert!(!root.join("src/a/nested").exists());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_dir_recreates_dir_links() {
+        let root = test_path("copy_dir_links");
+        fs::create_dir_all(root.join("src/real")).unwrap();
+        fs::write(root.join("src/real/file"), b"real").unwrap();
+        // 指向目录内的目录和指向自身的符号链接，跟随会重复复制或陷入循环
+        std::os::unix::fs::symlink("real", root.join("src/inner")).unwrap();
+        std::os::unix::fs::symlink(root.join("src"), root.join("src/self")).unwrap();
+        let copy = root.clone();
+        let r = block_on(async move {
+            let (src, dst) = (copy.join("src"), copy.join("dst"));
+            copy_dir(src.clone(), dst.clone(), false).await?;
+            let conflict = copy_dir(src.clone(), dst.clone(), false).await.map_err(|e| e.kind());
+            copy_dir(src, dst, true).await?;
+            Ok::<_, std::io::Error>(conflict)
+        })
+        .unwrap();
+        assert_eq!(r, Err(ErrorKind::AlreadyExists));
+        assert_eq!(fs::read_link(root.join("dst/inner")).unwrap(), Path::new("real"));
+        assert_eq!(fs::read_link(root.join("dst/self")).unwrap(), root.join("src"));
+        assert_eq!(fs::read(root.join("dst/inner/file")).unwrap(), b"real");
+        let _ = fs::remove_dir_all(root);
+    }
+}
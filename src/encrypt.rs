@@ -0,0 +1,314 @@
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use async_lock::Mutex;
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+
+use crate::{LockType, SafeFile};
+
+// 每块明文的字节数
+const ENCRYPT_CHUNK_SIZE: usize = 4096;
+// 每块的随机数字节数
+const NONCE_LEN: usize = 12;
+// 每块的认证标签字节数
+const TAG_LEN: usize = 16;
+// 每块在文件中占用的字节数
+const SLOT_SIZE: usize = NONCE_LEN + ENCRYPT_CHUNK_SIZE + TAG_LEN;
+
+/*
+* 以AES-256-GCM加密存储的文件，明文按ENCRYPT_CHUNK_SIZE分块，每块以随机数、密文、认证标签依次存储，只有最后一块可以不足一块
+* 每块使用独立的随机数，并以块序号和是否为最后一块作为附加数据认证，随机读只需要读取和解密涉及的块及最后一块
+* 块被篡改或移动、文件在块边界被截断都会使认证失败，只有长度为0的文件视为空文件
+* 同一写入器的写入按块读取、修改、重新加密后写入，互相串行，同一文件的多个写入器之间不保证串行
+*/
+pub struct EncryptedFile {
+    file: SafeFile,
+    cipher: Aes256Gcm,
+    lock: Mutex<()>, //串行化写入
+}
+
+impl Debug for EncryptedFile {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("EncryptedFile").field("file", &self.file).finish()
+    }
+}
+
+impl EncryptedFile {
+    // 以读写方式打开指定路径的加密文件，密钥为32字节的AES-256密钥
+    pub async fn open<P>(path: P, key: &[u8]) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let file = SafeFile::open(path, AsyncFileOptions::ReadWrite).await?;
+        EncryptedFile::new(file, key)
+    }
+
+    // 以指定密钥读写安全文件，密钥为32字节的AES-256密钥，截断写和追加写文件不支持
+    pub fn new(file: SafeFile, key: &[u8]) -> Result<Self> {
+        if matches!(file.0.lock, LockType::Lock(_)) || file.is_append() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encrypt file failed, file: {:?}, reason: truncate or append write file", file.path()),
+            ));
+        }
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Encrypt file failed, file: {:?}, reason: invalid key length {}", file.path(), key.len()),
+            )
+        })?;
+        Ok(EncryptedFile {
+            file,
+            cipher,
+            lock: Mutex::new(()),
+        })
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 异步获取明文的长度，会认证最后一块，文件被截断则返回InvalidData错误
+    pub async fn len(&self) -> Result<u64> {
+        let size = self.file.len().await?;
+        if size == 0 {
+            return Ok(0);
+        }
+        let rest = (size % SLOT_SIZE as u64) as usize;
+        if rest != 0 && rest <= NONCE_LEN + TAG_LEN {
+            return Err(self.corrupted(size / SLOT_SIZE as u64, "truncated chunk"));
+        }
+        let len = size / SLOT_SIZE as u64 * ENCRYPT_CHUNK_SIZE as u64 + rest.saturating_sub(NONCE_LEN + TAG_LEN) as u64;
+        let last = (len - 1) / ENCRYPT_CHUNK_SIZE as u64;
+        self.read_chunks(last, last, last).await?;
+        Ok(len)
+    }
+
+    // 明文是否为空
+    pub async fn is_empty(&self) -> Result<bool> {
+        Ok(self.len().await? == 0)
+    }
+
+    // 从明文的指定位置开始异步读指定字节，只读取和解密涉及的块，超出明文尾的部分截断，块认证失败则返回InvalidData错误
+    pub async fn read(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let size = self.len().await?;
+        if len == 0 || pos >= size {
+            return Ok(Vec::new());
+        }
+        let end = pos.saturating_add(len as u64).min(size);
+        let first = pos / ENCRYPT_CHUNK_SIZE as u64;
+        let last = (end - 1) / ENCRYPT_CHUNK_SIZE as u64;
+        let data = self.read_chunks(first, last, (size - 1) / ENCRYPT_CHUNK_SIZE as u64).await?;
+        let base = first * ENCRYPT_CHUNK_SIZE as u64;
+        Ok(data[(pos - base) as usize..(end - base) as usize].to_vec())
+    }
+
+    // 从明文的指定位置开始异步写入指定数据，重新加密涉及的块，写入位置超过明文尾则中间补零
+    pub async fn write(&self, pos: u64, data: &[u8]) -> Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        let _guard = self.lock.lock().await;
+        let size = self.len().await?;
+        let end = pos + data.len() as u64;
+        // 加长明文时从原来的最后一块开始重新加密，使其不再标记为最后一块，并补零到写入位置，空文件从第一块开始补零
+        let start = if end > size { pos.min(size.saturating_sub(1)) } else { pos };
+        let first = start / ENCRYPT_CHUNK_SIZE as u64;
+        let last = (end - 1) / ENCRYPT_CHUNK_SIZE as u64;
+
+        // 涉及的块中已有的明文，保留写入范围外的部分
+        let mut plain = if start < size {
+            let tail = (size - 1) / ENCRYPT_CHUNK_SIZE as u64;
+            self.read_chunks(first, last.min(tail), tail).await?
+        } else {
+            Vec::new()
+        };
+        let base = first * ENCRYPT_CHUNK_SIZE as u64;
+        if plain.len() < (end - base) as usize {
+            plain.resize((end - base) as usize, 0);
+        }
+        plain[(pos - base) as usize..(end - base) as usize].copy_from_slice(data);
+
+        let tail = (size.max(end) - 1) / ENCRYPT_CHUNK_SIZE as u64;
+        let mut out = Vec::with_capacity(plain.len() / ENCRYPT_CHUNK_SIZE * SLOT_SIZE + SLOT_SIZE);
+        for (offset, chunk) in plain.chunks(ENCRYPT_CHUNK_SIZE).enumerate() {
+            let index = first + offset as u64;
+            out.extend_from_slice(&self.encrypt_chunk(index, index == tail, chunk)?);
+        }
+        let mut pos = first * SLOT_SIZE as u64;
+        let mut out = &out[..];
+        while !out.is_empty() {
+            let r = self.file.write(pos, Arc::from(out), WriteOptions::None).await?;
+            if r == 0 {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    format!("Encrypt file failed, file: {:?}, pos: {}, reason: write zero", self.file.path(), pos),
+                ));
+            }
+            pos += r as u64;
+            out = &out[r..];
+        }
+        Ok(())
+    }
+
+    // 读取并解密指定范围的块，返回连续的明文，tail为明文最后一块的序号
+    async fn read_chunks(&self, first: u64, last: u64, tail: u64) -> Result<Vec<u8>> {
+        let raw = self
+            .file
+            .read(first * SLOT_SIZE as u64, (last - first + 1) as usize * SLOT_SIZE)
+            .await?;
+        let mut data = Vec::with_capacity(raw.len());
+        for (offset, slot) in raw.chunks(SLOT_SIZE).enumerate() {
+            let index = first + offset as u64;
+            data.extend_from_slice(&self.decrypt_chunk(index, index == tail, slot)?);
+        }
+        Ok(data)
+    }
+
+    // 以新的随机数加密指定块的明文，返回随机数和带认证标签的密文
+    fn encrypt_chunk(&self, index: u64, last: bool, chunk: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let aad = chunk_aad(index, last);
+        let data = self
+            .cipher
+            .encrypt(&nonce, Payload { msg: chunk, aad: &aad })
+            .map_err(|_| {
+                Error::other(format!(
+                    "Encrypt file failed, file: {:?}, chunk: {}, reason: encrypt error",
+                    self.file.path(),
+                    index
+                ))
+            })?;
+        let mut slot = Vec::with_capacity(NONCE_LEN + data.len());
+        slot.extend_from_slice(nonce.as_slice());
+        slot.extend_from_slice(&data);
+        Ok(slot)
+    }
+
+    // 解密并认证指定块，块不完整或认证失败则返回InvalidData错误
+    fn decrypt_chunk(&self, index: u64, last: bool, slot: &[u8]) -> Result<Vec<u8>> {
+        if slot.len() <= NONCE_LEN + TAG_LEN || (!last && slot.len() != SLOT_SIZE) {
+            return Err(self.corrupted(index, "truncated chunk"));
+        }
+        let aad = chunk_aad(index, last);
+        self.cipher
+            .decrypt(
+                Nonce::from_slice(&slot[..NONCE_LEN]),
+                Payload {
+                    msg: &slot[NONCE_LEN..],
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| self.corrupted(index, "authentication failed"))
+    }
+
+    // 构建块损坏的错误
+    fn corrupted(&self, index: u64, reason: &str) -> Error {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Decrypt file failed, file: {:?}, chunk: {}, reason: {}", self.file.path(), index, reason),
+        )
+    }
+}
+
+// 构建块的附加数据，为块序号及是否为最后一块
+fn chunk_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0; 9];
+    aad[..8].copy_from_slice(&index.to_le_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use std::fs;
+
+    const KEY: [u8; 32] = [7; 32];
+
+    #[test]
+    fn writes_span_chunks_and_fill_gaps() {
+        let path = test_path("encrypt_gap");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = EncryptedFile::open(copy, &KEY).await?;
+            // 空文件中超过一块的位置写入，之前的部分补零
+            let pos = ENCRYPT_CHUNK_SIZE as u64 + 100;
+            file.write(pos, b"tail").await?;
+            let first = (file.len().await?, file.read(0, 8).await?, file.read(pos - 2, 8).await?);
+            // 跨块覆盖写入
+            let data = vec![1u8; ENCRYPT_CHUNK_SIZE];
+            file.write(10, &data).await?;
+            let second = (
+                file.len().await?,
+                file.read(8, 4).await?,
+                file.read(ENCRYPT_CHUNK_SIZE as u64 + 8, 4).await?,
+            );
+            Ok::<_, Error>((first, second, file.read(pos, 4).await?))
+        })
+        .unwrap();
+        let size = ENCRYPT_CHUNK_SIZE as u64 + 104;
+        assert_eq!(r.0, (size, vec![0; 8], b"\0\0tail".to_vec()));
+        assert_eq!(r.1, (size, vec![0, 0, 1, 1], vec![1, 1, 0, 0]));
+        assert_eq!(r.2, b"tail");
+        // 文件中不包含明文
+        let raw = fs::read(&path).unwrap();
+        assert_eq!(raw.len(), SLOT_SIZE + NONCE_LEN + 104 + TAG_LEN);
+        assert!(!raw.windows(4).any(|w| w == b"tail"));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn tampered_chunk_fails_authentication() {
+        let path = test_path("encrypt_tamper");
+        let copy = path.clone();
+        block_on(async move {
+            let file = EncryptedFile::open(copy, &KEY).await?;
+            file.write(0, b"secret data").await
+        })
+        .unwrap();
+        let mut raw = fs::read(&path).unwrap();
+        raw[NONCE_LEN] ^= 1;
+        fs::write(&path, raw).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = EncryptedFile::open(copy, &KEY).await?;
+            let short = EncryptedFile::new(file.file().clone(), &KEY[..16])
+                .map(|_| ())
+                .map_err(|e| e.kind());
+            Ok::<_, Error>((file.read(0, 6).await.map_err(|e| e.kind()), short))
+        })
+        .unwrap();
+        assert_eq!(r.0, Err(ErrorKind::InvalidData));
+        assert_eq!(r.1, Err(ErrorKind::InvalidInput));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncation_at_chunk_boundary_is_detected() {
+        let path = test_path("encrypt_truncate");
+        let copy = path.clone();
+        block_on(async move {
+            let file = EncryptedFile::open(copy, &KEY).await?;
+            file.write(0, &vec![3u8; ENCRYPT_CHUNK_SIZE * 2 + 10]).await
+        })
+        .unwrap();
+        // 在块边界截断，剩余的块都是完整的块
+        let raw = fs::read(&path).unwrap();
+        fs::write(&path, &raw[..SLOT_SIZE * 2]).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = EncryptedFile::open(copy, &KEY).await?;
+            Ok::<_, Error>((file.len().await.map_err(|e| e.kind()), file.read(0, 8).await.map_err(|e| e.kind())))
+        })
+        .unwrap();
+        assert_eq!(r, (Err(ErrorKind::InvalidData), Err(ErrorKind::InvalidData)));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,201 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{Error as IoError, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/*
+* 文件操作的结果
+*/
+pub type FileResult<T> = std::result::Result<T, FileError>;
+
+/*
+* 文件操作的错误，尽量携带出错的文件路径
+*/
+#[derive(Debug)]
+pub enum FileError {
+    // 底层IO错误
+    Io { path: Option<PathBuf>, err: IoError },
+    // 路径已以不兼容的选项打开
+    Incompatible { path: PathBuf },
+    // 文件系统空间不足
+    OutOfSpace { path: PathBuf },
+    // 缓冲区版本冲突
+    VersionConflict { path: PathBuf, expected: u64, current: u64 },
+    // 文件在指定时间后已被修改
+    Modified { path: PathBuf, since: SystemTime, modified: SystemTime },
+    // 数据编码或解码失败
+    Codec { path: PathBuf, reason: String },
+    // 进程或系统打开的文件数已达上限
+    TooManyOpenFiles { path: PathBuf },
+}
+
+impl FileError {
+    // 构建指定路径的IO错误，空间不足的错误构建为OutOfSpace错误，打开的文件数已达上限的错误构建为TooManyOpenFiles错误
+    pub fn io<P: AsRef<Path>>(path: P, err: IoError) -> Self {
+        if err.kind() == ErrorKind::StorageFull {
+            return FileError::OutOfSpace {
+                path: path.as_ref().to_path_buf(),
+            };
+        }
+        if is_too_many_open_files(&err) {
+            return FileError::TooManyOpenFiles {
+                path: path.as_ref().to_path_buf(),
+            };
+        }
+        FileError::Io {
+            path: Some(path.as_ref().to_path_buf()),
+            err,
+        }
+    }
+
+    // 获取出错的文件路径
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            FileError::Io { path, .. } => path.as_deref(),
+            FileError::Incompatible { path } => Some(path),
+            FileError::OutOfSpace { path } => Some(path),
+            FileError::VersionConflict { path, .. } => Some(path),
+            FileError::Modified { path, .. } => Some(path),
+            FileError::Codec { path, .. } => Some(path),
+            FileError::TooManyOpenFiles { path } => Some(path),
+        }
+    }
+
+    // 获取对应的IO错误类型
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            FileError::Io { err, .. } => err.kind(),
+            FileError::Incompatible { .. } => ErrorKind::AlreadyExists,
+            FileError::OutOfSpace { .. } => ErrorKind::StorageFull,
+            FileError::VersionConflict { .. } => ErrorKind::Other,
+            FileError::Modified { .. } => ErrorKind::Other,
+            FileError::Codec { .. } => ErrorKind::InvalidData,
+            FileError::TooManyOpenFiles { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+impl Display for FileError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match self {
+            FileError::Io { path: Some(path), err } => {
+                write!(f, "File io failed, file: {:?}, reason: {}", path, err)
+            }
+            FileError::Io { path: None, err } => write!(f, "File io failed, reason: {}", err),
+            FileError::Incompatible { path } => write!(
+                f,
+                "Open file failed, file: {:?}, reason: already open with incompatible options",
+                path
+            ),
+            FileError::OutOfSpace { path } => {
+                write!(f, "Write file failed, file: {:?}, reason: out of space", path)
+            }
+            FileError::VersionConflict {
+                path,
+                expected,
+                current,
+            } => write!(
+                f,
+                "Write file failed, file: {:?}, expected version: {}, current version: {}, reason: version conflict",
+                path, expected, current
+            ),
+            FileError::Modified { path, since, modified } => write!(
+                f,
+                "Write file failed, file: {:?}, since: {:?}, modified: {:?}, reason: modified since",
+                path, since, modified
+            ),
+            FileError::Codec { path, reason } => {
+                write!(f, "Codec file failed, file: {:?}, reason: {}", path, reason)
+            }
+            FileError::TooManyOpenFiles { path } => {
+                write!(f, "Open file failed, file: {:?}, reason: too many open files", path)
+            }
+        }
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FileError::Io { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<IoError> for FileError {
+    fn from(err: IoError) -> Self {
+        FileError::Io { path: None, err }
+    }
+}
+
+impl From<FileError> for IoError {
+    fn from(err: FileError) -> Self {
+        match err {
+            FileError::Io { path: None, err } => err,
+            err => IoError::new(err.kind(), err),
+        }
+    }
+}
+
+// 是否是进程或系统打开的文件数已达上限的错误
+#[cfg(unix)]
+pub(crate) fn is_too_many_open_files(err: &IoError) -> bool {
+    matches!(err.raw_os_error(), Some(libc::EMFILE) | Some(libc::ENFILE))
+}
+
+// 是否是进程或系统打开的文件数已达上限的错误
+#[cfg(not(unix))]
+pub(crate) fn is_too_many_open_files(err: &IoError) -> bool {
+    // ERROR_TOO_MANY_OPEN_FILES
+    err.raw_os_error() == Some(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_error_keeps_path_and_kind() {
+        let path = Path::new("a.txt");
+        let err = FileError::io(path, IoError::from(ErrorKind::NotFound));
+        assert_eq!(err.kind(), ErrorKind::NotFound);
+        assert_eq!(err.path(), Some(path));
+        assert!(err.to_string().contains("a.txt"));
+        assert!(err.source().is_some());
+    }
+
+    #[test]
+    fn converts_to_io_error() {
+        let err = IoError::from(FileError::Incompatible { path: "a.txt".into() });
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert!(err.to_string().contains("incompatible"));
+        // 没有路径的IO错误原样转换
+        let err = IoError::from(FileError::from(IoError::from_raw_os_error(2)));
+        assert_eq!(err.raw_os_error(), Some(2));
+    }
+
+    #[test]
+    fn storage_full_becomes_out_of_space() {
+        let path = Path::new("a.txt");
+        let err = FileError::io(path, IoError::from(ErrorKind::StorageFull));
+        assert!(matches!(err, FileError::OutOfSpace { .. }));
+        assert_eq!(err.kind(), ErrorKind::StorageFull);
+        assert_eq!(err.path(), Some(path));
+        assert!(err.to_string().ends_with("out of space"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn descriptor_limit_becomes_too_many_open_files() {
+        let path = Path::new("a.txt");
+        for code in [libc::EMFILE, libc::ENFILE] {
+            let err = FileError::io(path, IoError::from_raw_os_error(code));
+            assert!(matches!(err, FileError::TooManyOpenFiles { .. }));
+            assert!(err.to_string().ends_with("too many open files"));
+        }
+        let err = FileError::io(path, IoError::from_raw_os_error(libc::ENOENT));
+        assert!(matches!(err, FileError::Io { .. }));
+    }
+}
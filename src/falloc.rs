@@ -0,0 +1,129 @@
+use std::fs::File;
+use std::io::Result;
+
+// 为文件从头开始预分配指定长度的空间，文件不足该长度则加长，加长部分读为零，返回文件的新长度
+// 文件系统不支持预分配时改为设置文件长度，不预留磁盘空间
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn preallocate(file: &File, len: u64) -> Result<u64> {
+    use std::convert::TryFrom;
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::io::AsRawFd;
+
+    let size = libc::off_t::try_from(len).map_err(|_| Error::new(ErrorKind::InvalidInput, "len overflow"))?;
+    loop {
+        if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, size) } == 0 {
+            return Ok(file.metadata()?.len());
+        }
+        let e = Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => return extend(file, len),
+            _ => return Err(e),
+        }
+    }
+}
+
+// 其它平台设置文件长度，Windows上相当于SetEndOfFile，不预留磁盘空间
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn preallocate(file: &File, len: u64) -> Result<u64> {
+    extend(file, len)
+}
+
+// 文件不足指定长度则加长，返回文件的新长度
+fn extend(file: &File, len: u64) -> Result<u64> {
+    let size = file.metadata()?.len();
+    if size >= len {
+        return Ok(size);
+    }
+    file.set_len(len)?;
+    Ok(len)
+}
+
+// 释放文件指定范围占用的磁盘空间，不改变文件长度，范围内读为零，范围超出文件尾的部分忽略，返回实际释放的长度
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn punch_hole(file: &File, offset: u64, len: u64) -> Result<u64> {
+    use std::convert::TryFrom;
+    use std::io::{Error, ErrorKind};
+    use std::os::unix::io::AsRawFd;
+
+    let len = file.metadata()?.len().saturating_sub(offset).min(len);
+    if len == 0 {
+        return Ok(0);
+    }
+    let (start, size) = match (libc::off_t::try_from(offset), libc::off_t::try_from(len)) {
+        (Ok(start), Ok(size)) => (start, size),
+        _ => return Err(Error::new(ErrorKind::InvalidInput, "range overflow")),
+    };
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    loop {
+        if unsafe { libc::fallocate(file.as_raw_fd(), mode, start, size) } == 0 {
+            return Ok(len);
+        }
+        let e = Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::EINTR) => continue,
+            Some(libc::EOPNOTSUPP) | Some(libc::ENOSYS) => {
+                return Err(Error::new(ErrorKind::Unsupported, "punch hole unsupported by file system"))
+            }
+            _ => return Err(e),
+        }
+    }
+}
+
+// 其它平台暂不支持释放文件中间的空间
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub(crate) fn punch_hole(_file: &File, _offset: u64, _len: u64) -> Result<u64> {
+    use std::io::{Error, ErrorKind};
+
+    Err(Error::new(ErrorKind::Unsupported, "punch hole unsupported on this platform"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_path;
+    use std::fs::{self, OpenOptions};
+
+    #[test]
+    fn preallocate_grows_but_never_shrinks() {
+        let path = test_path("falloc_grow");
+        fs::write(&path, b"abc").unwrap();
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        assert_eq!(preallocate(&file, 8).unwrap(), 8);
+        assert_eq!(preallocate(&file, 2).unwrap(), 8);
+        assert_eq!(fs::read(&path).unwrap(), b"abc\0\0\0\0\0");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn extend_keeps_longer_files() {
+        let path = test_path("falloc_extend");
+        fs::write(&path, b"abcdef").unwrap();
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        assert_eq!(extend(&file, 4).unwrap(), 6);
+        assert_eq!(extend(&file, 10).unwrap(), 10);
+        assert_eq!(fs::read(&path).unwrap(), b"abcdef\0\0\0\0");
+        let _ = fs::remove_file(path);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn punch_hole_zeros_range_and_keeps_size() {
+        let path = test_path("falloc_punch");
+        fs::write(&path, vec![7u8; 8192]).unwrap();
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        match punch_hole(&file, 4096, 8192) {
+            // 超出文件尾的部分忽略
+            Ok(len) => assert_eq!(len, 4096),
+            // 临时目录所在的文件系统不支持时跳过
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return,
+            Err(e) => panic!("{:?}", e),
+        }
+        assert_eq!(punch_hole(&file, 9000, 10).unwrap(), 0);
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 8192);
+        assert!(data[..4096].iter().all(|b| *b == 7));
+        assert!(data[4096..].iter().all(|b| *b == 0));
+        let _ = fs::remove_file(path);
+    }
+}
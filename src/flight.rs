@@ -0,0 +1,145 @@
+use std::future::Future;
+use std::io::Error;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use pi_async_rt::lock::spin_lock::SpinLock;
+
+// 共享读的结果
+pub(crate) type FlightResult = std::result::Result<Arc<[u8]>, Arc<Error>>;
+
+/*
+* 多个任务共享的一次读，由发起读的任务执行，读完成后唤醒所有等待的任务
+* 底层文件的读写在被重复轮询时会重复发起，因此只在结果未就绪时登记唤醒器，每个唤醒器最多唤醒一次，等待结束后不会再被唤醒
+*/
+#[derive(Clone)]
+pub(crate) struct ReadFlight(Arc<SpinLock<FlightState>>);
+
+// 共享读的状态
+struct FlightState {
+    result: Option<Option<FlightResult>>, //读的结果，内部为None表示发起读的任务已放弃
+    wakers: Vec<Option<Waker>>,           //等待的任务的唤醒器，已结束等待的为None
+}
+
+impl ReadFlight {
+    // 发起一次共享读，返回共享读和设置结果的发送者
+    pub(crate) fn new() -> (Self, FlightSender) {
+        let flight = ReadFlight(Arc::new(SpinLock::new(FlightState {
+            result: None,
+            wakers: Vec::new(),
+        })));
+        (flight.clone(), FlightSender(flight))
+    }
+
+    // 是否是同一次读
+    pub(crate) fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    // 等待读的结果，发起读的任务已放弃则返回None
+    pub(crate) fn wait(&self) -> FlightWait {
+        FlightWait {
+            flight: self.clone(),
+            key: None,
+        }
+    }
+
+    // 设置读的结果并唤醒所有等待的任务，已有结果则忽略
+    fn finish(&self, r: Option<FlightResult>) {
+        let wakers = {
+            let mut state = self.0.lock();
+            if state.result.is_some() {
+                return;
+            }
+            state.result = Some(r);
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers.into_iter().flatten() {
+            waker.wake();
+        }
+    }
+}
+
+/*
+* 复制共享读的错误，保留错误类型和消息，系统错误保留错误码
+*/
+pub(crate) fn copy_error(e: &Error) -> Error {
+    match e.raw_os_error() {
+        Some(code) => Error::from_raw_os_error(code),
+        None => Error::new(e.kind(), e.to_string()),
+    }
+}
+
+/*
+* 设置共享读结果的发送者，未设置结果就释放表示发起读的任务已放弃，等待的任务需要重新发起读
+*/
+pub(crate) struct FlightSender(ReadFlight);
+
+impl FlightSender {
+    // 设置读的结果
+    pub(crate) fn send(self, r: FlightResult) {
+        self.0.finish(Some(r));
+    }
+}
+
+impl Drop for FlightSender {
+    fn drop(&mut self) {
+        self.0.finish(None);
+    }
+}
+
+/*
+* 等待共享读结果的任务
+*/
+pub(crate) struct FlightWait {
+    flight: ReadFlight,
+    key: Option<usize>, //登记的唤醒器的序号
+}
+
+impl Future for FlightWait {
+    type Output = Option<FlightResult>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let flight = self.flight.clone();
+        let mut state = flight.0.lock();
+        if let Some(r) = &state.result {
+            return Poll::Ready(r.clone());
+        }
+        match self.key {
+            Some(key) => state.wakers[key] = Some(cx.waker().clone()),
+            None => {
+                state.wakers.push(Some(cx.waker().clone()));
+                self.key = Some(state.wakers.len() - 1);
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for FlightWait {
+    fn drop(&mut self) {
+        // 放弃等待时注销唤醒器，避免读完成后唤醒已在等待其它事件的任务
+        if let Some(key) = self.key {
+            let mut state = self.flight.0.lock();
+            if let Some(waker) = state.wakers.get_mut(key) {
+                *waker = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::ErrorKind;
+
+    #[test]
+    fn copy_error_keeps_code_and_message() {
+        let os = copy_error(&Error::from_raw_os_error(libc::EBADF));
+        assert_eq!(os.raw_os_error(), Some(libc::EBADF));
+        let custom = copy_error(&Error::new(ErrorKind::InvalidData, "bad chunk"));
+        assert_eq!(custom.kind(), ErrorKind::InvalidData);
+        assert_eq!(custom.to_string(), "bad chunk");
+    }
+}
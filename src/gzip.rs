@@ -0,0 +1,289 @@
+use std::io::{Error, ErrorKind, Result, Write};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use flate2::write::{GzEncoder, MultiGzDecoder};
+pub use flate2::Compression;
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntime;
+
+use crate::{LockType, SafeFile, FILE_RUNTIME, READ_CHUNK_SIZE};
+
+/*
+* gzip压缩写入器，写入的数据压缩后追加到文件尾，每个写入器写入一个独立的gzip成员，多个成员首尾相接仍是有效的gzip文件
+* 刷新时同步刷新压缩流并写入文件，崩溃后可以解压出最后一次刷新前的数据，关闭时写入gzip尾部，释放时在FILE_RUNTIME上异步关闭
+*/
+#[derive(Debug)]
+pub struct CompressedWriter {
+    file: SafeFile,
+    encoder: Option<GzEncoder<Vec<u8>>>, //压缩流，压缩后的数据在写入文件前暂存在内部的缓冲区中，关闭后为None
+}
+
+impl CompressedWriter {
+    // 以追加方式打开指定路径的文件，以默认的压缩级别写入
+    pub async fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let file = SafeFile::open(path, AsyncFileOptions::OnlyAppend).await?;
+        CompressedWriter::new(file, Compression::default())
+    }
+
+    // 以指定的压缩级别向安全文件的文件尾写入，截断写文件每次写入都会替换全部数据，不支持
+    pub fn new(file: SafeFile, level: Compression) -> Result<Self> {
+        if let LockType::Lock(_) = file.0.lock {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Compress file failed, file: {:?}, reason: truncate write file", file.path()),
+            ));
+        }
+        Ok(CompressedWriter {
+            file,
+            encoder: Some(GzEncoder::new(Vec::new(), level)),
+        })
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 异步压缩并写入指定数据，压缩后的数据可能暂存在压缩流中，刷新或关闭后才会全部写入文件
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let out = self.encode(|encoder| encoder.write_all(data))?;
+        append_all(&self.file, out).await
+    }
+
+    // 异步同步刷新压缩流，并将压缩后的数据写入文件，刷新后文件中已有的数据可以被完整解压
+    pub async fn flush(&mut self) -> Result<()> {
+        let out = self.encode(|encoder| encoder.flush())?;
+        append_all(&self.file, out).await
+    }
+
+    // 异步结束压缩流，写入gzip尾部，并获取内部的安全文件
+    pub async fn close(mut self) -> Result<SafeFile> {
+        self.finish().await?;
+        Ok(self.file.clone())
+    }
+
+    // 结束压缩流，写入gzip尾部，失败时也会丢弃压缩流
+    async fn finish(&mut self) -> Result<()> {
+        let r = self.encode(|encoder| encoder.try_finish());
+        self.encoder = None;
+        append_all(&self.file, r?).await
+    }
+
+    // 对压缩流执行指定操作，并取出压缩后的数据
+    fn encode<F>(&mut self, f: F) -> Result<Vec<u8>>
+    where
+        F: FnOnce(&mut GzEncoder<Vec<u8>>) -> Result<()>,
+    {
+        let file = &self.file;
+        let encoder = match self.encoder.as_mut() {
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Compress file failed, file: {:?}, reason: writer closed", file.path()),
+                ))
+            }
+            Some(encoder) => encoder,
+        };
+        f(encoder).map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Compress file failed, file: {:?}, reason: {:?}", file.path(), e),
+            )
+        })?;
+        Ok(mem::take(encoder.get_mut()))
+    }
+}
+
+impl Drop for CompressedWriter {
+    //释放时在FILE_RUNTIME上异步结束压缩流，写入失败则输出错误
+    fn drop(&mut self) {
+        let encoder = match self.encoder.take() {
+            None => return,
+            Some(encoder) => encoder,
+        };
+        let mut writer = CompressedWriter {
+            file: self.file.clone(),
+            encoder: Some(encoder),
+        };
+        let r = FILE_RUNTIME.spawn(async move {
+            if let Err(e) = writer.finish().await {
+                log::warn!("Compress file failed, file: {:?}, reason: {:?}", writer.file.path(), e);
+            }
+        });
+        if let Err(e) = r {
+            log::warn!("Compress file failed, file: {:?}, reason: {:?}", self.file.path(), e);
+        }
+    }
+}
+
+/*
+* gzip解压读取器，从文件头开始按块读取压缩数据并解压，支持多个首尾相接的gzip成员
+*/
+#[derive(Debug)]
+pub struct CompressedReader {
+    file: SafeFile,
+    pos: u64,                           //下次读取的压缩数据的位置
+    decoder: MultiGzDecoder<Vec<u8>>,   //解压流，解压后的数据暂存在内部的缓冲区中
+    offset: usize,                      //解压后的数据中已消费的字节数
+    eof: bool,                          //是否已读完所有压缩数据
+}
+
+impl CompressedReader {
+    // 以只读方式打开指定路径的文件
+    pub async fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let file = SafeFile::open(path, AsyncFileOptions::OnlyRead).await?;
+        Ok(CompressedReader::new(file))
+    }
+
+    // 从文件头开始解压读取安全文件
+    pub fn new(file: SafeFile) -> Self {
+        CompressedReader {
+            file,
+            pos: 0,
+            decoder: MultiGzDecoder::new(Vec::new()),
+            offset: 0,
+            eof: false,
+        }
+    }
+
+    // 获取内部的安全文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 异步读取最多指定字节的解压数据，读完则返回空
+    // 压缩数据不完整时，先返回可以解压出的数据，之后返回UnexpectedEof错误
+    pub async fn read(&mut self, len: usize) -> Result<Vec<u8>> {
+        while self.decoder.get_ref().len() == self.offset && !self.eof {
+            self.fill().await?;
+        }
+        let start = self.offset;
+        let end = start.saturating_add(len).min(self.decoder.get_ref().len());
+        let data = self.decoder.get_ref()[start..end].to_vec();
+        self.offset = end;
+        Ok(data)
+    }
+
+    // 异步读取剩余的所有解压数据
+    pub async fn read_to_end(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        loop {
+            let r = self.read(READ_CHUNK_SIZE).await?;
+            if r.is_empty() {
+                return Ok(data);
+            }
+            data.extend_from_slice(&r);
+        }
+    }
+
+    // 读取一块压缩数据并解压，丢弃已消费的解压数据，读到文件尾时结束解压流
+    async fn fill(&mut self) -> Result<()> {
+        let chunk = self.file.read(self.pos, READ_CHUNK_SIZE).await?;
+        self.pos += chunk.len() as u64;
+        let buf = self.decoder.get_mut();
+        buf.drain(..self.offset);
+        self.offset = 0;
+        let r = if chunk.is_empty() {
+            // 数据已读完但gzip成员未正常结束，缺少数据或尾部都视为数据不完整
+            self.eof = true;
+            self.decoder
+                .try_finish()
+                .map_err(|e| Error::new(ErrorKind::UnexpectedEof, e))
+        } else {
+            self.decoder.write_all(&chunk).and_then(|_| self.decoder.flush())
+        };
+        r.map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Decompress file failed, file: {:?}, pos: {}, reason: {:?}", self.file.path(), self.pos, e),
+            )
+        })
+    }
+}
+
+// 将数据全部追加到文件尾
+async fn append_all(file: &SafeFile, mut data: Vec<u8>) -> Result<()> {
+    while !data.is_empty() {
+        let r = file.append(Arc::from(&data[..])).await?;
+        if r == 0 {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                format!("Compress file failed, file: {:?}, reason: write zero", file.path()),
+            ));
+        }
+        data.drain(..r);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use std::fs;
+
+    #[test]
+    fn members_concatenate_into_one_stream() {
+        let path = test_path("gzip_members");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let mut writer = CompressedWriter::open(copy.clone()).await?;
+            writer.write(b"hello ").await?;
+            writer.write(b"gzip").await?;
+            writer.close().await?;
+            // 第二个写入器追加一个新的成员
+            let mut writer = CompressedWriter::open(copy.clone()).await?;
+            writer.write(b" again").await?;
+            writer.close().await?;
+            let mut reader = CompressedReader::open(copy).await?;
+            let head = reader.read(5).await?;
+            Ok::<_, Error>((head, reader.read_to_end().await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"hello");
+        assert_eq!(r.1, b" gzip again");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn flushed_data_survives_a_missing_trailer() {
+        let path = test_path("gzip_flush");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let mut writer = CompressedWriter::open(copy.clone()).await?;
+            writer.write(b"flushed").await?;
+            writer.flush().await?;
+            // 模拟崩溃，不写入gzip尾部
+            writer.encoder = None;
+            drop(writer);
+            let mut reader = CompressedReader::open(copy).await?;
+            let data = reader.read(64).await?;
+            Ok::<_, Error>((data, reader.read(64).await.map_err(|e| e.kind())))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"flushed");
+        assert_eq!(r.1, Err(ErrorKind::UnexpectedEof));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn rejects_truncate_write_file() {
+        let path = test_path("gzip_truncate");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            Ok::<_, Error>(CompressedWriter::new(file, Compression::default()).map(|_| ()).map_err(|e| e.kind()))
+        })
+        .unwrap();
+        assert_eq!(r, Err(ErrorKind::InvalidInput));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,131 @@
+use std::fs;
+use std::io::Result;
+use std::path::Path;
+use std::sync::Arc;
+
+use pi_async_file::file::AsyncFileOptions;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{atomic_write, run_sync, FileError, FileResult, SafeFile};
+
+/*
+* 将指定值序列化为json，并原子写入指定文件
+*/
+pub async fn write_json<P, T>(path: P, value: &T) -> FileResult<()>
+where
+    P: AsRef<Path> + Send + 'static,
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref().to_path_buf();
+    let data = serde_json::to_vec(value).map_err(|e| FileError::Codec {
+        path: path.clone(),
+        reason: e.to_string(),
+    })?;
+    atomic_write(path.clone(), Arc::from(data))
+        .await
+        .map_err(|e| FileError::io(&path, e))
+}
+
+/*
+* 读取指定文件的全部数据，并反序列化json
+* 文件已以不可读的方式打开时，复用已打开的句柄读取最近写入的全数据
+*/
+pub async fn read_json<P, T>(path: P) -> FileResult<T>
+where
+    P: AsRef<Path> + Send + 'static,
+    T: DeserializeOwned,
+{
+    let path = path.as_ref().to_path_buf();
+    let data = loop {
+        match SafeFile::try_open(path.clone(), AsyncFileOptions::OnlyRead).await {
+            Ok(file) => break file.read_to_end().await,
+            Err(FileError::Incompatible { .. }) => {
+                // 已打开的句柄可能刚被关闭，此时重新打开
+                if let Some(file) = SafeFile::opened(&path).await {
+                    break read_written(&file).await;
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    .map_err(|e| FileError::io(&path, e))?;
+    serde_json::from_slice(&data).map_err(|e| FileError::Codec {
+        path,
+        reason: e.to_string(),
+    })
+}
+
+// 读取不可读的已打开文件最近写入的全数据，截断写文件直接返回缓冲数据，否则持有锁后读取磁盘上的文件
+async fn read_written(file: &SafeFile) -> Result<Vec<u8>> {
+    let data = file.0.buffered();
+    if !data.is_empty() {
+        return Ok(data.to_vec());
+    }
+    let _guard = file.read_lock().await;
+    let path = file.path().to_path_buf();
+    run_sync(move || fs::read(path)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::WriteOptions;
+    use serde::Deserialize;
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Save {
+        name: String,
+        level: u32,
+        items: Vec<u64>,
+        flags: BTreeMap<String, bool>,
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = test_path("json_round_trip");
+        let save = Save {
+            name: "player".to_string(),
+            level: 7,
+            items: vec![1, 2, 3],
+            flags: vec![("tutorial".to_string(), true)].into_iter().collect(),
+        };
+        let copy = path.clone();
+        let r = block_on(async move {
+            write_json(copy.clone(), &save).await?;
+            Ok::<_, FileError>((save, read_json::<_, Save>(copy).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, r.1);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn malformed_json_is_codec_error() {
+        let path = test_path("json_malformed");
+        fs::write(&path, b"{\"name\":").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move { read_json::<_, Save>(copy).await });
+        assert!(matches!(r, Err(FileError::Codec { path: ref p, .. }) if *p == path));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn reads_through_unreadable_open_handle() {
+        let path = test_path("json_truncate_open");
+        let copy = path.clone();
+        let r = block_on(async move {
+            // 截断写文件不可读，读取时复用已打开的句柄取最近写入的全数据
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"{\"a\":1}"[..]), WriteOptions::None).await?;
+            let value = read_json::<_, BTreeMap<String, u32>>(copy).await?;
+            Ok::<_, FileError>(value)
+        })
+        .unwrap();
+        assert_eq!(r, vec![("a".to_string(), 1)].into_iter().collect());
+        let _ = fs::remove_file(path);
+    }
+}
@@ -5,49 +5,219 @@ extern crate pi_hash;
 #[macro_use]
 extern crate lazy_static;
 
-use async_lock::{Mutex, RwLock};
+mod advise;
+mod blocking;
+mod buf_reader;
+mod buf_writer;
+#[cfg(feature = "blake3")]
+mod cas;
+#[cfg(feature = "crc")]
+mod checksum;
+mod cursor;
+mod diff;
+mod dir;
+#[cfg(feature = "encryption")]
+mod encrypt;
+mod error;
+mod falloc;
+mod flight;
+#[cfg(feature = "flate2")]
+mod gzip;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "crc")]
+mod record_log;
+mod os_lock;
+mod page_cache;
+mod pool;
+mod resumable;
+mod rotating;
+mod runtime;
+mod space;
+mod stats;
+mod temp;
+#[cfg(feature = "tokio")]
+mod tokio_io;
+#[cfg(feature = "bytes")]
+mod zero_copy;
+
+pub use advise::Advice;
+pub use blocking::BlockingSafeFile;
+pub use buf_reader::BufferedReader;
+pub use buf_writer::BufferedWriter;
+#[cfg(feature = "blake3")]
+pub use cas::{load_cas, store_cas};
+#[cfg(feature = "crc")]
+pub use checksum::crc32;
+pub use cursor::FileCursor;
+pub use diff::{apply, diff, DiffOp};
+pub use dir::{copy_dir, read_dir, walk_dir, walk_dir_with, DirEntry, WalkOptions};
+#[cfg(feature = "encryption")]
+pub use encrypt::EncryptedFile;
+pub use error::{FileError, FileResult};
+#[cfg(feature = "flate2")]
+pub use gzip::{CompressedReader, CompressedWriter, Compression};
+#[cfg(feature = "serde")]
+pub use json::{read_json, write_json};
+pub use pool::PooledBytes;
+#[cfg(feature = "crc")]
+pub use record_log::RecordLog;
+pub use resumable::ResumableWriter;
+pub use rotating::RotatingWriter;
+pub use runtime::{init_runtime, runtime_stats, set_file_runtime, RuntimeConfig, RuntimeStats};
+pub use space::{available_space, set_space_check_threshold, total_space};
+pub use stats::{global_io_stats, reset_global_io_stats, CacheStats, GlobalStats};
+pub use temp::{temp_file, TempSafeFile};
+#[cfg(feature = "tokio")]
+pub use tokio_io::SafeFileReader;
+
+use arc_swap::ArcSwap;
+#[cfg(feature = "mmap")]
+use arc_swap::ArcSwapOption;
+use async_lock::{Mutex, MutexGuard, MutexGuardArc, RwLock, RwLockReadGuard, RwLockWriteGuard, SemaphoreGuardArc};
+use error::is_too_many_open_files;
+use flight::{copy_error, FlightSender, ReadFlight};
+use os_lock::OsLock;
+use page_cache::PageCache;
+use futures::future::{self, Either};
+use futures::stream::{self, Stream, StreamExt};
 use pi_async_rt::lock::spin_lock::SpinLock;
-use pi_async_rt::rt::multi_thread::{MultiTaskRuntime, MultiTaskRuntimeBuilder, StealableTaskPool};
+use pi_async_rt::rt::multi_thread::MultiTaskRuntime;
+use pi_async_rt::rt::AsyncRuntime;
 use pi_async_file::file::{AsyncFile, AsyncFileOptions, WriteOptions};
 use pi_hash::XHashMap;
-use std::collections::hash_map::Entry;
+use std::collections::hash_map::{DefaultHasher, Entry};
 use std::fmt::{Debug, Formatter, Result as FmtResult};
-use std::io::Result;
+use std::hash::{Hash, Hasher};
+use std::fs::Metadata;
+use std::io::{Error, ErrorKind, Result, Write};
 use std::ops::Deref;
 use std::{
-    env,
-    path::{Path, PathBuf},
+    fs,
+    future::Future,
+    path::{Component, Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
     sync::Arc,
     sync::Weak,
+    time::SystemTime,
 };
 
 lazy_static! {
     /// 异步 文件IO 运行时，多线程，不需要主动推
-    pub static ref FILE_RUNTIME: MultiTaskRuntime<()> = {
-        // 获得环境变量声明的异步文件线程数，如果没有声明，则取cpu物理核数
-        let count = match env::var("_ver") {
-            Ok(r) => usize::from_str_radix(r.as_str(), 10).unwrap(),
-            _ => num_cpus::get()
-        };
-        let pool = StealableTaskPool::with(count, 100000, [1,1], 3000);
-        // 线程池：每个线程1M的栈空间，10ms 休眠，10毫秒的定时器间隔
-        let builder = MultiTaskRuntimeBuilder::new(pool)
-        .thread_prefix("File-Runtime")
-        .thread_stack_size(1024 * 1024)
-        .init_worker_size(count)
-        .set_worker_limit(count, count)
-        .set_timeout(10)
-        .set_timer_interval(10);
-        builder.build()
-    };
+    pub static ref FILE_RUNTIME: MultiTaskRuntime<()> = runtime::build_runtime();
     /// 打开文件的全局表
-    static ref OPEN_FILE_MAP: Table = Table(Mutex::new(XHashMap::default()));
+    static ref OPEN_FILE_MAP: Table = Table((0..TABLE_SHARDS).map(|_| Mutex::new(XHashMap::default())).collect());
+    //本库保留强引用的最近访问的文件
+    static ref KEEP_ALIVE: Mutex<XHashMap<PathBuf, Arc<InnerSafeFile>>> = Mutex::new(XHashMap::default());
+}
+
+// 打开文件的全局表的分片数
+const TABLE_SHARDS: usize = 16;
+
+/*
+* 按路径的哈希分片的打开文件表，不同分片的路径互不竞争
+*/
+struct Table(Vec<Mutex<XHashMap<PathBuf, Slot>>>);
+
+/*
+* 打开文件表的条目
+*/
+enum Slot {
+    Opening(Arc<Mutex<()>>),   //正在打开，打开的任务持有锁，打开同一路径的其它任务等待该锁释放
+    Open(Weak<InnerSafeFile>), //已打开
+}
+
+impl Slot {
+    // 获取已打开的文件，正在打开或已关闭则返回None
+    fn upgrade(&self) -> Option<Arc<InnerSafeFile>> {
+        match self {
+            Slot::Open(file) => file.upgrade(),
+            Slot::Opening(_) => None,
+        }
+    }
+
+    // 获取已打开的文件被持有的句柄数
+    fn strong_count(&self) -> usize {
+        match self {
+            Slot::Open(file) => file.strong_count(),
+            Slot::Opening(_) => 0,
+        }
+    }
+
+    // 条目是否仍然有效，正在打开的条目始终有效
+    fn is_live(&self) -> bool {
+        match self {
+            Slot::Open(file) => file.strong_count() > 0,
+            Slot::Opening(_) => true,
+        }
+    }
+}
+
+/*
+* 在打开文件表中查找的结果
+*/
+enum Lookup {
+    Found(Arc<InnerSafeFile>), //已打开的文件
+    Reserved(MutexGuardArc<()>), //已为本任务占位，需要由本任务打开后登记或取消占位
+}
+
+impl Table {
+    // 获取指定路径所在的分片
+    fn shard(&self, path: &Path) -> &Mutex<XHashMap<PathBuf, Slot>> {
+        let mut hasher = DefaultHasher::new();
+        path.hash(&mut hasher);
+        &self.0[hasher.finish() as usize % self.0.len()]
+    }
+
+    // 获取所有分片
+    fn shards(&self) -> &[Mutex<XHashMap<PathBuf, Slot>>] {
+        &self.0
+    }
 }
 
-struct Table(Mutex<XHashMap<PathBuf, Weak<InnerSafeFile>>>);
+// 定时整理任务是否已启动
+static AUTO_COLLECT_RUNNING: AtomicBool = AtomicBool::new(false);
+// 定时整理任务的停止标记
+static AUTO_COLLECT_STOP: AtomicBool = AtomicBool::new(false);
+
+// 本库最多保留强引用的文件数，为0则不保留
+static KEEP_ALIVE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+// 所有文件的缓存最多占用的内存字节数，为0则不限制
+static CACHE_MEMORY_LIMIT: AtomicUsize = AtomicUsize::new(0);
+// 文件访问的序号，用于比较文件的最近访问先后
+static ACCESS_SEQ: AtomicU64 = AtomicU64::new(0);
+
+// 临时文件序号
+static TEMP_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+// 批量打开或移除文件时的最大并发数
+const BATCH_CONCURRENCY: usize = 16;
+
+// 读到文件尾时每次追加读取的字节数
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+// 开启读缓存时单个文件默认缓存的最大字节数
+const DEFAULT_CACHE_SIZE: usize = 16 * 1024 * 1024;
+
+// 跟随读取文件时没有新数据的等待时间，单位ms
+const FOLLOW_INTERVAL: usize = 100;
+
+// 等待打开许可时重新检查可释放的保留文件的间隔，单位ms
+const PERMIT_RETRY_INTERVAL: usize = 50;
+
+// 截断写文件在写入位置前补零的最大字节数，补零的部分会随缓冲的全数据一起留在内存中
+const MAX_TRUNCATE_GAP: u64 = 64 * 1024 * 1024;
+
+/*
+* 非截断写文件的版本，SafeFile::version对这类文件总是返回该值，read_to_end_versioned返回的版本不会等于该值
+*/
+pub const UNVERSIONED: u64 = u64::MAX;
 
 /*
-* 安全文件， 如果打开文件为截断写，采用异步锁，否则采用异步读写锁
+* 安全文件， 如果打开文件为截断写，采用异步锁，只读则不加锁，否则采用异步读写锁
+* 同一路径的多次打开和所有克隆共享同一个句柄，共享锁、缓存、版本和统计，锁和缓存选项由首次打开决定
+* 再次打开时，已打开的方式必须提供请求方式的全部语义且不附加其它写语义，否则返回FileError::Incompatible错误，不会升级已打开的句柄
 */
 #[derive(Debug, Clone)]
 pub struct SafeFile(Arc<InnerSafeFile>);
@@ -56,228 +226,4708 @@ impl Deref for SafeFile {
     type Target = AsyncFile<()>;
     #[inline(always)]
     fn deref(&self) -> &AsyncFile<()> {
-        &(*self.0).file
+        // 被替换的旧句柄保留到文件关闭，当前句柄在本文件存活期间不会释放，因此引用始终有效
+        let file = self.0.file.load();
+        unsafe { &*(&**file as *const AsyncFile<()>) }
     }
 }
 enum LockType {
     Rw(RwLock<()>),
     Lock(Mutex<()>),
+    Immutable, //只读文件，本进程不会修改，读取不需要加锁，写入会返回错误
+}
+/*
+* 读缓存选项，同一路径共享句柄，以首次打开时的选项为准
+* 默认缓存读到的数据，单个文件最多缓存16MB，截断写文件总是缓冲最近一次写入的全数据
+*/
+#[derive(Debug, Clone, Copy)]
+pub struct CacheOptions {
+    pub enable: bool,    //是否缓存读到的数据，默认缓存
+    pub max_size: usize, //单个文件缓存的最大字节数，超过则不缓存，默认为16MB
+    pub metadata: bool,  //是否缓存文件元信息，缓存后只有本进程的写入和改变长度会使其失效
+    pub page_size: usize, //按页缓存时每页的字节数，写入只使受影响的页失效，为0则以整个文件为单位缓存
+    pub cache_whole: Option<usize>, //开启缓存且打开时文件不超过指定字节数，则在打开时读入全部数据并缓存，之后的读直接从缓存返回
+}
+impl Default for CacheOptions {
+    fn default() -> Self {
+        CacheOptions {
+            enable: true,
+            max_size: DEFAULT_CACHE_SIZE,
+            metadata: false,
+            page_size: 0,
+            cache_whole: None,
+        }
+    }
 }
+
+/*
+* 缓冲的数据，截断写文件为最近一次写入后文件的全数据，其它文件为缓存的全数据
+*/
+struct Buffered {
+    data: Arc<[u8]>, //缓冲的数据，为空表示没有缓冲
+    pending: usize,  //未落地的版本，为0表示已落地
+    at: u64,         //截断写文件未落地的数据的写入位置，之前的部分为截断后补的零
+}
+
 struct InnerSafeFile {
-    file: AsyncFile<()>,
+    path: PathBuf,
+    file: ArcSwap<AsyncFile<()>>, //文件句柄，原子写替换文件后指向新文件
+    replaced: SpinLock<Vec<Arc<AsyncFile<()>>>>, //被原子写替换的旧句柄，保留到文件关闭
     lock: LockType,
-    buff: SpinLock<(Arc<[u8]>, usize)>,
+    buff: ArcSwap<Buffered>, //读取时无锁加载，修改时持有缓冲区锁并整体替换
+    buff_lock: SpinLock<()>, //缓冲区锁，修改缓冲数据时互斥，先替换缓冲数据再增加代数
+    cache: CacheOptions,
+    gen: AtomicUsize, //缓存的代数，每次写入都会增加，读到的数据只有在代数未变时才能填充缓存
+    meta: SpinLock<Option<Metadata>>, //缓存的文件元信息
+    read_bytes: AtomicU64,            //所有句柄累计读取的字节数
+    written_bytes: AtomicU64,         //所有句柄累计写入的字节数
+    cache_hits: AtomicU64,            //所有句柄累计读缓存命中数
+    cache_misses: AtomicU64,          //所有句柄累计读缓存未命中数
+    pinned: AtomicBool,               //缓存是否已固定，固定的缓存不会被全局释放，文件也不会因最久未访问而被释放
+    last_access: AtomicU64,           //最近一次访问的序号
+    flights: SpinLock<XHashMap<(u64, usize), (usize, ReadFlight)>>, //正在进行的读及发起时缓存的代数，同一范围的并发读共享一次IO
+    pages: Option<PageCache>,         //按页缓存时的页缓存，存在时不以整个文件为单位缓存
+    permit: Option<SemaphoreGuardArc>, //打开文件的许可，文件关闭时释放
+    #[cfg(feature = "mmap")]
+    mmap: ArcSwapOption<memmap2::Mmap>, //只读文件的内存映射，存在时直接从映射读取
 }
 impl Debug for InnerSafeFile {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(f, "{:?}", self.file)
+        write!(f, "{:?}", self.file())
     }
 }
 impl InnerSafeFile {
-    fn new(file: AsyncFile<()>, lock: LockType) -> Self {
-        let vec = Vec::new();
+    fn new(path: PathBuf, file: AsyncFile<()>, lock: LockType, cache: CacheOptions) -> Self {
         InnerSafeFile {
-            file,
+            path,
+            file: ArcSwap::from_pointee(file),
+            replaced: SpinLock::new(Vec::new()),
             lock,
-            buff: SpinLock::new((Arc::from(&vec[..]), 0)),
+            buff: ArcSwap::from_pointee(Buffered {
+                data: Arc::from(Vec::new()),
+                pending: 0,
+                at: 0,
+            }),
+            buff_lock: SpinLock::new(()),
+            cache,
+            gen: AtomicUsize::new(0),
+            meta: SpinLock::new(None),
+            read_bytes: AtomicU64::new(0),
+            written_bytes: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+            pinned: AtomicBool::new(false),
+            last_access: AtomicU64::new(ACCESS_SEQ.fetch_add(1, Ordering::Relaxed)),
+            flights: SpinLock::new(XHashMap::default()),
+            pages: (cache.enable && cache.page_size > 0).then(|| PageCache::new(cache.page_size, cache.max_size)),
+            permit: None,
+            #[cfg(feature = "mmap")]
+            mmap: ArcSwapOption::empty(),
+        }
+    }
+    // 获取当前的文件句柄，原子写替换文件后为新文件的句柄
+    fn file(&self) -> AsyncFile<()> {
+        AsyncFile::clone(&self.file.load())
+    }
+    // 记录本次访问
+    fn touch(&self) {
+        self.last_access
+            .store(ACCESS_SEQ.fetch_add(1, Ordering::Relaxed), Ordering::Relaxed);
+    }
+    // 加入指定范围正在进行的读，没有或发起后已有写入，则发起新的读
+    // 发起新的读时同时返回设置读结果的发送者
+    fn join_read(&self, gen: usize, pos: u64, len: usize) -> (ReadFlight, Option<FlightSender>) {
+        let mut flights = self.flights.lock();
+        match flights.get(&(pos, len)) {
+            Some((g, flight)) if *g == gen => (flight.clone(), None),
+            _ => {
+                let (flight, sender) = ReadFlight::new();
+                flights.insert((pos, len), (gen, flight.clone()));
+                (flight, Some(sender))
+            }
+        }
+    }
+    // 读完成后移除指定范围的读，之后的读会重新发起，已被新的读替换则保留
+    fn finish_read(&self, pos: u64, len: usize, flight: &ReadFlight) {
+        let mut flights = self.flights.lock();
+        if flights.get(&(pos, len)).is_some_and(|(_, f)| f.ptr_eq(flight)) {
+            flights.remove(&(pos, len));
+        }
+    }
+    // 增加读取的字节数
+    fn count_read(&self, len: usize) {
+        self.touch();
+        self.read_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        stats::add_read(len);
+    }
+    // 增加写入的字节数
+    fn count_written(&self, len: usize) {
+        self.touch();
+        self.written_bytes.fetch_add(len as u64, Ordering::Relaxed);
+        stats::add_written(len);
+    }
+    // 记录一次读缓存是否命中
+    fn count_cache(&self, hit: bool) {
+        if hit {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.cache_misses.fetch_add(1, Ordering::Relaxed);
         }
+        stats::add_cache_access(hit);
     }
+    // 无锁获取缓冲的数据
+    fn buffered(&self) -> Arc<[u8]> {
+        self.buff.load().data.clone()
+    }
+    // 替换缓冲的数据及未落地的版本，不改变未落地的数据的写入位置，调用前需要持有缓冲区锁
+    fn set_buff(&self, data: Arc<[u8]>, pending: usize) {
+        let at = self.buff.load().at;
+        self.buff.store(Arc::new(Buffered { data, pending, at }));
+    }
+    // 截断写文件缓冲从指定位置开始写入的数据，并增加未落地的版本和代数，调用前需要持有缓冲区锁
+    // 截断写会先清空文件，因此缓冲的全数据为写入位置前补零再接上写入的数据
+    // 写入位置超过补零的上限或无法分配缓冲的内存时返回错误，不修改缓冲数据
+    fn set_pending(&self, pos: u64, buf: Arc<[u8]>) -> Result<()> {
+        let data = if pos == 0 {
+            buf
+        } else {
+            if pos > MAX_TRUNCATE_GAP {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!(
+                        "Write file failed, file: {:?}, pos: {}, reason: truncate write past the {} bytes gap limit",
+                        self.path, pos, MAX_TRUNCATE_GAP
+                    ),
+                ));
+            }
+            let mut data = Vec::new();
+            data.try_reserve_exact(pos as usize + buf.len()).map_err(|e| {
+                Error::new(
+                    ErrorKind::OutOfMemory,
+                    format!("Write file failed, file: {:?}, pos: {}, reason: {:?}", self.path, pos, e),
+                )
+            })?;
+            data.resize(pos as usize, 0);
+            data.extend_from_slice(&buf);
+            Arc::from(data)
+        };
+        let pending = self.buff.load().pending + 1;
+        self.buff.store(Arc::new(Buffered { data, pending, at: pos }));
+        self.gen.fetch_add(1, Ordering::AcqRel);
+        Ok(())
+    }
+    // 指定长度的数据是否允许缓存
+    fn cacheable(&self, len: usize) -> bool {
+        self.cache.enable && self.pages.is_none() && len <= self.cache.max_size
+    }
+    // 从缓存中获取指定范围的数据，超出部分截断，没有缓存则返回None
+    fn cached(&self, pos: u64, len: usize) -> Option<Vec<u8>> {
+        let data = self.buffered();
+        self.count_cache(!data.is_empty());
+        if data.is_empty() {
+            return None;
+        }
+        let start = (pos as usize).min(data.len());
+        let end = start.saturating_add(len).min(data.len());
+        Some(data[start..end].to_vec())
+    }
+    // 用读到的全数据填充缓存，如果读取期间有写入，则放弃
+    fn fill_cache(&self, gen: usize, data: &[u8]) {
+        if !self.cacheable(data.len()) {
+            return;
+        }
+        let _lock = self.buff_lock.lock();
+        let buff = self.buff.load_full();
+        if buff.data.is_empty() && self.gen.load(Ordering::Acquire) == gen {
+            self.set_buff(Arc::from(data), buff.pending);
+        }
+    }
+    // 写入后修补缓存，写入范围与缓存数据相连则修补，否则清除缓存
+    fn patch_cache(&self, pos: u64, buf: &[u8]) {
+        self.meta.lock().take();
+        if let Some(ref pages) = self.pages {
+            pages.invalidate(pos, buf.len());
+        }
+        let _lock = self.buff_lock.lock();
+        let buff = self.buff.load_full();
+        if !buff.data.is_empty() {
+            let start = pos as usize;
+            let end = start + buf.len();
+            let data = if start > buff.data.len() || !self.cacheable(end.max(buff.data.len())) {
+                Arc::from(Vec::new())
+            } else {
+                let mut data = buff.data.to_vec();
+                if end > data.len() {
+                    data.resize(end, 0);
+                }
+                data[start..end].copy_from_slice(buf);
+                Arc::from(data)
+            };
+            self.set_buff(data, buff.pending);
+        }
+        self.gen.fetch_add(1, Ordering::AcqRel);
+    }
+    // 文件指定范围被置零后修补缓存，范围不超出文件尾
+    fn zero_cache(&self, pos: u64, len: u64) {
+        self.meta.lock().take();
+        if let Some(ref pages) = self.pages {
+            pages.invalidate(pos, len as usize);
+        }
+        let _lock = self.buff_lock.lock();
+        let buff = self.buff.load_full();
+        if !buff.data.is_empty() {
+            let mut data = buff.data.to_vec();
+            let start = (pos as usize).min(data.len());
+            let end = start.saturating_add(len as usize).min(data.len());
+            data[start..end].fill(0);
+            self.set_buff(Arc::from(data), buff.pending);
+        }
+        self.gen.fetch_add(1, Ordering::AcqRel);
+    }
+    // 改变文件长度后调整缓存，缩短则截断缓存，加长则补零，补零后超过缓存上限则清除缓存
+    fn resize_cache(&self, size: u64) {
+        self.meta.lock().take();
+        if let Some(ref pages) = self.pages {
+            pages.clear();
+        }
+        let _lock = self.buff_lock.lock();
+        let buff = self.buff.load_full();
+        if !buff.data.is_empty() && buff.data.len() as u64 != size {
+            let data = if self.cacheable(size as usize) {
+                let mut data = buff.data.to_vec();
+                data.resize(size as usize, 0);
+                Arc::from(data)
+            } else {
+                Arc::from(Vec::new())
+            };
+            self.set_buff(data, buff.pending);
+        }
+        self.gen.fetch_add(1, Ordering::AcqRel);
+    }
+    // 打开时文件不超过整体缓存的上限，则读入全部数据并缓存，读取失败则忽略，之后按需读取
+    async fn cache_whole(&self) {
+        let file = self.file();
+        let size = file.get_size();
+        match self.cache.cache_whole {
+            Some(limit) if size > 0 && size <= limit as u64 && self.cacheable(size as usize) => (),
+            _ => return,
+        }
+        let gen = self.gen.load(Ordering::Acquire);
+        if let Ok(data) = runtime::retry(|| file.read(0, size as usize)).await {
+            if data.len() as u64 == size {
+                self.fill_cache(gen, &data);
+            }
+        }
+    }
+    // 释放缓存的数据，不改变版本，截断写文件未落地的缓冲数据不释放
+    fn clear_cache(&self) {
+        if let Some(ref pages) = self.pages {
+            pages.clear();
+        }
+        let _lock = self.buff_lock.lock();
+        if self.buff.load().pending == 0 {
+            self.set_buff(Arc::from(Vec::new()), 0);
+        }
+    }
+    // 文件被原子替换后切换到新文件的句柄，旧句柄可能仍被解引用，因此保留到文件关闭
+    fn replace_file(&self, file: AsyncFile<()>) {
+        let old = self.file.swap(Arc::new(file));
+        self.replaced.lock().push(old);
+    }
+    // 文件被原子替换后以新的全数据刷新缓存，超过缓存上限或未开启缓存则清除缓存，截断写文件未落地的缓冲数据随之丢弃
+    fn replace_cache(&self, data: &[u8]) {
+        self.meta.lock().take();
+        if let Some(ref pages) = self.pages {
+            pages.clear();
+        }
+        let _lock = self.buff_lock.lock();
+        let data = if self.cacheable(data.len()) {
+            Arc::from(data)
+        } else {
+            Arc::from(Vec::new())
+        };
+        self.buff.store(Arc::new(Buffered { data, pending: 0, at: 0 }));
+        self.gen.fetch_add(1, Ordering::AcqRel);
+    }
+    // 获取缓存的数据占用的字节数，包括整体缓存或截断写的缓冲数据，及页缓存，不包括内存映射
+    fn cache_memory(&self) -> usize {
+        self.buffered().len() + self.pages.as_ref().map(PageCache::cached_size).unwrap_or(0)
+    }
+}
+
+impl Drop for InnerSafeFile {
+    //最后一个句柄释放时，从打开文件表中移除本文件的条目，表被占用时在FILE_RUNTIME上异步移除
+    fn drop(&mut self) {
+        let ptr = self as *const InnerSafeFile as usize;
+        let path = std::mem::take(&mut self.path);
+        if let Some(mut tab) = OPEN_FILE_MAP.shard(&path).try_lock() {
+            remove_closed(&mut tab, &path, ptr);
+            return;
+        }
+        let _ = FILE_RUNTIME.spawn(async move {
+            let mut tab = OPEN_FILE_MAP.shard(&path).lock().await;
+            remove_closed(&mut tab, &path, ptr);
+        });
+    }
+}
+
+// 移除指定路径上已关闭的指定文件的条目，条目已被重新打开或正在打开则保留
+fn remove_closed(tab: &mut XHashMap<PathBuf, Slot>, path: &Path, ptr: usize) {
+    if let Some(Slot::Open(file)) = tab.get(path) {
+        if file.as_ptr() as usize == ptr {
+            tab.remove(path);
+        }
+    }
+}
+
+// 持有中的文件锁，只用于在作用域内持有锁
+enum FileGuard<'a> {
+    Lock { _guard: MutexGuard<'a, ()> },
+    Read { _guard: RwLockReadGuard<'a, ()> },
+    Write { _guard: RwLockWriteGuard<'a, ()> },
+    None,
 }
 
 /*
 * 异步文件的异步方法
 */
 impl SafeFile {
-    //以指定方式异步打开指定的文件
+    //以指定方式异步打开指定的文件，如果路径已以不兼容的方式打开，则返回AlreadyExists错误，内部错误为FileError::Incompatible
     pub async fn open<P>(path: P, options: AsyncFileOptions) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        SafeFile::open_with(path, options, CacheOptions::default()).await
+    }
+
+    //以指定方式和读缓存选项异步打开指定的文件
+    pub async fn open_with<P>(path: P, options: AsyncFileOptions, cache: CacheOptions) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let _op = runtime::enter()?;
+        SafeFile::open_shared(path.as_ref().to_path_buf(), options, cache)
+            .await
+            .map(|(file, _)| file)
+            .map_err(Error::from)
+    }
+
+    //以指定方式异步打开指定的文件，同时返回是否新建了文件句柄，为false表示共享了已打开的句柄
+    pub async fn open_tracked<P>(path: P, options: AsyncFileOptions) -> Result<(Self, bool)>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let _op = runtime::enter()?;
+        SafeFile::open_shared(path.as_ref().to_path_buf(), options, CacheOptions::default())
+            .await
+            .map_err(Error::from)
+    }
+
+    //以指定方式异步打开指定的文件，超时则放弃本次打开并返回TimedOut错误
+    //超时后会移除本次打开在打开文件表中的占位，已提交到运行时的底层打开仍会完成，但打开的文件会被直接关闭
+    pub async fn open_timeout<P>(path: P, options: AsyncFileOptions, timeout_ms: u64) -> Result<Self>
     where
         P: AsRef<Path> + Send + 'static,
     {
         let path = path.as_ref().to_path_buf();
-        {
-            let tab = OPEN_FILE_MAP.0.lock().await;
-            match tab.get(&path) {
-                Some(r) => match r.upgrade() {
-                    Some(rr) => return Ok(SafeFile(rr)),
-                    _ => (),
-                },
-                _ => (),
+        let open = Box::pin(SafeFile::open(path.clone(), options));
+        let timer = FILE_RUNTIME.timeout(timeout_ms as usize);
+        match future::select(open, timer).await {
+            Either::Left((r, _)) => r,
+            Either::Right((_, open)) => {
+                drop(open);
+                SafeFile::remove_abandoned(&path).await;
+                Err(Error::new(
+                    ErrorKind::TimedOut,
+                    format!("Open file failed, file: {:?}, reason: timeout after {}ms", path, timeout_ms),
+                ))
             }
         }
-        let lock = match options {
-            AsyncFileOptions::TruncateWrite => LockType::Lock(Mutex::new(())),
-            _ => LockType::Rw(RwLock::new(())),
+    }
+
+    //以可读可写方式异步打开指定的文件，并按指定的页大小缓存读到的数据，写入只使受影响的页失效
+    pub async fn open_read_write_cached<P>(path: P, page_size: usize) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let cache = CacheOptions {
+            page_size,
+            ..CacheOptions::default()
+        };
+        SafeFile::open_with(path, AsyncFileOptions::ReadWrite, cache).await
+    }
+
+    //打开或共享指定路径的文件，同时返回是否新建了文件句柄，已打开的文件与请求的选项不兼容则返回FileError::Incompatible错误
+    async fn open_shared(path: PathBuf, options: AsyncFileOptions, cache: CacheOptions) -> FileResult<(Self, bool)> {
+        let guard = loop {
+            match SafeFile::lookup(&path).await {
+                Lookup::Found(file) => match SafeFile(file.clone()).check_options(&options) {
+                    Ok(file) => return Ok((file, false)),
+                    // 不兼容的文件只被本库保留时释放后重新打开
+                    Err(e) => {
+                        if !release_idle(file).await {
+                            return Err(e);
+                        }
+                    }
+                },
+                Lookup::Reserved(guard) => break guard,
+            }
+        };
+        // 按打开方式选择锁和读缓存：
+        // 只读不加锁，按选项缓存读到的数据
+        // 可读可写和可读可追加使用读写锁，按选项缓存读到的数据
+        // 只写和只追加使用读写锁，不可读，因此不缓存
+        // 只覆写使用互斥锁，缓冲区总是保存最近一次写入的全数据，不按页缓存
+        // 可读可覆写使用读写锁，每次写入前底层都会截断文件，缓存无法按写入范围修补，因此不缓存
+        let no_cache = CacheOptions {
+            enable: false,
+            ..cache
+        };
+        let (lock, cache) = match options {
+            AsyncFileOptions::OnlyRead => (LockType::Immutable, cache),
+            AsyncFileOptions::ReadWrite | AsyncFileOptions::ReadAppend => (LockType::Rw(RwLock::new(())), cache),
+            AsyncFileOptions::OnlyWrite | AsyncFileOptions::OnlyAppend => (LockType::Rw(RwLock::new(())), no_cache),
+            AsyncFileOptions::TruncateWrite => (
+                LockType::Lock(Mutex::new(())),
+                CacheOptions {
+                    enable: true,
+                    page_size: 0,
+                    ..cache
+                },
+            ),
+            AsyncFileOptions::TruncateReadWrite => (LockType::Rw(RwLock::new(())), no_cache),
+        };
+        // 已打开的文件数达到上限时，等待其它文件关闭后再打开
+        let permit = open_permit().await;
+        let file = match runtime::guard(AsyncFile::open(FILE_RUNTIME.clone(), path.clone(), options.clone())).await {
+            Ok(file) => {
+                stats::add_opened();
+                let mut inner = InnerSafeFile::new(path.clone(), file, lock, cache);
+                inner.permit = permit;
+                Arc::new(inner)
+            }
+            Err(r) => {
+                SafeFile::unreserve(&path, guard).await;
+                if is_too_many_open_files(&r) {
+                    return Err(FileError::TooManyOpenFiles { path });
+                }
+                return Err(r.into());
+            }
         };
-        let file = match AsyncFile::open(FILE_RUNTIME.clone(), path.clone(), options).await {
-            Ok(file) => Arc::new(InnerSafeFile::new(file, lock)),
-            Err(r) => return Err(r),
+        file.cache_whole().await;
+        let opened = SafeFile::register(path, file.clone(), guard).await;
+        let created = Arc::ptr_eq(&opened.0, &file);
+        Ok((opened.check_options(&options)?, created))
+    }
+
+    //检查已打开的文件能否满足请求的选项
+    //只读可以共享任何可读的句柄，写入方式必须一致，按位置写不能共享每次写入都会截断文件的句柄
+    fn check_options(self, options: &AsyncFileOptions) -> FileResult<Self> {
+        use AsyncFileOptions::*;
+
+        let compatible = match options {
+            OnlyRead => matches!(self.0.file().get_options(), OnlyRead | ReadAppend | ReadWrite | TruncateReadWrite),
+            OnlyWrite => matches!(self.0.file().get_options(), OnlyWrite | ReadWrite),
+            OnlyAppend => matches!(self.0.file().get_options(), OnlyAppend | ReadAppend),
+            ReadAppend => matches!(self.0.file().get_options(), ReadAppend),
+            ReadWrite => matches!(self.0.file().get_options(), ReadWrite),
+            TruncateWrite => matches!(self.0.file().get_options(), TruncateWrite),
+            TruncateReadWrite => matches!(self.0.file().get_options(), TruncateReadWrite),
+        };
+        if !compatible {
+            return Err(FileError::Incompatible {
+                path: self.0.path.clone(),
+            });
+        }
+        Ok(self)
+    }
+
+    //以只读方式异步打开指定的文件，并将文件映射到内存，之后的读取直接从映射中复制，适用于不会被修改的大文件
+    //如果路径已以可读方式打开，则返回已打开的文件，不会重新映射，否则返回AlreadyExists错误，内部错误为FileError::Incompatible
+    #[cfg(feature = "mmap")]
+    pub async fn open_mmap<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let _op = runtime::enter()?;
+        let path = path.as_ref().to_path_buf();
+        let guard = match SafeFile::lookup(&path).await {
+            Lookup::Found(file) => return Ok(SafeFile(file).check_options(&AsyncFileOptions::OnlyRead)?),
+            Lookup::Reserved(guard) => guard,
         };
-        let mut tab = OPEN_FILE_MAP.0.lock().await;
-        match tab.entry(path) {
+        let r = async {
+            let permit = open_permit().await;
+            let open = AsyncFile::open(FILE_RUNTIME.clone(), path.clone(), AsyncFileOptions::OnlyRead);
+            let file = runtime::guard(open).await.map_err(|e| match is_too_many_open_files(&e) {
+                true => FileError::TooManyOpenFiles { path: path.clone() }.into(),
+                false => e,
+            })?;
+            stats::add_opened();
+            let copy = file.clone();
+            let mmap = run_sync(move || unsafe { memmap2::Mmap::map(&copy.get_inner()?) })
+                .await
+                .map_err(|e| Error::new(e.kind(), format!("Map file failed, file: {:?}, reason: {:?}", path, e)))?;
+            // 映射本身就在内存中，不再需要读缓存
+            let cache = CacheOptions {
+                enable: false,
+                max_size: 0,
+                metadata: true,
+                page_size: 0,
+                cache_whole: None,
+            };
+            let mut inner = InnerSafeFile::new(path.clone(), file, LockType::Immutable, cache);
+            inner.permit = permit;
+            inner.mmap = ArcSwapOption::from_pointee(mmap);
+            Ok::<_, Error>(Arc::new(inner))
+        }
+        .await;
+        match r {
+            Ok(inner) => Ok(SafeFile::register(path, inner, guard)
+                .await
+                .check_options(&AsyncFileOptions::OnlyRead)?),
+            Err(e) => {
+                SafeFile::unreserve(&path, guard).await;
+                Err(e)
+            }
+        }
+    }
+
+    //从打开文件表中查找已打开的文件，未打开则为本任务占位，正在被其它任务打开则等待其完成
+    async fn lookup(path: &Path) -> Lookup {
+        loop {
+            let mut tab = OPEN_FILE_MAP.shard(path).lock().await;
+            let opening = match tab.get(path) {
+                Some(Slot::Opening(lock)) => match lock.try_lock_arc() {
+                    // 占位的任务已放弃打开，由本任务接替
+                    Some(guard) => return Lookup::Reserved(guard),
+                    None => lock.clone(),
+                },
+                Some(slot) if slot.is_live() => {
+                    let file = slot.upgrade().unwrap();
+                    drop(tab);
+                    file.touch();
+                    keep_alive(&file).await;
+                    return Lookup::Found(file);
+                }
+                _ => {
+                    let lock = Arc::new(Mutex::new(()));
+                    let guard = lock.try_lock_arc().unwrap();
+                    tab.insert(path.to_path_buf(), Slot::Opening(lock));
+                    return Lookup::Reserved(guard);
+                }
+            };
+            drop(tab);
+            // 等待占位的任务完成后重新查找
+            let _ = opening.lock_arc().await;
+        }
+    }
+
+    //为替换指定路径的文件占位，同时取出已打开的文件，正在被其它任务打开则等待其完成
+    //占位期间打开同一路径的任务会等待，替换完成后由unreserve移除占位
+    async fn reserve_replace(path: &Path) -> (Option<Arc<InnerSafeFile>>, MutexGuardArc<()>) {
+        loop {
+            let mut tab = OPEN_FILE_MAP.shard(path).lock().await;
+            let opening = match tab.get(path) {
+                Some(Slot::Opening(lock)) => match lock.try_lock_arc() {
+                    Some(guard) => return (None, guard),
+                    None => lock.clone(),
+                },
+                slot => {
+                    let file = slot.and_then(Slot::upgrade);
+                    let lock = Arc::new(Mutex::new(()));
+                    let guard = lock.try_lock_arc().unwrap();
+                    tab.insert(path.to_path_buf(), Slot::Opening(lock));
+                    return (file, guard);
+                }
+            };
+            drop(tab);
+            let _ = opening.lock_arc().await;
+        }
+    }
+
+    //将新打开的文件登记到打开文件表并释放占位，如果占位已被移除且期间已有其它任务打开了同一路径，则返回已打开的文件
+    async fn register(path: PathBuf, file: Arc<InnerSafeFile>, guard: MutexGuardArc<()>) -> Self {
+        let mut tab = OPEN_FILE_MAP.shard(&path).lock().await;
+        let file = match tab.entry(path) {
             Entry::Occupied(mut e) => match e.get().upgrade() {
-                Some(rr) => return Ok(SafeFile(rr)),
+                Some(rr) => rr,
                 _ => {
-                    e.insert(Arc::downgrade(&file));
-                    Ok(SafeFile(file))
+                    e.insert(Slot::Open(Arc::downgrade(&file)));
+                    file
                 }
             },
             Entry::Vacant(e) => {
-                e.insert(Arc::downgrade(&file));
-                Ok(SafeFile(file))
+                e.insert(Slot::Open(Arc::downgrade(&file)));
+                file
+            }
+        };
+        drop(tab);
+        drop(guard);
+        keep_alive(&file).await;
+        SafeFile(file)
+    }
+
+    //打开失败后移除本任务的占位，并唤醒等待的任务
+    async fn unreserve(path: &Path, guard: MutexGuardArc<()>) {
+        let mut tab = OPEN_FILE_MAP.shard(path).lock().await;
+        if let Some(Slot::Opening(lock)) = tab.get(path) {
+            if Arc::ptr_eq(lock, MutexGuardArc::source(&guard)) {
+                tab.remove(path);
             }
         }
     }
+
+    //移除已放弃打开的任务的占位，占位仍被其它任务持有则保留
+    async fn remove_abandoned(path: &Path) {
+        let mut tab = OPEN_FILE_MAP.shard(path).lock().await;
+        if let Some(Slot::Opening(lock)) = tab.get(path) {
+            if lock.try_lock().is_some() {
+                tab.remove(path);
+            }
+        }
+    }
+    //获取指定路径已打开的文件，未打开或正在打开则返回None
+    #[cfg(feature = "serde")]
+    async fn opened(path: &Path) -> Option<Self> {
+        let tab = OPEN_FILE_MAP.shard(path).lock().await;
+        tab.get(path).and_then(Slot::upgrade).map(SafeFile)
+    }
+    //以指定方式异步打开指定的文件，如果路径已以不兼容的方式打开，则返回FileError::Incompatible错误
+    pub async fn try_open<P>(path: P, options: AsyncFileOptions) -> FileResult<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let _op = runtime::enter().map_err(|e| FileError::io(&path, e))?;
+        SafeFile::open_shared(path.clone(), options, CacheOptions::default())
+            .await
+            .map(|(file, _)| file)
+            .map_err(|e| match e {
+                FileError::Io { path: None, err } => FileError::io(&path, err),
+                e => e,
+            })
+    }
+
+    //从指定位置开始异步读指定字节，错误携带文件路径
+    pub async fn try_read(&self, pos: u64, len: usize) -> FileResult<Vec<u8>> {
+        self.read(pos, len)
+            .await
+            .map_err(|e| FileError::io(&self.0.path, e))
+    }
+
+    //从指定位置开始异步写指定字节，错误携带文件路径
+    pub async fn try_write(&self, pos: u64, buf: Arc<[u8]>, options: WriteOptions) -> FileResult<usize> {
+        self.write(pos, buf, options)
+            .await
+            .map_err(|e| FileError::io(&self.0.path, e))
+    }
+
+    //获取文件路径
+    pub fn path(&self) -> &Path {
+        &self.0.path
+    }
+
+    //获取同一路径所有句柄累计读取和写入的字节数
+    pub fn io_stats(&self) -> (u64, u64) {
+        (
+            self.0.read_bytes.load(Ordering::Relaxed),
+            self.0.written_bytes.load(Ordering::Relaxed),
+        )
+    }
+
+    //获取同一路径所有句柄累计的读缓存命中和未命中数
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.0.cache_hits.load(Ordering::Relaxed),
+            misses: self.0.cache_misses.load(Ordering::Relaxed),
+        }
+    }
+
     //从指定位置开始异步读指定字节
     pub async fn read(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let _op = runtime::enter()?;
         if len == 0 {
             //无效的字节数，则立即返回
             return Ok(Vec::with_capacity(0));
         }
+        let before = self.0.cache_memory();
+        let r = self.read_range(pos, len).await?;
+        self.0.count_read(r.len());
+        if self.0.cache_memory() > before {
+            // 本次读填充了缓存，超过全局上限则释放最久未访问的文件的缓存
+            limit_cache_memory().await;
+        }
+        Ok(r)
+    }
+
+    //从指定位置开始异步读指定字节，优先从内存映射或缓存中读取
+    //内存映射和截断写文件的缓冲数据在首次轮询时直接返回，不等待锁，也不经过运行时
+    async fn read_range(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        #[cfg(feature = "mmap")]
+        if let Some(mmap) = self.0.mmap.load_full() {
+            // 内存映射的只读文件，直接复制映射中指定范围的数据
+            let start = (pos as usize).min(mmap.len());
+            let end = start.saturating_add(len).min(mmap.len());
+            return Ok(mmap[start..end].to_vec());
+        }
+        if let Some(ref pages) = self.0.pages {
+            let _guard = self.read_lock().await;
+            return self.read_paged(pages, pos, len).await;
+        }
         match self.0.lock {
             // 如果是截断写，则读取缓冲区的数据
             LockType::Lock(ref lock) => {
-                let data = {
-                    let lock = self.0.buff.lock();
-                    lock.0.clone()
-                };
-                lock.lock().await;
-                if data.len() > 0 {
-                    // 如果有数据，则直接返回
-                    Ok(Vec::from([])) // TODO .slice(pos, pos + usize)
-                } else {
-                    match self.0.file.read(pos, len).await {
-                        Ok(r) => {
-                            // 如果是全数据，则缓存读到的数据 TODO
-                            let mut lock = self.0.buff.lock();
-                            lock.0 = Arc::from(&r[..]);
-                            Ok(r)
-                        }
-                        Err(r) => Err(r),
-                    }
+                // 缓冲数据总是最近一次写入的全数据，无论是否落地，都不需要等待写入释放互斥锁
+                if let Some(r) = self.0.cached(pos, len) {
+                    return Ok(r);
                 }
+                let _guard = lock.lock().await;
+                self.read_and_cache(pos, len).await
             }
             LockType::Rw(ref lock) => {
-                lock.read().await;
-                self.0.file.read(pos, len).await
+                // 持有读锁直到文件读取完成
+                let _guard = lock.read().await;
+                match self.0.cached(pos, len) {
+                    Some(r) => Ok(r),
+                    None => self.read_and_cache(pos, len).await,
+                }
             }
+            // 只读文件不会被修改，不需要加锁
+            LockType::Immutable => match self.0.cached(pos, len) {
+                Some(r) => Ok(r),
+                None => self.read_and_cache(pos, len).await,
+            },
         }
     }
 
-    //从指定位置开始异步写指定字节
-    pub async fn write(&self, pos: u64, buf: Arc<[u8]>, options: WriteOptions) -> Result<usize> {
-        if buf.len() == 0 {
-            //无效的字节数，则立即返回
-            return Ok(0);
+    //从指定位置开始异步读指定字节，超时则放弃本次读并返回TimedOut错误
+    //超时后未完成的读会被丢弃并释放持有的锁，已提交到运行时的底层读仍会完成，但结果被忽略
+    pub async fn read_timeout(&self, pos: u64, len: usize, timeout_ms: u64) -> Result<Vec<u8>> {
+        let read = Box::pin(self.read(pos, len));
+        let timer = FILE_RUNTIME.timeout(timeout_ms as usize);
+        match future::select(read, timer).await {
+            Either::Left((r, _)) => r,
+            Either::Right(_) => Err(Error::new(
+                ErrorKind::TimedOut,
+                format!(
+                    "Read file failed, file: {:?}, pos: {}, len: {}, reason: timeout after {}ms",
+                    self.path(),
+                    pos,
+                    len,
+                    timeout_ms
+                ),
+            )),
         }
-        match self.0.lock {
-            // 如果是截断写，则必须为全数据，忽略pos，则先设置缓冲区的数据和版本
-            LockType::Lock(ref lock) => {
-                {
-                    let mut lock = self.0.buff.lock();
-                    lock.0 = buf;
-                    lock.1 += 1;
-                    lock.1
-                };
-                lock.lock().await;
-                let data_ver = {
-                    // 获得异步锁后先获取数据及版本
-                    let lock = self.0.buff.lock();
-                    (lock.0.clone(), lock.1)
-                };
-                if data_ver.1 == 0 {
-                    // 最新数据已经落地，则直接返回成功
-                    Ok(data_ver.0.len())
-                } else {
-                    match self.0.file.write(pos, data_ver.0, options).await {
-                        Ok(r) => {
-                            // 写成功后再次获取锁
-                            let mut lock = self.0.buff.lock();
-                            // 比较版本号， 如果相同，则将版本号设为0，表示数据已经落地
-                            if lock.1 == data_ver.1 {
-                                lock.1 = 0;
-                            }
-                            Ok(r)
-                        }
-                        Err(r) => Err(r),
-                    }
+    }
+
+    //预读指定范围的数据到读缓存，预读在运行时上异步执行，不等待完成
+    //读缓存以整个文件为单位，因此会预读整个文件，未开启缓存、已有缓存或文件超过缓存上限时忽略
+    pub async fn prefetch(&self, pos: u64, len: usize) {
+        if !self.0.cache.enable || len == 0 || !self.0.buffered().is_empty() {
+            return;
+        }
+        let size = self.0.file().get_size().max(pos.saturating_add(len as u64));
+        if !self.0.cacheable(size as usize) {
+            return;
+        }
+        let file = self.clone();
+        let _ = FILE_RUNTIME.spawn(async move {
+            let _ = file.read(0, size as usize).await;
+        });
+    }
+
+    //按页从缓存读取指定范围的数据，有页未缓存则一次读取范围涉及的所有页并填充缓存，调用前需要持有锁
+    async fn read_paged(&self, pages: &PageCache, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let versions = match pages.get(pos, len) {
+            Ok(r) => {
+                self.0.count_cache(true);
+                return Ok(r);
+            }
+            Err(versions) => versions,
+        };
+        self.0.count_cache(false);
+        let start = pages.page_start(pos);
+        let span = versions.len() * pages.page_size();
+        let file = self.0.file();
+        let data = runtime::retry(|| file.read(start, span)).await?;
+        pages.fill(start / pages.page_size() as u64, &versions, &data);
+        let offset = (pos - start) as usize;
+        Ok(data[offset.min(data.len())..(offset + len).min(data.len())].to_vec())
+    }
+
+    //从文件读指定字节，如果是全数据且允许缓存，则缓存读到的数据，调用前需要持有锁
+    //同一范围的并发读共享一次IO，失败的读不会影响之后的读
+    async fn read_and_cache(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let gen = self.0.gen.load(Ordering::Acquire);
+        let r = loop {
+            let (flight, sender) = self.0.join_read(gen, pos, len);
+            let r = match sender {
+                // 底层读只能由发起读的任务轮询，共享的只是读的结果
+                // 发起读的任务返回原始错误，等待的任务得到错误的副本
+                Some(sender) => {
+                    let file = self.0.file();
+                    let r = runtime::retry(|| file.read(pos, len)).await.map(Arc::from);
+                    sender.send(match r {
+                        Ok(ref data) => Ok(Arc::clone(data)),
+                        Err(ref e) => Err(Arc::new(copy_error(e))),
+                    });
+                    Some(r)
                 }
+                None => flight.wait().await.map(|r| r.map_err(|e| copy_error(&e))),
+            };
+            self.0.finish_read(pos, len, &flight);
+            match r {
+                Some(r) => break r,
+                // 发起读的任务已放弃，重新加入或发起读
+                None => continue,
             }
-            LockType::Rw(ref lock) => {
-                lock.write().await;
-                self.0.file.write(pos, buf, options).await
+        };
+        let r = r?;
+        if pos == 0 && r.len() as u64 >= self.0.file().get_size() {
+            self.0.fill_cache(gen, &r);
+        }
+        Ok(r.to_vec())
+    }
+
+    //从指定位置开始异步读数据到指定缓冲区，返回读取的字节数，读到文件尾则少于缓冲区长度
+    //有内存映射或缓冲数据时直接复制，否则由运行时读入从缓冲区池借出的缓冲区后复制，不阻塞调用者的线程
+    pub async fn read_into(&self, pos: u64, buf: &mut [u8]) -> Result<usize> {
+        let _op = runtime::enter()?;
+        if buf.is_empty() {
+            //无效的字节数，则立即返回
+            return Ok(0);
+        }
+        let len = buf.len();
+        if let Some(n) = self.with_cached(pos, len, |data| {
+            buf[..data.len()].copy_from_slice(data);
+            data.len()
+        }) {
+            self.0.count_read(n);
+            return Ok(n);
+        }
+        let r = self.read_pooled(pos, len).await?;
+        buf[..r.len()].copy_from_slice(&r);
+        Ok(r.len())
+    }
+
+    //从内存映射或缓冲数据中取出指定范围的数据交给指定函数处理，都没有则返回None
+    fn with_cached<R>(&self, pos: u64, len: usize, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        #[cfg(feature = "mmap")]
+        if let Some(mmap) = self.0.mmap.load_full() {
+            let start = (pos as usize).min(mmap.len());
+            let end = start.saturating_add(len).min(mmap.len());
+            return Some(f(&mmap[start..end]));
+        }
+        let data = self.0.buffered();
+        if data.is_empty() {
+            return None;
+        }
+        self.0.count_cache(true);
+        let start = (pos as usize).min(data.len());
+        let end = start.saturating_add(len).min(data.len());
+        Some(f(&data[start..end]))
+    }
+
+    //从指定位置开始异步读取准确的字节数，可读数据不足则返回UnexpectedEof
+    pub async fn read_exact(&self, pos: u64, len: usize) -> Result<Vec<u8>> {
+        let mut data = self.read(pos, len).await?;
+        while data.len() < len {
+            let r = self.read(pos + data.len() as u64, len - data.len()).await?;
+            if r.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    format!(
+                        "Read exact failed, file: {:?}, pos: {}, len: {}, readed: {}",
+                        self.path(),
+                        pos,
+                        len,
+                        data.len()
+                    ),
+                ));
             }
+            data.extend_from_slice(&r);
         }
+        Ok(data)
     }
-}
+
+    //异步读取文件的全部数据
+    pub async fn read_to_end(&self) -> Result<Vec<u8>> {
+        self.read_whole(false).await.map(|(r, _)| r)
+    }
+
+    //获取截断写文件的当前版本，只需要缓冲区的自旋锁，不需要获取异步锁，可用于write_if_version
+    //版本在每次通过本库写入或改变文件长度时增加，同一路径的所有句柄共享版本，其它进程的修改不会改变版本
+    //截断写文件在更新缓冲数据时即增加版本，不等待数据落地
+    //其它文件总是返回UNVERSIONED，可读写文件应使用read_to_end_versioned返回的版本
+    pub fn version(&self) -> u64 {
+        if let LockType::Lock(_) = self.0.lock {
+            let _lock = self.0.buff_lock.lock();
+            return self.0.gen.load(Ordering::Acquire) as u64;
+        }
+        UNVERSIONED
+    }
+
+    //异步读取文件的全部数据，同时返回读到的数据对应的版本，用于之后的条件写入
+    //可读写文件的版本还包括文件的长度和修改时间，其它进程的修改也会使条件写入冲突，因此总是从文件读取，不使用缓存
+    pub async fn read_to_end_versioned(&self) -> Result<(Vec<u8>, u64)> {
+        self.read_whole(true).await
+    }
+
+    //异步读取文件的全部数据和对应的版本，需要可读写文件的版本时不使用缓存
+    async fn read_whole(&self, versioned: bool) -> Result<(Vec<u8>, u64)> {
+        let _op = runtime::enter()?;
+        let (data, version) = loop {
+            // 写入先替换缓冲数据再增加版本，前后两次获取的版本相同时，数据不会旧于版本
+            // 数据可能新于版本，此时用该版本条件写入只会返回冲突，不会覆盖未读到的数据
+            let version = self.0.gen.load(Ordering::Acquire);
+            let data = self.0.buffered();
+            if self.0.gen.load(Ordering::Acquire) == version {
+                break (data, version);
+            }
+        };
+        let rw = versioned && matches!(self.0.lock, LockType::Rw(_));
+        if !data.is_empty() && !rw {
+            // 如果有数据，则直接返回缓冲区的数据
+            self.0.count_read(data.len());
+            self.0.count_cache(true);
+            return Ok((data.to_vec(), version as u64));
+        }
+        self.0.count_cache(false);
+        let _guard = self.read_lock().await;
+        let gen = self.0.gen.load(Ordering::Acquire);
+        let (r, version) = if rw {
+            // 其它进程可能同时修改文件，读取前后的版本相同时，数据与版本一致
+            loop {
+                let version = self.disk_version(gen).await?;
+                let r = self.read_all().await?;
+                if self.disk_version(gen).await? == version {
+                    break (r, version);
+                }
+            }
+        } else {
+            (self.read_all().await?, gen as u64)
+        };
+        // 读到的是全数据，如果期间没有新的写入，则缓存
+        self.0.fill_cache(gen, &r);
+        self.0.count_read(r.len());
+        Ok((r, version))
+    }
+
+    //获取可读写文件的版本，由写入的代数和文件的长度及修改时间组成，调用前需要持有文件锁，不会返回UNVERSIONED
+    async fn disk_version(&self, gen: usize) -> Result<u64> {
+        let file = self.0.file();
+        let meta = run_sync(move || file.get_inner()?.metadata()).await?;
+        let mut hasher = DefaultHasher::new();
+        (gen, meta.len(), meta.modified().ok()).hash(&mut hasher);
+        Ok(hasher.finish().min(UNVERSIONED - 1))
+    }
+
+    //异步获取文件长度，截断写文件有未落地的缓冲数据时，返回缓冲数据的长度
+    pub async fn len(&self) -> Result<u64> {
+        if let LockType::Lock(_) = self.0.lock {
+            let buff = self.0.buff.load();
+            if buff.pending != 0 {
+                return Ok(buff.data.len() as u64);
+            }
+        }
+        Ok(self.metadata().await?.len())
+    }
+
+    //异步获取文件元信息，允许缓存元信息时优先返回缓存的元信息
+    pub async fn metadata(&self) -> Result<Metadata> {
+        if let Some(meta) = self.0.meta.lock().clone() {
+            return Ok(meta);
+        }
+        self.refresh_metadata().await
+    }
+
+    //异步重新获取文件元信息，允许缓存元信息时更新缓存
+    pub async fn refresh_metadata(&self) -> Result<Metadata> {
+        let file = self.0.file();
+        let _guard = self.read_lock().await;
+        let meta = run_sync(move || file.get_inner()?.metadata()).await?;
+        if self.0.cache.metadata {
+            *self.0.meta.lock() = Some(meta.clone());
+        }
+        Ok(meta)
+    }
+
+    //获取读锁，截断写文件获取互斥锁，只读文件不加锁
+    async fn read_lock(&self) -> FileGuard<'_> {
+        match self.0.lock {
+            LockType::Lock(ref lock) => FileGuard::Lock { _guard: lock.lock().await },
+            LockType::Rw(ref lock) => FileGuard::Read { _guard: lock.read().await },
+            LockType::Immutable => FileGuard::None,
+        }
+    }
+
+    //获取写锁，截断写文件获取互斥锁，只读文件不加锁
+    async fn write_lock(&self) -> FileGuard<'_> {
+        match self.0.lock {
+            LockType::Lock(ref lock) => FileGuard::Lock { _guard: lock.lock().await },
+            LockType::Rw(ref lock) => FileGuard::Write { _guard: lock.write().await },
+            LockType::Immutable => FileGuard::None,
+        }
+    }
+
+    //从文件头开始，按指定大小分块读取文件，直到文件尾，最后一块可能不足指定大小
+    pub fn chunks(&self, chunk_size: usize) -> impl Stream<Item = Result<Vec<u8>>> {
+        let file = self.clone();
+        stream::unfold(Some(0u64), move |state| {
+            let file = file.clone();
+            async move {
+                let pos = state?;
+                match file.read(pos, chunk_size).await {
+                    Ok(r) if r.is_empty() => None,
+                    Ok(r) => {
+                        // 不足一块，则已读到文件尾
+                        let next = if r.len() < chunk_size {
+                            None
+                        } else {
+                            Some(pos + r.len() as u64)
+                        };
+                        Some((Ok(r), next))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        })
+    }
+
+    //从指定位置开始跟随读取文件新增的数据，文件增长时读取新增的部分，每次最多读取READ_CHUNK_SIZE字节
+    //没有新数据时在FILE_RUNTIME上等待FOLLOW_INTERVAL毫秒后再检查，文件被截断时从文件头重新读取，文件被移除时结束
+    //跟随读取直接读文件，不使用读缓存，因此可以读到其它进程追加的数据
+    pub fn follow(&self, from: u64) -> impl Stream<Item = Result<Vec<u8>>> {
+        let file = self.clone();
+        stream::unfold(Some(from), move |state| {
+            let file = file.clone();
+            async move {
+                let mut pos = state?;
+                loop {
+                    let path = file.0.path.clone();
+                    let len = match run_sync(move || fs::metadata(path)).await {
+                        Err(e) if e.kind() == ErrorKind::NotFound => return None,
+                        Err(e) => return Some((Err(e), None)),
+                        Ok(meta) => meta.len(),
+                    };
+                    if len < pos {
+                        // 文件被截断，从文件头重新读取
+                        pos = 0;
+                    }
+                    if len > pos {
+                        let size = ((len - pos) as usize).min(READ_CHUNK_SIZE);
+                        let handle = file.0.file();
+                        return match runtime::retry(|| handle.read(pos, size)).await {
+                            Ok(r) => {
+                                file.0.count_read(r.len());
+                                let next = pos + r.len() as u64;
+                                Some((Ok(r), Some(next)))
+                            }
+                            Err(e) => Some((Err(e), None)),
+                        };
+                    }
+                    FILE_RUNTIME.timeout(FOLLOW_INTERVAL).await;
+                }
+            }
+        })
+    }
+
+    //读取文件的全部数据，如果读取期间文件增长，则继续读到文件尾，调用前需要持有锁
+    async fn read_all(&self) -> Result<Vec<u8>> {
+        let mut len = self.0.file().get_size() as usize;
+        let mut data: Vec<u8> = Vec::new();
+        loop {
+            if len == 0 {
+                len = READ_CHUNK_SIZE;
+            }
+            let file = self.0.file();
+            let r = runtime::retry(|| file.read(data.len() as u64, len)).await?;
+            let size = r.len();
+            if data.is_empty() {
+                data = r;
+            } else {
+                data.extend_from_slice(&r);
+            }
+            if size < len {
+                // 已读到文件尾
+                return Ok(data);
+            }
+            len = READ_CHUNK_SIZE;
+        }
+    }
+
+    //从指定位置开始异步写指定字节，截断写文件每次写入都会先清空文件再写到指定位置，之前的部分补零，补零超过MAX_TRUNCATE_GAP则返回InvalidInput错误
+    pub async fn write(&self, pos: u64, buf: Arc<[u8]>, options: WriteOptions) -> Result<usize> {
+        let _op = runtime::enter()?;
+        if buf.len() == 0 {
+            //无效的字节数，则立即返回
+            return Ok(0);
+        }
+        space::check_space(self.path(), buf.len() as u64).await?;
+        match self.0.lock {
+            // 如果是截断写，则先设置缓冲区的数据和版本，写入时会先清空文件再写到pos
+            LockType::Lock(ref lock) => {
+                {
+                    let _lock = self.0.buff_lock.lock();
+                    self.0.set_pending(pos, buf)?;
+                };
+                // 持有互斥锁，直到写入完成并比较版本
+                let _guard = lock.lock().await;
+                let r = self.write_pending(options).await?;
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Rw(ref lock) => {
+                // 持有写锁直到文件写入完成，追加模式则忽略pos，写到文件尾
+                let _guard = lock.write().await;
+                let pos = if self.is_append() {
+                    self.0.file().get_size()
+                } else {
+                    pos
+                };
+                let file = self.0.file();
+                let r = runtime::retry_write(|| file.write(pos, buf.clone(), options.clone()))
+                    .await
+                    .map_err(|e| self.out_of_space(e))?;
+                self.0.patch_cache(pos, &buf[..r]);
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Immutable => Err(self.read_only("Write file")),
+        }
+    }
+
+    //当前版本与指定版本相同时，异步用指定数据替换文件的全部数据，否则返回VersionConflict错误
+    //版本由read_to_end_versioned获取，截断写文件也可以由version获取，追加文件不支持
+    pub async fn write_if_version(&self, buf: Arc<[u8]>, expected: u64) -> Result<usize> {
+        let _op = runtime::enter()?;
+        space::check_space(self.path(), buf.len() as u64).await?;
+        match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let empty = buf.is_empty();
+                {
+                    // 在缓冲区锁内比较并设置缓冲数据和版本，与其它写入互斥，空数据没有需要落地的缓冲数据
+                    let _lock = self.0.buff_lock.lock();
+                    self.check_version(expected, self.0.gen.load(Ordering::Acquire) as u64)?;
+                    if empty {
+                        self.0.set_buff(buf, 0);
+                        self.0.gen.fetch_add(1, Ordering::AcqRel);
+                    } else {
+                        self.0.set_pending(0, buf)?;
+                    }
+                }
+                let _guard = lock.lock().await;
+                if empty {
+                    let file = self.0.file();
+                    run_sync(move || file.get_inner()?.set_len(0)).await?;
+                    self.0.meta.lock().take();
+                    return Ok(0);
+                }
+                let r = self.write_pending(WriteOptions::None).await?;
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Rw(ref lock) => {
+                if self.is_append() {
+                    return Err(Error::new(
+                        ErrorKind::Unsupported,
+                        format!("Write file failed, file: {:?}, reason: conditional write to append file", self.path()),
+                    ));
+                }
+                // 持有写锁直到文件写入完成，期间本库的写入不会改变版本
+                let _guard = lock.write().await;
+                self.check_version(expected, self.disk_version(self.0.gen.load(Ordering::Acquire)).await?)?;
+                if buf.is_empty() {
+                    let file = self.0.file();
+                    run_sync(move || file.get_inner()?.set_len(0)).await?;
+                    self.0.resize_cache(0);
+                    return Ok(0);
+                }
+                let file = self.0.file();
+                let r = runtime::retry_write(|| file.write(0, buf.clone(), WriteOptions::Truncate))
+                    .await
+                    .map_err(|e| self.out_of_space(e))?;
+                self.0.resize_cache(r as u64);
+                self.0.patch_cache(0, &buf[..r]);
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Immutable => Err(self.read_only("Write file")),
+        }
+    }
+
+    //文件在指定时间后未被修改时，从指定位置开始异步写指定字节，否则返回Modified错误
+    //查询修改时间和写入在同一次加锁内完成，截断写文件会先写入未落地的缓冲数据再查询
+    pub async fn write_if_unmodified_since(&self, pos: u64, buf: Arc<[u8]>, since: SystemTime) -> Result<usize> {
+        let _op = runtime::enter()?;
+        if buf.is_empty() {
+            //无效的字节数，则立即返回
+            return Ok(0);
+        }
+        space::check_space(self.path(), buf.len() as u64).await?;
+        match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let _guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                self.check_unmodified(since).await?;
+                {
+                    let _lock = self.0.buff_lock.lock();
+                    self.0.set_pending(pos, buf)?;
+                }
+                let r = self.write_pending(WriteOptions::None).await?;
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Rw(ref lock) => {
+                let _guard = lock.write().await;
+                self.check_unmodified(since).await?;
+                let pos = if self.is_append() {
+                    self.0.file().get_size()
+                } else {
+                    pos
+                };
+                let file = self.0.file();
+                let r = runtime::retry_write(|| file.write(pos, buf.clone(), WriteOptions::None))
+                    .await
+                    .map_err(|e| self.out_of_space(e))?;
+                self.0.patch_cache(pos, &buf[..r]);
+                self.0.count_written(r);
+                Ok(r)
+            }
+            LockType::Immutable => Err(self.read_only("Write file")),
+        }
+    }
+
+    //查询磁盘上文件的修改时间，晚于指定时间则返回Modified错误，调用前需要持有锁
+    async fn check_unmodified(&self, since: SystemTime) -> Result<()> {
+        let file = self.0.file();
+        let modified = run_sync(move || file.get_inner()?.metadata()?.modified()).await?;
+        if modified > since {
+            return Err(FileError::Modified {
+                path: self.0.path.clone(),
+                since,
+                modified,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    //比较当前版本与指定版本，不同则返回VersionConflict错误
+    fn check_version(&self, expected: u64, current: u64) -> Result<()> {
+        if current != expected {
+            return Err(FileError::VersionConflict {
+                path: self.0.path.clone(),
+                expected,
+                current,
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    //从指定位置开始依次异步写入多个缓冲区，返回实际写入的总字节数，部分写入时返回已写入的字节数
+    //截断写文件会将多个缓冲区合并为全数据后写入
+    pub async fn write_vectored(&self, pos: u64, bufs: &[Arc<[u8]>], options: WriteOptions) -> Result<usize> {
+        let _op = runtime::enter()?;
+        if bufs.iter().all(|buf| buf.is_empty()) {
+            //无效的字节数，则立即返回
+            return Ok(0);
+        }
+        let len = bufs.iter().map(|buf| buf.len() as u64).sum();
+        space::check_space(self.path(), len).await?;
+        let lock = match self.0.lock {
+            LockType::Lock(_) => return self.write(pos, Arc::from(bufs.concat()), options).await,
+            LockType::Rw(ref lock) => lock,
+            LockType::Immutable => return Err(self.read_only("Write vectored file")),
+        };
+        // 持有写锁直到文件写入完成，追加模式则忽略pos，写到文件尾
+        let _guard = lock.write().await;
+        let pos = if self.is_append() {
+            self.0.file().get_size()
+        } else {
+            pos
+        };
+        let file = self.0.file();
+        let batch = bufs.to_vec();
+        let r = run_sync(move || {
+            let file = file.get_inner()?;
+            let r = write_vectored_at(&file, pos, &batch)?;
+            match options {
+                WriteOptions::None | WriteOptions::Flush => (),
+                WriteOptions::Sync(_) => file.sync_data()?,
+                WriteOptions::SyncAll(_) => file.sync_all()?,
+                WriteOptions::Truncate => file.set_len(pos + r as u64)?,
+            }
+            Ok(r)
+        })
+        .await
+        .map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Write vectored file failed, file: {:?}, pos: {}, reason: {:?}", self.path(), pos, e),
+            )
+        })
+        .map_err(|e| self.out_of_space(e))?;
+        let data = bufs.concat();
+        self.0.patch_cache(pos, &data[..r]);
+        self.0.count_written(r);
+        Ok(r)
+    }
+
+    //持有一次写锁，按顺序异步写入多个范围，重叠的范围以后写入的为准，写入期间读取者不会看到部分写入的结果
+    //写选项只在最后一个范围写入时使用，中途出错时已写入的范围不会回滚，截断写文件不支持批量写
+    pub async fn write_batch(&self, writes: Vec<(u64, Arc<[u8]>)>, options: WriteOptions) -> Result<()> {
+        let _op = runtime::enter()?;
+        let lock = match self.0.lock {
+            LockType::Lock(_) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Write batch file failed, file: {:?}, reason: truncate write file", self.path()),
+                ))
+            }
+            LockType::Rw(ref lock) => lock,
+            LockType::Immutable => return Err(self.read_only("Write batch file")),
+        };
+        let last = match writes.iter().rposition(|(_, buf)| !buf.is_empty()) {
+            None => return Ok(()),
+            Some(last) => last,
+        };
+        let len = writes.iter().map(|(_, buf)| buf.len() as u64).sum();
+        space::check_space(self.path(), len).await?;
+        let _guard = lock.write().await;
+        for (index, (pos, buf)) in writes.into_iter().enumerate().take(last + 1) {
+            if buf.is_empty() {
+                continue;
+            }
+            let opts = if index == last {
+                options.clone()
+            } else {
+                WriteOptions::None
+            };
+            let pos = if self.is_append() {
+                self.0.file().get_size()
+            } else {
+                pos
+            };
+            let file = self.0.file();
+            let r = runtime::retry_write(|| file.write(pos, buf.clone(), opts.clone()))
+                .await
+                .map_err(|e| self.out_of_space(e))?;
+            self.0.patch_cache(pos, &buf[..r]);
+            self.0.count_written(r);
+        }
+        Ok(())
+    }
+
+    //持有一次写锁，异步写入多个补丁，补丁按位置排序，相邻或重叠的补丁合并后一次写入，重叠的部分以后面的补丁为准
+    //中途出错时已写入的补丁不会回滚，截断写文件和追加文件不支持补丁
+    pub async fn apply_patch(&self, ops: &[(u64, Arc<[u8]>)]) -> Result<()> {
+        let _op = runtime::enter()?;
+        let lock = match self.0.lock {
+            LockType::Rw(ref lock) if !self.is_append() => lock,
+            LockType::Immutable => return Err(self.read_only("Patch file")),
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("Patch file failed, file: {:?}, reason: truncate write or append file", self.path()),
+                ))
+            }
+        };
+        let mut order: Vec<usize> = (0..ops.len()).filter(|&index| !ops[index].1.is_empty()).collect();
+        if order.is_empty() {
+            return Ok(());
+        }
+        order.sort_by_key(|&index| ops[index].0);
+
+        // 将相邻或重叠的补丁分为一组，每组合并为一次写入
+        let mut runs: Vec<(u64, u64, Vec<usize>)> = Vec::new();
+        for index in order {
+            let (pos, buf) = &ops[index];
+            let end = pos + buf.len() as u64;
+            match runs.last_mut() {
+                Some((_, run_end, members)) if *pos <= *run_end => {
+                    *run_end = (*run_end).max(end);
+                    members.push(index);
+                }
+                _ => runs.push((*pos, end, vec![index])),
+            }
+        }
+        let len = runs.iter().map(|(start, end, _)| end - start).sum();
+        space::check_space(self.path(), len).await?;
+
+        let _guard = lock.write().await;
+        for (start, end, mut members) in runs {
+            let buf: Arc<[u8]> = if members.len() == 1 {
+                ops[members[0]].1.clone()
+            } else {
+                // 按补丁的原始顺序复制，后面的补丁覆盖前面的
+                members.sort_unstable();
+                let mut data = vec![0; (end - start) as usize];
+                for index in members {
+                    let (pos, buf) = &ops[index];
+                    let offset = (pos - start) as usize;
+                    data[offset..offset + buf.len()].copy_from_slice(buf);
+                }
+                Arc::from(data)
+            };
+            let file = self.0.file();
+            let r = runtime::retry_write(|| file.write(start, buf.clone(), WriteOptions::None))
+                .await
+                .map_err(|e| self.out_of_space(e))?;
+            self.0.patch_cache(start, &buf[..r]);
+            self.0.count_written(r);
+        }
+        Ok(())
+    }
+
+    //异步追加写指定字节到文件尾，截断写文件不支持追加
+    pub async fn append(&self, buf: Arc<[u8]>) -> Result<usize> {
+        self.append_at(buf).await.map(|(_, r)| r)
+    }
+
+    //异步追加写指定字节到文件尾，返回写入的位置和字节数
+    async fn append_at(&self, buf: Arc<[u8]>) -> Result<(u64, usize)> {
+        let _op = runtime::enter()?;
+        if buf.is_empty() {
+            //无效的字节数，则立即返回
+            return Ok((self.len().await?, 0));
+        }
+        match self.0.lock {
+            LockType::Lock(_) => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Append file failed, file: {:?}, reason: truncate write file", self.path()),
+            )),
+            LockType::Rw(ref lock) => {
+                // 同一路径的所有句柄共享写锁，追加不会交错
+                let _guard = lock.write().await;
+                let file = self.0.file();
+                let pos = run_sync(move || Ok(file.get_inner()?.metadata()?.len()))
+                    .await
+                    .map_err(|e| {
+                        Error::new(
+                            e.kind(),
+                            format!("Append file failed, file: {:?}, reason: {:?}", self.path(), e),
+                        )
+                    })?;
+                let file = self.0.file();
+                let r = runtime::retry_write(|| file.write(pos, buf.clone(), WriteOptions::None))
+                    .await
+                    .map_err(|e| self.out_of_space(e))?;
+                self.0.patch_cache(pos, &buf[..r]);
+                self.0.count_written(r);
+                Ok((pos, r))
+            }
+            LockType::Immutable => Err(self.read_only("Append file")),
+        }
+    }
+
+    //异步设置文件长度，缩短则截断，加长则补零，截断写文件会先写入未落地的缓冲数据
+    pub async fn set_len(&self, size: u64) -> Result<()> {
+        let _op = runtime::enter()?;
+        let _guard = match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                FileGuard::Lock { _guard: guard }
+            }
+            LockType::Rw(ref lock) => FileGuard::Write { _guard: lock.write().await },
+            LockType::Immutable => return Err(self.read_only("Set file len")),
+        };
+        let file = self.0.file();
+        run_sync(move || file.get_inner()?.set_len(size))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Set file len failed, file: {:?}, len: {}, reason: {:?}", self.path(), size, e),
+                )
+            })?;
+        self.0.resize_cache(size);
+        Ok(())
+    }
+
+    //异步为文件从头开始预分配指定长度的空间，减少碎片，文件不足该长度则加长，加长部分读为零，不会缩短文件
+    //文件系统或平台不支持预分配时改为设置文件长度，截断写文件会先写入未落地的缓冲数据
+    pub async fn preallocate(&self, len: u64) -> Result<()> {
+        let _op = runtime::enter()?;
+        let _guard = match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                FileGuard::Lock { _guard: guard }
+            }
+            LockType::Rw(ref lock) => FileGuard::Write { _guard: lock.write().await },
+            LockType::Immutable => return Err(self.read_only("Preallocate file")),
+        };
+        let file = self.0.file();
+        let size = run_sync(move || falloc::preallocate(&file.get_inner()?, len))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Preallocate file failed, file: {:?}, len: {}, reason: {:?}", self.path(), len, e),
+                )
+            })?;
+        self.0.resize_cache(size);
+        Ok(())
+    }
+
+    //异步释放文件指定范围占用的磁盘空间，不改变文件长度，之后范围内读为零，范围超出文件尾的部分忽略
+    //文件系统或平台不支持时返回Unsupported错误，截断写文件会先写入未落地的缓冲数据
+    pub async fn punch_hole(&self, offset: u64, len: u64) -> Result<()> {
+        let _op = runtime::enter()?;
+        let _guard = match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                FileGuard::Lock { _guard: guard }
+            }
+            LockType::Rw(ref lock) => FileGuard::Write { _guard: lock.write().await },
+            LockType::Immutable => return Err(self.read_only("Punch hole file")),
+        };
+        let file = self.0.file();
+        let len = run_sync(move || falloc::punch_hole(&file.get_inner()?, offset, len))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!(
+                        "Punch hole file failed, file: {:?}, offset: {}, len: {}, reason: {:?}",
+                        self.path(),
+                        offset,
+                        len,
+                        e
+                    ),
+                )
+            })?;
+        if len > 0 {
+            self.0.zero_cache(offset, len);
+        }
+        Ok(())
+    }
+
+    //关闭文件，如果是最后一个句柄，则写入截断写文件未落地的缓冲数据，从打开文件表中移除条目并关闭文件，返回写入的错误
+    //否则只释放本句柄，本库为最近访问而保留的引用不计入句柄
+    pub async fn close(self) -> Result<()> {
+        let _op = runtime::enter()?;
+        {
+            let mut retained = KEEP_ALIVE.lock().await;
+            if Arc::strong_count(&self.0) == 2
+                && retained
+                    .get(&self.0.path)
+                    .is_some_and(|file| Arc::ptr_eq(file, &self.0))
+            {
+                retained.remove(&self.0.path);
+            }
+        }
+        if Arc::strong_count(&self.0) > 1 {
+            return Ok(());
+        }
+        let r = match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let _guard = lock.lock().await;
+                self.write_pending(WriteOptions::Flush).await.map(|_| ())
+            }
+            LockType::Rw(_) | LockType::Immutable => Ok(()),
+        };
+        // 持有表时再次确认，期间其它任务可能已重新打开本文件
+        let mut tab = OPEN_FILE_MAP.shard(&self.0.path).lock().await;
+        if Arc::strong_count(&self.0) == 1 {
+            remove_closed(&mut tab, &self.0.path, Arc::as_ptr(&self.0) as usize);
+        }
+        drop(tab);
+        drop(self);
+        r
+    }
+
+    //将截断写文件未落地的缓冲数据写入文件，非截断写文件忽略
+    pub async fn flush(&self) -> Result<()> {
+        let _op = runtime::enter()?;
+        if let LockType::Lock(ref lock) = self.0.lock {
+            let _guard = lock.lock().await;
+            self.write_pending(WriteOptions::Flush).await?;
+        }
+        Ok(())
+    }
+
+    //将未落地的缓冲数据写入文件，并同步文件的数据和元信息到磁盘
+    pub async fn sync_all(&self) -> Result<()> {
+        self.sync(true).await
+    }
+
+    //将未落地的缓冲数据写入文件，并同步文件的数据到磁盘
+    pub async fn sync_data(&self) -> Result<()> {
+        self.sync(false).await
+    }
+
+    //固定文件的读缓存，固定后drop_all_caches不会释放该文件的缓存，本库保留的文件也不会因最久未访问而被释放
+    //同一路径的所有句柄共享固定状态
+    pub fn pin_cache(&self) {
+        self.0.pinned.store(true, Ordering::Release);
+    }
+
+    //取消固定文件的读缓存
+    pub fn unpin_cache(&self) {
+        self.0.pinned.store(false, Ordering::Release);
+    }
+
+    //文件的读缓存是否已固定
+    pub fn is_cache_pinned(&self) -> bool {
+        self.0.pinned.load(Ordering::Acquire)
+    }
+
+    //释放文件的读缓存，不关闭文件，截断写文件会先写入未落地的缓冲数据再释放，缓存已固定时也会释放
+    pub async fn drop_cache(&self) -> Result<()> {
+        let _op = runtime::enter()?;
+        match self.0.lock {
+            LockType::Lock(ref lock) => {
+                // 持有互斥锁，写入期间不会有新的缓冲数据落地，之后的新数据仍未落地，不会被释放
+                let _guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                self.0.clear_cache();
+            }
+            LockType::Rw(_) | LockType::Immutable => self.0.clear_cache(),
+        }
+        Ok(())
+    }
+
+    async fn sync(&self, all: bool) -> Result<()> {
+        let _guard = match self.0.lock {
+            LockType::Lock(ref lock) => {
+                let guard = lock.lock().await;
+                self.write_pending(WriteOptions::None).await?;
+                Some(guard)
+            }
+            LockType::Rw(_) | LockType::Immutable => None,
+        };
+        let file = self.0.file();
+        run_sync(move || {
+            let file = file.get_inner()?;
+            if all {
+                file.sync_all()
+            } else {
+                file.sync_data()
+            }
+        })
+        .await
+    }
+
+    //阻塞的获取进程间的独占建议锁，与进程内的读写锁相互独立，同一文件的所有句柄共享这把锁
+    pub async fn lock_exclusive(&self) -> Result<()> {
+        self.os_lock(OsLock::Exclusive).await
+    }
+
+    //阻塞的获取进程间的共享建议锁，与进程内的读写锁相互独立，同一文件的所有句柄共享这把锁
+    pub async fn lock_shared(&self) -> Result<()> {
+        self.os_lock(OsLock::Shared).await
+    }
+
+    //释放进程间的建议锁
+    pub async fn unlock(&self) -> Result<()> {
+        self.os_lock(OsLock::Unlock).await
+    }
+
+    async fn os_lock(&self, lock: OsLock) -> Result<()> {
+        let file = self.0.file();
+        run_sync(move || os_lock::flock(&file.get_inner()?, lock))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!("Lock file failed, file: {:?}, lock: {:?}, reason: {:?}", self.path(), lock, e),
+                )
+            })
+    }
+
+    //为指定范围设置访问模式的建议，长度为0表示到文件尾，例如读完后建议DontNeed以释放内核的页缓存
+    //只影响内核的预读和页缓存，不影响本库的读缓存，不支持的平台忽略
+    pub async fn advise(&self, offset: u64, len: u64, advice: Advice) -> Result<()> {
+        let file = self.0.file();
+        run_sync(move || advise::fadvise(&file.get_inner()?, offset, len, advice))
+            .await
+            .map_err(|e| {
+                Error::new(
+                    e.kind(),
+                    format!(
+                        "Advise file failed, file: {:?}, offset: {}, len: {}, advice: {:?}, reason: {:?}",
+                        self.path(),
+                        offset,
+                        len,
+                        advice,
+                        e
+                    ),
+                )
+            })
+    }
+
+    //将截断写文件未落地的缓冲数据写到最近一次写入指定的位置，返回写入的字节数，调用前需要持有互斥锁
+    //并发写入时只有最新的数据会被写入，不经过运行时关闭的检查，关闭运行时时也可以写入
+    async fn write_pending(&self, options: WriteOptions) -> Result<usize> {
+        // 获得异步锁后先获取数据及版本
+        let buff = self.0.buff.load_full();
+        let at = (buff.at as usize).min(buff.data.len());
+        if buff.pending == 0 {
+            // 最新数据已经由其它写入落地，但其它写入的选项可能未同步到磁盘，需要按本次的选项同步
+            let file = self.0.file();
+            match options {
+                WriteOptions::Sync(_) => run_blocking(move || file.get_inner()?.sync_data()).await?,
+                WriteOptions::SyncAll(_) => run_blocking(move || file.get_inner()?.sync_all()).await?,
+                _ => (),
+            }
+            return Ok(buff.data.len() - at);
+        }
+        let data_ver = (if at == 0 { buff.data.clone() } else { Arc::from(&buff.data[at..]) }, buff.pending);
+        let file = self.0.file();
+        let r = runtime::retry_write(|| file.write(at as u64, data_ver.0.clone(), options.clone()))
+            .await
+            .map_err(|e| self.out_of_space(e))?;
+        self.0.meta.lock().take();
+        // 写成功后获取缓冲区锁
+        let _lock = self.0.buff_lock.lock();
+        let buff = self.0.buff.load_full();
+        // 比较版本号， 如果相同，则将版本号设为0，表示数据已经落地
+        if buff.pending == data_ver.1 {
+            self.0.set_buff(buff.data.clone(), 0);
+        }
+        Ok(r)
+    }
+
+    //写入时空间不足的错误转换为OutOfSpace错误，其它错误不变
+    fn out_of_space(&self, e: Error) -> Error {
+        if e.kind() != ErrorKind::StorageFull {
+            return e;
+        }
+        FileError::OutOfSpace {
+            path: self.0.path.clone(),
+        }
+        .into()
+    }
+
+    //只读文件不支持修改的错误
+    fn read_only(&self, op: &str) -> Error {
+        Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} failed, file: {:?}, reason: read only file", op, self.path()),
+        )
+    }
+
+    //是否以追加方式打开
+    fn is_append(&self) -> bool {
+        matches!(
+            self.0.file().get_options(),
+            AsyncFileOptions::OnlyAppend | AsyncFileOptions::ReadAppend
+        )
+    }
+}
 
 /*
-* 打开异步文件
-*/
-pub async fn open<P>(path: P, options: AsyncFileOptions) -> Result<AsyncFile<()>>
-where
-    P: AsRef<Path> + Send + 'static,
-{
-    AsyncFile::open(FILE_RUNTIME.clone(), path, options).await
-}
-/*
-* 异步创建目录
+* 打开异步文件
 */
-pub async fn create_dir<P>(path: P) -> Result<()>
+pub async fn open<P>(path: P, options: AsyncFileOptions) -> Result<AsyncFile<()>>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::create_dir(FILE_RUNTIME.clone(), path).await
+    let _op = runtime::enter()?;
+    runtime::guard(AsyncFile::open(FILE_RUNTIME.clone(), path, options)).await
 }
 
 /*
-* 异步移除文件
+* 在FILE_RUNTIME上以有限的并发数批量打开安全文件，结果与输入的路径顺序一致，重复的路径共享同一个句柄
 */
-pub async fn remove_file<P>(path: P) -> Result<()>
+pub async fn open_many<P>(paths: Vec<P>, options: AsyncFileOptions) -> Vec<Result<SafeFile>>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::remove_file(FILE_RUNTIME.clone(), path).await
+    stream::iter(paths)
+        .map(|path| {
+            let options = options.clone();
+            async move {
+                let wait = FILE_RUNTIME.wait();
+                wait.spawn(FILE_RUNTIME.clone(), None, async move { SafeFile::open(path, options).await })?;
+                wait.wait_result().await
+            }
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await
 }
 
 /*
-* 异步移除目录
+* 在FILE_RUNTIME上以有限的并发数以只读方式打开文件并读取全部数据，填充文件的读缓存，结果与输入的路径顺序一致
+* 预热的文件总是被本库保留，未开启保留时一直保留到被关闭、被移除或调用set_open_file_limit，开启保留时与其它保留的文件一起受上限约束
+* 未打开的文件以只读方式打开，之后以其它方式打开时，只被本库保留的只读句柄会被释放后重新打开
 */
-pub async fn remove_dir<P>(path: P) -> Result<()>
+pub async fn prewarm<P>(paths: Vec<P>) -> Vec<Result<()>>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::remove_dir(FILE_RUNTIME.clone(), path).await
+    stream::iter(paths)
+        .map(|path| async move {
+            let wait = FILE_RUNTIME.wait();
+            wait.spawn(FILE_RUNTIME.clone(), None, async move {
+                let file = SafeFile::open(path, AsyncFileOptions::OnlyRead).await?;
+                file.read_to_end().await?;
+                retain(&file.0).await;
+                Ok(())
+            })?;
+            wait.wait_result().await
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await
 }
 /*
-* 异步重命名文件或目录
+* 异步创建目录
 */
-pub async fn rename<P>(from: P, to: P) -> Result<()>
+pub async fn create_dir<P>(path: P) -> Result<()>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::rename(FILE_RUNTIME.clone(), from, to).await
+    let _op = runtime::enter()?;
+    runtime::guard(pi_async_file::file::create_dir(FILE_RUNTIME.clone(), path)).await
 }
+
 /*
-* 异步复制文件
+* 异步递归创建目录及所有缺失的上级目录，目录已存在则成功，路径中有文件则返回错误
 */
-pub async fn copy_file<P>(from: P, to: P) -> Result<u64>
+pub async fn create_dir_all<P>(path: P) -> Result<()>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::copy_file(FILE_RUNTIME.clone(), from, to).await
+    run_sync(move || fs::create_dir_all(path)).await
 }
 
 /*
-* 异步递归移除目录 TODO
+* 异步移除文件
 */
-pub async fn remove_dir_all<P>(path: P) -> Result<()>
+pub async fn remove_file<P>(path: P) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let _op = runtime::enter()?;
+    runtime::guard(pi_async_file::file::remove_file(FILE_RUNTIME.clone(), path)).await
+}
+
+/*
+* 在FILE_RUNTIME上以有限的并发数批量移除文件，并从OPEN_FILE_MAP中移除对应的条目，结果与输入的路径顺序一致
+* 单个路径移除失败不影响其它路径
+*/
+pub async fn remove_files<P>(paths: Vec<P>) -> Vec<Result<()>>
 where
     P: AsRef<Path> + Send + 'static,
 {
-    pi_async_file::file::remove_dir(FILE_RUNTIME.clone(), path).await
+    stream::iter(paths)
+        .map(|path| async move {
+            let path = path.as_ref().to_path_buf();
+            force_evict(&path).await;
+            let wait = FILE_RUNTIME.wait();
+            let copy = path.clone();
+            wait.spawn(FILE_RUNTIME.clone(), None, async move { remove_file(copy).await })?;
+            wait.wait_result()
+                .await
+                .map_err(|e| Error::new(e.kind(), format!("Remove file failed, file: {:?}, reason: {:?}", path, e)))
+        })
+        .buffered(BATCH_CONCURRENCY)
+        .collect()
+        .await
 }
 
 /*
-* 整理OPEN_FILE_MAP, 将已经关闭的文件的弱引用条目清除 TODO 用定时器定时清理？
+* 异步移除目录
+*/
+pub async fn remove_dir<P>(path: P) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let _op = runtime::enter()?;
+    runtime::guard(pi_async_file::file::remove_dir(FILE_RUNTIME.clone(), path)).await
+}
+/*
+* 异步重命名文件或目录
 */
-pub async fn collect() {}
+pub async fn rename<P>(from: P, to: P) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let _op = runtime::enter()?;
+    runtime::guard(pi_async_file::file::rename(FILE_RUNTIME.clone(), from, to)).await
+}
+/*
+* 异步移动文件，先尝试重命名，源和目标不在同一文件系统时改为复制后移除源文件
+* 复制失败时保留源文件，并移除不完整的目标文件
+*/
+pub async fn move_file<P>(from: P, to: P) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    move_file_with(from.as_ref().to_path_buf(), to.as_ref().to_path_buf(), rename).await
+}
+
+// 移动文件，由指定的函数重命名，重命名因跨文件系统失败时改为复制后移除源文件
+async fn move_file_with<R, F>(from: PathBuf, to: PathBuf, try_rename: R) -> Result<()>
+where
+    R: FnOnce(PathBuf, PathBuf) -> F,
+    F: Future<Output = Result<()>>,
+{
+    match try_rename(from.clone(), to.clone()).await {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => (),
+        r => return r,
+    }
+    if let Err(e) = copy_file(from.clone(), to.clone()).await {
+        let _ = remove_file(to).await;
+        return Err(e);
+    }
+    remove_file(from).await
+}
+
+/*
+* 异步复制文件
+*/
+pub async fn copy_file<P>(from: P, to: P) -> Result<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let _op = runtime::enter()?;
+    runtime::guard(pi_async_file::file::copy_file(FILE_RUNTIME.clone(), from, to)).await
+}
+
+/*
+* 异步复制文件，并将源文件的权限以及访问和修改时间复制到目标文件，返回复制的字节数
+* 平台不支持获取的时间不复制，文件所有者和扩展属性不复制
+*/
+pub async fn copy_file_preserve<P>(from: P, to: P) -> Result<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let from = from.as_ref().to_path_buf();
+    let to = to.as_ref().to_path_buf();
+    let len = copy_file(from.clone(), to.clone()).await?;
+    let (src, dst) = (from.clone(), to.clone());
+    run_sync(move || {
+        let meta = fs::metadata(src)?;
+        let mut times = fs::FileTimes::new();
+        if let Ok(time) = meta.accessed() {
+            times = times.set_accessed(time);
+        }
+        if let Ok(time) = meta.modified() {
+            times = times.set_modified(time);
+        }
+        // 先设置时间再设置权限，避免目标文件变为只读后无法打开
+        fs::OpenOptions::new().write(true).open(&dst)?.set_times(times)?;
+        fs::set_permissions(dst, meta.permissions())
+    })
+    .await
+    .map_err(|e| {
+        Error::new(
+            e.kind(),
+            format!("Copy file metadata failed, from: {:?}, to: {:?}, reason: {:?}", from, to, e),
+        )
+    })?;
+    Ok(len)
+}
+
+/*
+* 按指定块大小异步复制文件，每复制一块调用一次进度回调，参数为已复制字节数和源文件当前大小，返回复制的总字节数
+* 复制期间源文件大小变化时以读到文件尾为准，最后一次回调的两个参数一定相等
+*/
+pub async fn copy_file_with_progress<P, F>(from: P, to: P, chunk_size: usize, mut on_progress: F) -> Result<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+    F: FnMut(u64, u64),
+{
+    let _op = runtime::enter()?;
+    if chunk_size == 0 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Copy file failed, from: {:?}, reason: chunk size is zero", from.as_ref()),
+        ));
+    }
+    let src_path = from.as_ref().to_path_buf();
+    let src = runtime::guard(AsyncFile::open(FILE_RUNTIME.clone(), src_path, AsyncFileOptions::OnlyRead)).await?;
+    // 先创建或清空目标文件，再以只写方式按位置写入
+    let dst_path = to.as_ref().to_path_buf();
+    let create_path = dst_path.clone();
+    run_sync(move || fs::File::create(create_path).map(|_| ())).await?;
+    let dst = runtime::guard(AsyncFile::open(FILE_RUNTIME.clone(), dst_path, AsyncFileOptions::OnlyWrite)).await?;
+    let mut copied = 0u64;
+    let mut reported = None;
+    loop {
+        let data = runtime::guard(src.read(copied, chunk_size)).await?;
+        if data.is_empty() {
+            break;
+        }
+        let mut written = 0;
+        while written < data.len() {
+            let r = runtime::guard(dst.write(copied + written as u64, &data[written..], WriteOptions::None)).await?;
+            if r == 0 {
+                return Err(Error::new(
+                    ErrorKind::WriteZero,
+                    format!("Copy file failed, from: {:?}, to: {:?}, reason: write zero", from.as_ref(), to.as_ref()),
+                ));
+            }
+            written += r;
+        }
+        copied += data.len() as u64;
+        let total = src.get_size().max(copied);
+        runtime::callback(|| on_progress(copied, total));
+        reported = Some(total);
+        if data.len() < chunk_size {
+            // 读到文件尾
+            break;
+        }
+    }
+    if reported != Some(copied) {
+        runtime::callback(|| on_progress(copied, copied));
+    }
+    Ok(copied)
+}
+
+/*
+* 异步原子写文件，先写入同目录下的临时文件并落地，再重命名覆盖目标文件并落地所在目录，失败时清理临时文件
+* 替换期间在OPEN_FILE_MAP中为目标路径占位，并发打开会等待替换完成后共享已有的句柄或打开新文件
+* 已有的句柄在重命名后切换到新文件，并以新内容刷新缓存，重命名失败则仍指向未被修改的原文件
+*/
+pub async fn atomic_write<P>(path: P, data: Arc<[u8]>) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    atomic_write_with(path.as_ref().to_path_buf(), data, rename).await
+}
+
+// 原子写文件，由指定的函数将临时文件重命名为目标文件
+async fn atomic_write_with<R, F>(path: PathBuf, data: Arc<[u8]>, try_rename: R) -> Result<()>
+where
+    R: FnOnce(PathBuf, PathBuf) -> F,
+    F: Future<Output = Result<()>>,
+{
+    let tmp = temp_sibling(&path);
+    // 替换期间占位，避免并发打开取到被替换的句柄，已有的句柄持有写锁，避免与进行中的读写交错
+    let (live, guard) = SafeFile::reserve_replace(&path).await;
+    let live = live.map(SafeFile);
+    let lock = match live {
+        Some(ref file) => Some(file.write_lock().await),
+        None => None,
+    };
+    let r = match write_temp(&tmp, data.clone(), live.as_ref()).await {
+        Ok(reopened) => try_rename(tmp.clone(), path.clone()).await.map(|()| reopened),
+        Err(e) => Err(e),
+    };
+    let r = match r {
+        Ok(reopened) => {
+            if let (Some(file), Some(reopened)) = (live.as_ref(), reopened) {
+                #[cfg(feature = "mmap")]
+                if file.0.mmap.load().is_some() {
+                    // 内存映射的句柄改为映射新文件，映射失败则直接读取新文件
+                    let copy = reopened.clone();
+                    let mmap = run_sync(move || unsafe { memmap2::Mmap::map(&copy.get_inner()?) }).await;
+                    file.0.mmap.store(mmap.ok().map(Arc::new));
+                }
+                file.0.replace_file(reopened);
+                file.0.replace_cache(&data);
+            }
+            sync_parent(path.clone()).await
+        }
+        Err(e) => {
+            let _ = remove_file(tmp).await;
+            Err(e)
+        }
+    };
+    drop(lock);
+    match live {
+        Some(file) => drop(SafeFile::register(path, file.0.clone(), guard).await),
+        None => SafeFile::unreserve(&path, guard).await,
+    }
+    r
+}
+
+// 创建临时文件，写入全部数据并落地，目标路径已打开时，在写入前以相同方式打开临时文件，避免截断方式的打开丢失写入的数据
+async fn write_temp(tmp: &Path, data: Arc<[u8]>, live: Option<&SafeFile>) -> Result<Option<AsyncFile<()>>> {
+    let copy = tmp.to_path_buf();
+    let mut file = run_sync(move || fs::File::create(&copy)).await?;
+    let reopened = match live {
+        Some(live) => {
+            let open = AsyncFile::open(FILE_RUNTIME.clone(), tmp.to_path_buf(), live.get_options());
+            Some(runtime::guard(open).await?)
+        }
+        None => None,
+    };
+    run_sync(move || {
+        file.write_all(&data)?;
+        file.sync_all()
+    })
+    .await?;
+    Ok(reopened)
+}
+
+// 落地指定路径所在的目录，使目录项的修改持久化，非unix平台无法打开目录，直接返回成功
+async fn sync_parent(path: PathBuf) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        run_sync(move || fs::File::open(&dir)?.sync_all()).await.map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Sync dir failed, file: {:?}, reason: {:?}", path, e),
+            )
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+// 获取指定路径同目录下的唯一临时文件路径
+fn temp_sibling(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(format!(
+        ".tmp.{}.{}",
+        process::id(),
+        TEMP_SEQ.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+// 计算相对位置，越界则返回None
+fn offset_pos(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    }
+}
+
+// 从指定位置开始依次写入多个缓冲区，返回写入的总字节数，已写入部分数据后出错则返回已写入的字节数
+fn write_vectored_at(file: &fs::File, pos: u64, bufs: &[Arc<[u8]>]) -> Result<usize> {
+    #[cfg(unix)]
+    use std::os::unix::fs::FileExt;
+    #[cfg(windows)]
+    use std::os::windows::fs::FileExt;
+
+    let mut writed = 0;
+    for buf in bufs {
+        let mut offset = 0;
+        while offset < buf.len() {
+            let at = pos + writed as u64;
+            #[cfg(unix)]
+            let r = file.write_at(&buf[offset..], at);
+            #[cfg(windows)]
+            let r = file.seek_write(&buf[offset..], at);
+            match r {
+                Ok(0) if writed == 0 => return Err(Error::from(ErrorKind::WriteZero)),
+                Ok(0) => return Ok(writed),
+                Ok(len) => {
+                    offset += len;
+                    writed += len;
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) if writed == 0 => return Err(e),
+                Err(_) => return Ok(writed),
+            }
+        }
+    }
+    Ok(writed)
+}
+
+/*
+* 异步递归移除目录，符号链接只移除链接本身，返回遇到的第一个错误
+*/
+pub async fn remove_dir_all<P>(path: P) -> Result<()>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || fs::remove_dir_all(path)).await
+}
+
+/*
+* 异步判断路径是否存在，跟随符号链接，不会打开文件，也不会登记到OPEN_FILE_MAP，无法获取元信息时视为不存在
+*/
+pub async fn exists<P>(path: P) -> bool
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || Ok(path.as_ref().exists())).await.unwrap_or(false)
+}
+
+/*
+* 异步判断路径是否是文件，跟随符号链接，不会打开文件，也不会登记到OPEN_FILE_MAP
+*/
+pub async fn is_file<P>(path: P) -> bool
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || Ok(path.as_ref().is_file())).await.unwrap_or(false)
+}
+
+/*
+* 异步判断路径是否是目录，跟随符号链接，不会打开文件，也不会登记到OPEN_FILE_MAP
+*/
+pub async fn is_dir<P>(path: P) -> bool
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    run_sync(move || Ok(path.as_ref().is_dir())).await.unwrap_or(false)
+}
+
+/*
+* 在FILE_RUNTIME上执行同步的文件系统操作，并异步等待结果
+*/
+async fn run_sync<F, V>(f: F) -> Result<V>
+where
+    F: FnOnce() -> Result<V> + Send + 'static,
+    V: Send + 'static,
+{
+    let _op = runtime::enter()?;
+    run_blocking(f).await
+}
+
+// 在FILE_RUNTIME上执行同步的文件系统操作，并异步等待结果，不检查运行时是否已关闭，用于关闭时写入缓冲数据
+async fn run_blocking<F, V>(f: F) -> Result<V>
+where
+    F: FnOnce() -> Result<V> + Send + 'static,
+    V: Send + 'static,
+{
+    let wait = FILE_RUNTIME.wait();
+    wait.spawn(FILE_RUNTIME.clone(), None, async move { f() })?;
+    wait.wait_result().await
+}
+
+/*
+* 释放OPEN_FILE_MAP中所有仍然打开且缓存未固定的文件的读缓存，不关闭文件，截断写文件会先写入未落地的缓冲数据再释放
+* 单个文件写入失败不影响其它文件，返回第一个错误
+*/
+pub async fn drop_all_caches() -> Result<()> {
+    let mut files = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        files.extend(shard.lock().await.values().filter_map(Slot::upgrade).map(SafeFile));
+    }
+    files.retain(|file| !file.is_cache_pinned());
+    let mut result = Ok(());
+    for file in files {
+        if let Err(e) = file.drop_cache().await {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+/*
+* 获取OPEN_FILE_MAP中所有仍然打开且缓存已固定的文件的路径，按路径排序
+*/
+pub async fn pinned_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        paths.extend(
+            shard
+                .lock()
+                .await
+                .values()
+                .filter_map(Slot::upgrade)
+                .filter(|file| file.pinned.load(Ordering::Acquire))
+                .map(|file| file.path.clone()),
+        );
+    }
+    paths.sort();
+    paths
+}
+
+/*
+* 获取OPEN_FILE_MAP中所有仍然打开的文件的路径快照，跳过已关闭和正在打开的条目，顺序不确定
+*/
+pub async fn list_open_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        paths.extend(
+            shard
+                .lock()
+                .await
+                .iter()
+                .filter(|(_, slot)| slot.strong_count() > 0)
+                .map(|(path, _)| path.clone()),
+        );
+    }
+    paths
+}
+
+/*
+* 获取OPEN_FILE_MAP中所有仍然打开的文件的缓存占用的内存字节数，包括截断写文件未落地的缓冲数据，不包括内存映射
+* 需要遍历整个打开文件表，文件较多时开销较大，不宜频繁调用
+*/
+pub async fn cache_memory_usage() -> usize {
+    let mut size = 0;
+    for shard in OPEN_FILE_MAP.shards() {
+        size += shard
+            .lock()
+            .await
+            .values()
+            .filter_map(Slot::upgrade)
+            .map(|file| file.cache_memory())
+            .sum::<usize>();
+    }
+    size
+}
+
+/*
+* 设置所有文件的缓存最多占用的内存字节数，读取填充缓存后超过上限时，按最久未访问的顺序释放文件的缓存，直到不超过上限
+* 截断写文件未落地的缓冲数据和已固定的缓存不会被释放，因此实际占用可能超过上限，为0则不限制，默认为0
+* 超过上限时需要遍历整个打开文件表
+*/
+pub async fn set_cache_memory_limit(limit: usize) {
+    CACHE_MEMORY_LIMIT.store(limit, Ordering::Release);
+    limit_cache_memory().await;
+}
+
+// 所有文件的缓存占用超过上限时，按最久未访问的顺序释放文件的缓存，直到不超过上限或没有可释放的缓存
+async fn limit_cache_memory() {
+    let limit = CACHE_MEMORY_LIMIT.load(Ordering::Acquire);
+    if limit == 0 {
+        return;
+    }
+    let mut files = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        files.extend(shard.lock().await.values().filter_map(Slot::upgrade));
+    }
+    let mut size: usize = files.iter().map(|file| file.cache_memory()).sum();
+    if size <= limit {
+        return;
+    }
+    files.retain(|file| !file.pinned.load(Ordering::Acquire));
+    files.sort_by_key(|file| file.last_access.load(Ordering::Relaxed));
+    for file in files {
+        if size <= limit {
+            break;
+        }
+        let used = file.cache_memory();
+        file.clear_cache();
+        // 释放期间其它任务可能已重新填充缓存
+        size = size.saturating_sub(used.saturating_sub(file.cache_memory()));
+    }
+}
+
+/*
+* 关闭文件运行时，之后的文件操作都会返回运行时已关闭的错误，等待进行中的文件操作完成后返回，FILE_RUNTIME的工作线程不会停止
+* flush为true时，会在进行中的操作完成后，将所有截断写文件未落地的缓冲数据写入文件，并同步到磁盘
+* 在文件操作的回调中调用时，关闭会等待该操作自身完成而永远无法返回，因此返回Deadlock错误，且不关闭运行时
+*/
+pub fn shutdown(flush: bool) -> impl Future<Output = Result<()>> {
+    let inside = runtime::in_callback();
+    async move {
+        if inside {
+            return Err(Error::new(
+                ErrorKind::Deadlock,
+                "Shutdown file runtime failed, reason: called inside a file operation",
+            ));
+        }
+        shutdown_and_flush(flush).await
+    }
+}
+
+// 关闭文件运行时，等待进行中的文件操作完成，需要时写入所有截断写文件未落地的缓冲数据
+async fn shutdown_and_flush(flush: bool) -> Result<()> {
+    runtime::mark_shutdown();
+    stop_auto_collect();
+    runtime::drain().await;
+    if !flush {
+        return Ok(());
+    }
+    let mut files = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        files.extend(shard.lock().await.values().filter_map(Slot::upgrade).map(SafeFile));
+    }
+    let mut result = Ok(());
+    for file in files {
+        if let LockType::Lock(ref lock) = file.0.lock {
+            let _guard = lock.lock().await;
+            if let Err(e) = file.write_pending(WriteOptions::SyncAll(true)).await {
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+    }
+    result
+}
+
+/*
+* 整理OPEN_FILE_MAP, 将已经关闭的文件的弱引用条目清除，返回清除的条目数
+*/
+pub async fn collect() -> usize {
+    let mut count = 0;
+    for shard in OPEN_FILE_MAP.shards() {
+        let mut tab = shard.lock().await;
+        let len = tab.len();
+        tab.retain(|_, r| r.is_live());
+        count += len - tab.len();
+    }
+    count
+}
+
+/*
+* 获取OPEN_FILE_MAP中仍然打开的文件数量
+*/
+pub async fn open_file_count() -> usize {
+    let mut count = 0;
+    for shard in OPEN_FILE_MAP.shards() {
+        count += shard.lock().await.values().filter(|r| r.strong_count() > 0).count();
+    }
+    count
+}
+
+/*
+* 获取OPEN_FILE_MAP中的条目总数，包括已关闭但未整理的条目
+*/
+pub async fn total_entry_count() -> usize {
+    let mut count = 0;
+    for shard in OPEN_FILE_MAP.shards() {
+        count += shard.lock().await.len();
+    }
+    count
+}
+
+/*
+* 判断指定路径是否仍被打开，路径需与打开时使用的路径一致
+*/
+pub async fn is_open<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    ref_count(path).await > 0
+}
+
+/*
+* 获取指定路径当前被持有的句柄数，未打开或已关闭则返回0
+*/
+pub async fn ref_count<P>(path: P) -> usize
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let tab = OPEN_FILE_MAP.shard(path).lock().await;
+    tab.get(path).map(Slot::strong_count).unwrap_or(0)
+}
+
+/*
+* 从OPEN_FILE_MAP中移除指定路径的条目，返回条目是否存在，之后打开该路径将创建新的句柄
+* 已有的安全文件仍然有效，但不再与之后打开的句柄共享锁和缓存
+*/
+pub async fn force_evict<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let r = OPEN_FILE_MAP.shard(path).lock().await.remove(path).is_some();
+    KEEP_ALIVE.lock().await.remove(path);
+    r
+}
+
+/*
+* 从OPEN_FILE_MAP中移除路径以指定前缀开头的所有条目，返回移除的条目数，用于删除目录前释放目录下的所有句柄
+* 按路径组件匹配，忽略路径中的"."，不解析".."和符号链接，移除前会写入截断写文件未落地的缓冲数据，写入失败的数据保留在已有的句柄中
+*/
+pub async fn evict_prefix<P>(prefix: P) -> usize
+where
+    P: AsRef<Path>,
+{
+    let prefix = normalize_path(prefix.as_ref());
+    let mut files = Vec::new();
+    for shard in OPEN_FILE_MAP.shards() {
+        files.extend(
+            shard
+                .lock()
+                .await
+                .iter()
+                .filter(|(path, _)| normalize_path(path).starts_with(&prefix))
+                .filter_map(|(_, slot)| slot.upgrade())
+                .map(SafeFile),
+        );
+    }
+    for file in files {
+        if let LockType::Lock(ref lock) = file.0.lock {
+            let _guard = lock.lock().await;
+            let _ = file.write_pending(WriteOptions::Flush).await;
+        }
+    }
+
+    let mut count = 0;
+    for shard in OPEN_FILE_MAP.shards() {
+        let mut tab = shard.lock().await;
+        let len = tab.len();
+        // 正在打开的条目由打开的任务登记或取消
+        tab.retain(|path, slot| matches!(slot, Slot::Opening(_)) || !normalize_path(path).starts_with(&prefix));
+        count += len - tab.len();
+    }
+    KEEP_ALIVE
+        .lock()
+        .await
+        .retain(|path, _| !normalize_path(path).starts_with(&prefix));
+    count
+}
+
+// 移除路径中的"."，使"./a/b"与"a/b"一致
+fn normalize_path(path: &Path) -> PathBuf {
+    path.components().filter(|c| *c != Component::CurDir).collect()
+}
+
+/*
+* 设置本库最多保留强引用的文件数，保留的文件在用户释放所有句柄后仍保持打开，再次打开时无需重新打开
+* 超过上限时释放最久未访问且未被用户持有的文件，用户仍持有或缓存已固定的文件不会被释放，为0则不保留，默认为0
+* 未被用户持有的保留文件与之后请求的打开方式不兼容时，先释放再按请求的方式打开
+*/
+pub async fn set_open_file_limit(limit: usize) {
+    KEEP_ALIVE_LIMIT.store(limit, Ordering::Release);
+    let mut tab = KEEP_ALIVE.lock().await;
+    if limit == 0 {
+        tab.clear();
+    } else {
+        evict_idle(&mut tab, limit);
+    }
+}
+
+/*
+* 获取本库当前保留强引用的文件数
+*/
+pub async fn retained_file_count() -> usize {
+    KEEP_ALIVE.lock().await.len()
+}
+
+// 保留指定文件的强引用，超过上限时释放最久未访问且未被用户持有的文件
+async fn keep_alive(file: &Arc<InnerSafeFile>) {
+    if KEEP_ALIVE_LIMIT.load(Ordering::Acquire) == 0 {
+        return;
+    }
+    retain(file).await;
+}
+
+// 保留指定文件的强引用，不检查是否开启保留，开启保留时超过上限则释放最久未访问且未被用户持有的文件
+async fn retain(file: &Arc<InnerSafeFile>) {
+    let mut tab = KEEP_ALIVE.lock().await;
+    tab.insert(file.path.clone(), file.clone());
+    let limit = KEEP_ALIVE_LIMIT.load(Ordering::Acquire);
+    if limit > 0 {
+        evict_idle(&mut tab, limit);
+    }
+}
+
+// 释放只被本库保留的指定文件，使其路径可以按其它方式重新打开，返回是否已释放，缓存已固定或有未落地数据的文件不释放
+async fn release_idle(file: Arc<InnerSafeFile>) -> bool {
+    let mut tab = KEEP_ALIVE.lock().await;
+    let idle = match tab.get(&file.path) {
+        Some(retained) => {
+            Arc::ptr_eq(retained, &file)
+                && Arc::strong_count(&file) == 2
+                && !file.pinned.load(Ordering::Acquire)
+                && file.buff.load().pending == 0
+        }
+        None => false,
+    };
+    let retained = if idle { tab.remove(&file.path) } else { None };
+    drop(tab);
+    drop(retained);
+    idle
+}
+
+// 释放最久未访问且只被本库持有的文件，直到不超过上限或没有可释放的文件，缓存已固定的文件不释放
+fn evict_idle(tab: &mut XHashMap<PathBuf, Arc<InnerSafeFile>>, limit: usize) {
+    while tab.len() > limit {
+        if evict_oldest(tab).is_none() {
+            break;
+        }
+    }
+}
+
+// 释放最久未访问且只被本库持有的一个文件，返回释放的文件，缓存已固定的文件不释放
+fn evict_oldest(tab: &mut XHashMap<PathBuf, Arc<InnerSafeFile>>) -> Option<Arc<InnerSafeFile>> {
+    let path = tab
+        .iter()
+        .filter(|(_, file)| Arc::strong_count(file) == 1 && !file.pinned.load(Ordering::Acquire))
+        .min_by_key(|(_, file)| file.last_access.load(Ordering::Relaxed))
+        .map(|(path, _)| path.clone())?;
+    tab.remove(&path)
+}
+
+// 获取打开一个安全文件的许可，已打开的文件数达到上限时先释放最久未访问且只被本库保留的文件，没有可释放的文件则等待其它文件关闭
+// 用户释放句柄后文件可能只被本库保留，因此等待期间定时重新检查，未指定上限则返回None
+async fn open_permit() -> Option<SemaphoreGuardArc> {
+    let permits = runtime::open_permits()?;
+    loop {
+        if let Some(permit) = permits.try_acquire_arc() {
+            return Some(permit);
+        }
+        let evicted = evict_oldest(&mut *KEEP_ALIVE.lock().await);
+        if evicted.is_some() {
+            // 释放文件时归还许可
+            drop(evicted);
+            continue;
+        }
+        let acquire = Box::pin(permits.acquire_arc());
+        if let Either::Left((permit, _)) = future::select(acquire, FILE_RUNTIME.timeout(PERMIT_RETRY_INTERVAL)).await {
+            return Some(permit);
+        }
+    }
+}
+
+/*
+* 在FILE_RUNTIME上启动定时整理OPEN_FILE_MAP的任务，间隔单位ms，已启动则忽略，返回本次是否启动
+*/
+pub fn start_auto_collect(interval_ms: u64) -> bool {
+    if AUTO_COLLECT_RUNNING
+        .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+        .is_err()
+    {
+        return false;
+    }
+    AUTO_COLLECT_STOP.store(false, Ordering::Release);
+    let rt = FILE_RUNTIME.clone();
+    let r = FILE_RUNTIME.spawn(async move {
+        while !AUTO_COLLECT_STOP.load(Ordering::Acquire) {
+            rt.timeout(interval_ms as usize).await;
+            if AUTO_COLLECT_STOP.load(Ordering::Acquire) {
+                break;
+            }
+            collect().await;
+        }
+        AUTO_COLLECT_RUNNING.store(false, Ordering::Release);
+    });
+    if r.is_err() {
+        // 派发失败，则允许再次启动
+        AUTO_COLLECT_RUNNING.store(false, Ordering::Release);
+        return false;
+    }
+    true
+}
+
+/*
+* 停止定时整理任务，任务会在当前间隔结束后退出
+*/
+pub fn stop_auto_collect() {
+    AUTO_COLLECT_STOP.store(true, Ordering::Release);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+    use pi_async_rt::rt::{startup_global_time_loop, AsyncRuntimeExt};
+    use std::env;
+    use std::fs;
+    use std::future::Future;
+    use std::io::Error;
+    use std::mem;
+    use std::process;
+    use std::sync::Once;
+    use std::thread;
+    use std::time::{Duration, SystemTime};
+
+    // 启动全局时间循环，运行时的定时器依赖它计时
+    static TIME_LOOP: Once = Once::new();
+
+    // 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+    pub(crate) fn block_on<F, T>(future: F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+        T: Send + 'static,
+    {
+        TIME_LOOP.call_once(|| mem::forget(startup_global_time_loop(10)));
+        FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+    }
+
+    // 获取系统临时目录下本进程唯一的测试路径，每个测试使用不同的名称
+    pub(crate) fn test_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("pi_rt_file.test.{}.{}", process::id(), name))
+    }
+
+    // 全局表中是否有指定路径
+    async fn in_table(path: &Path) -> bool {
+        OPEN_FILE_MAP.shard(path).lock().await.contains_key(path)
+    }
+
+    #[test]
+    fn truncate_write_reads_buffered_slices() {
+        let path = test_path("truncate_slices");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"hello world"[..]), WriteOptions::None).await?;
+            Ok::<_, Error>((
+                file.read(0, 100).await?,
+                file.read(6, 5).await?,
+                file.read(6, 100).await?,
+                file.read(20, 5).await?,
+            ))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"hello world");
+        assert_eq!(r.1, b"world");
+        assert_eq!(r.2, b"world");
+        assert!(r.3.is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn rw_read_sees_consistent_snapshot() {
+        let path = test_path("rw_snapshot");
+        fs::write(&path, vec![b'a'; 64 * 1024]).unwrap();
+        let copy = path.clone();
+        let writer = thread::spawn(move || {
+            block_on(async move {
+                let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+                for i in 0..64 {
+                    let byte = if i % 2 == 0 { b'b' } else { b'a' };
+                    file.write(0, Arc::from(vec![byte; 64 * 1024]), WriteOptions::None).await?;
+                }
+                Ok::<_, Error>(())
+            })
+        });
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let mut snapshots = Vec::new();
+            for _ in 0..64 {
+                let data = file.read(0, 64 * 1024).await?;
+                snapshots.push(data.len() == 64 * 1024 && data.iter().all(|b| *b == data[0]));
+            }
+            Ok::<_, Error>(snapshots)
+        })
+        .unwrap();
+        writer.join().unwrap().unwrap();
+        assert!(r.iter().all(|consistent| *consistent));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_lands_last_write_under_contention() {
+        let path = test_path("truncate_stress");
+        let writers = (0..8u8)
+            .map(|t| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    block_on(async move {
+                        let file = SafeFile::open(path, AsyncFileOptions::TruncateWrite).await?;
+                        for i in 0..16u8 {
+                            let byte = t * 16 + i;
+                            file.write(0, Arc::from(vec![byte; 64 + byte as usize]), WriteOptions::None).await?;
+                        }
+                        Ok::<_, Error>(file)
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        let files = writers.into_iter().map(|w| w.join().unwrap().unwrap()).collect::<Vec<_>>();
+        let file = files[0].clone();
+        let buffered = block_on(async move { file.read(0, 1024).await }).unwrap();
+        let disk = fs::read(&path).unwrap();
+        assert_eq!(buffered, disk);
+        assert!(disk.len() >= 64 && disk.iter().all(|b| *b as usize + 64 == disk.len()));
+        drop(files);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn collect_prunes_dropped_entries() {
+        let live = test_path("collect_live");
+        let dead = (0..16).map(|i| test_path(&format!("collect_dead{}", i))).collect::<Vec<_>>();
+        let (l, d) = (live.clone(), dead.clone());
+        let r = block_on(async move {
+            let file = SafeFile::open(l.clone(), AsyncFileOptions::ReadWrite).await?;
+            let mut opened = Vec::new();
+            for path in d.iter() {
+                opened.push(SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await?);
+            }
+            let mut before = true;
+            for path in d.iter() {
+                before &= in_table(path).await;
+            }
+            drop(opened);
+            collect().await;
+            let mut after = false;
+            for path in d.iter() {
+                after |= in_table(path).await;
+            }
+            let tab = OPEN_FILE_MAP.shard(&l).lock().await;
+            let kept = tab.get(&l).and_then(Slot::upgrade).filter(|r| Arc::ptr_eq(r, &file.0)).is_some();
+            Ok::<_, Error>((before, after, kept))
+        })
+        .unwrap();
+        assert_eq!(r, (true, false, true));
+        let _ = fs::remove_file(live);
+        for path in dead {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn auto_collect_prunes_without_manual_collect() {
+        let paths = (0..8).map(|i| test_path(&format!("auto_collect{}", i))).collect::<Vec<_>>();
+        let copy = paths.clone();
+        block_on(async move {
+            for path in copy {
+                SafeFile::open(path, AsyncFileOptions::ReadWrite).await?;
+            }
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        let started = start_auto_collect(10);
+        let again = start_auto_collect(10);
+        thread::sleep(Duration::from_millis(200));
+        let copy = paths.clone();
+        let pruned = block_on(async move {
+            let mut pruned = true;
+            for path in copy.iter() {
+                pruned &= !in_table(path).await;
+            }
+            pruned
+        });
+        assert!(started);
+        assert!(!again);
+        assert!(pruned);
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn remove_dir_all_removes_nested_tree() {
+        let root = test_path("remove_tree");
+        let outside = test_path("remove_tree_target");
+        fs::create_dir_all(root.join("a/b/c")).unwrap();
+        fs::write(root.join("top"), b"1").unwrap();
+        fs::write(root.join("a/mid"), b"2").unwrap();
+        fs::write(root.join("a/b/c/leaf"), b"3").unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("keep"), b"4").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&outside, root.join("a/link")).unwrap();
+        let copy = root.clone();
+        let r = block_on(async move { remove_dir_all(copy).await });
+        assert!(r.is_ok());
+        assert!(!root.exists());
+        // 符号链接只移除链接本身，不影响链接的目标
+        assert!(outside.join("keep").exists());
+        let _ = fs::remove_dir_all(outside);
+    }
+
+    #[test]
+    fn read_to_end_reads_whole_file() {
+        let empty = test_path("read_to_end_empty");
+        let large = test_path("read_to_end_large");
+        let growing = test_path("read_to_end_growing");
+        let buffered = test_path("read_to_end_buffered");
+        let content = (0..200 * 1024).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&empty, b"").unwrap();
+        fs::write(&large, &content).unwrap();
+        fs::write(&growing, b"head").unwrap();
+        let (e, l, g, b) = (empty.clone(), large.clone(), growing.clone(), buffered.clone());
+        let r = block_on(async move {
+            let empty = SafeFile::open(e, AsyncFileOptions::OnlyRead).await?.read_to_end().await?;
+            let large = SafeFile::open(l, AsyncFileOptions::OnlyRead).await?.read_to_end().await?;
+            // 打开后文件被其它写者增长，读取到的是完整的新内容
+            let file = SafeFile::open(g.clone(), AsyncFileOptions::OnlyRead).await?;
+            fs::write(&g, vec![b'x'; READ_CHUNK_SIZE * 2 + 3])?;
+            let growing = file.read_to_end().await?;
+            let file = SafeFile::open(b, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"buffered"[..]), WriteOptions::None).await?;
+            Ok::<_, Error>((empty, large, growing, file.read_to_end().await?))
+        })
+        .unwrap();
+        assert!(r.0.is_empty());
+        assert_eq!(r.1, content);
+        assert_eq!(r.2, vec![b'x'; READ_CHUNK_SIZE * 2 + 3]);
+        assert_eq!(r.3, b"buffered");
+        for path in [empty, large, growing, buffered].iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn len_matches_written_bytes() {
+        let rw = test_path("len_rw");
+        let truncate = test_path("len_truncate");
+        let (a, b) = (rw.clone(), truncate.clone());
+        let r = block_on(async move {
+            let file = SafeFile::open(a, AsyncFileOptions::ReadWrite).await?;
+            let written = file.write(0, Arc::from(vec![1; 300]), WriteOptions::Flush).await?;
+            let rw = (written as u64, file.len().await?, file.metadata().await?.len());
+            let file = SafeFile::open(b, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(vec![2; 500]), WriteOptions::Flush).await?;
+            // 缓冲区尚未落地时返回缓冲数据的长度
+            file.0.set_buff(Arc::from(vec![3; 40]), 1);
+            Ok::<_, Error>((rw, file.len().await?, file.metadata().await?.len()))
+        })
+        .unwrap();
+        assert_eq!(r.0, (300, 300, 300));
+        assert_eq!(r.1, 40);
+        assert_eq!(r.2, 500);
+        let _ = fs::remove_file(rw);
+        let _ = fs::remove_file(truncate);
+    }
+
+    #[test]
+    fn concurrent_appends_never_interleave() {
+        let path = test_path("append");
+        let appenders = (0..4u8)
+            .map(|t| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    block_on(async move {
+                        let file = SafeFile::open(path, AsyncFileOptions::ReadAppend).await?;
+                        for i in 0..32u8 {
+                            // 每条记录由同一字节填充，交错写入会破坏记录
+                            file.append(Arc::from(vec![t * 32 + i; 100])).await?;
+                        }
+                        Ok::<_, Error>(())
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+        for appender in appenders {
+            appender.join().unwrap().unwrap();
+        }
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), 128 * 100);
+        let mut records = data.chunks(100).map(|r| r[0]).collect::<Vec<_>>();
+        assert!(data.chunks(100).all(|r| r.iter().all(|b| *b == r[0])));
+        records.sort_unstable();
+        assert_eq!(records, (0..128u8).collect::<Vec<_>>());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_replaces_target() {
+        let path = test_path("atomic");
+        fs::write(&path, b"old").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let before = SafeFile::open(copy.clone(), AsyncFileOptions::OnlyRead).await?.read_to_end().await?;
+            atomic_write(copy.clone(), Arc::from(&b"newer"[..])).await?;
+            let after = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?.read_to_end().await?;
+            Ok::<_, Error>((before, after))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"old");
+        assert_eq!(r.1, b"newer");
+        assert_eq!(temp_siblings(&path), 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_failed_rename_keeps_original() {
+        // 目标是非空目录，临时文件写入成功后重命名失败
+        let path = test_path("atomic_fail");
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("inner"), b"original").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move { atomic_write(copy, Arc::from(&b"new"[..])).await });
+        assert!(r.is_err());
+        assert_eq!(fs::read(path.join("inner")).unwrap(), b"original");
+        assert_eq!(temp_siblings(&path), 0);
+        let _ = fs::remove_dir_all(path);
+    }
+
+    // 统计指定路径同目录下遗留的临时文件数量
+    fn temp_siblings(path: &Path) -> usize {
+        let prefix = format!("{}.tmp.", path.file_name().unwrap().to_string_lossy());
+        fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+            .count()
+    }
+
+    #[test]
+    fn read_exact_exact_short_and_past_end() {
+        let path = test_path("read_exact");
+        fs::write(&path, (0..100u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        // 错误信息中是文件路径，而不是底层句柄
+        let named = format!("file: {:?},", path);
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            Ok::<_, Error>((
+                file.read_exact(10, 20).await?,
+                file.read_exact(90, 20).await.map_err(|e| (e.kind(), e.to_string().contains(&named))),
+                file.read_exact(200, 1).await.map_err(|e| e.kind()),
+            ))
+        })
+        .unwrap();
+        assert_eq!(r.0, (10..30u8).collect::<Vec<_>>());
+        assert_eq!(r.1, Err((ErrorKind::UnexpectedEof, true)));
+        assert_eq!(r.2, Err(ErrorKind::UnexpectedEof));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_into_counts_bytes_at_end() {
+        let path = test_path("read_into");
+        fs::write(&path, (0..200u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let mut buf = vec![0; 100];
+            let n = file.read_into(150, &mut buf).await?;
+            buf.truncate(n);
+            Ok::<_, Error>((buf, file.read_into(300, &mut [0; 8]).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, (150..200u8).collect::<Vec<_>>());
+        assert_eq!(r.1, 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_cache_respects_size_limit() {
+        let paths = ["cache_limited", "cache_disabled"].iter().map(|n| test_path(n)).collect::<Vec<_>>();
+        let copy = paths.clone();
+        let r = block_on(async move {
+            let limited = CacheOptions { enable: true, max_size: 10, ..Default::default() };
+            let disabled = CacheOptions { enable: false, ..Default::default() };
+            let limited = SafeFile::open_with(copy[0].clone(), AsyncFileOptions::TruncateWrite, limited).await?;
+            let disabled = SafeFile::open_with(copy[1].clone(), AsyncFileOptions::ReadWrite, disabled).await?;
+            // 同一路径再次打开共享首次打开的选项
+            let shared = SafeFile::open(copy[0].clone(), AsyncFileOptions::TruncateWrite).await?;
+            Ok::<_, Error>((
+                (limited.0.cacheable(8), limited.0.cacheable(10), limited.0.cacheable(11)),
+                disabled.0.cacheable(8),
+                shared.0.cache.max_size,
+            ))
+        })
+        .unwrap();
+        assert_eq!(r, ((true, true, false), false, 10));
+        // 默认开启缓存，但限制单个文件的缓存大小
+        let default = CacheOptions::default();
+        assert_eq!((default.enable, default.max_size), (true, DEFAULT_CACHE_SIZE));
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn writes_invalidate_cached_reads() {
+        let path = test_path("cache_invalidate");
+        fs::write(&path, vec![0u8; 16]).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let mut reads = Vec::new();
+            // 先读全数据填充缓存
+            reads.push(file.read(0, 64).await?);
+            // 与缓存部分重叠并超出缓存末尾的写入
+            file.write(12, Arc::from(vec![1u8; 8]), WriteOptions::None).await?;
+            reads.push(file.read(0, 64).await?);
+            // 与缓存不相连的写入，缓存被清除
+            file.write(30, Arc::from(vec![2u8; 2]), WriteOptions::None).await?;
+            reads.push(file.read(0, 64).await?);
+            for i in 0..8u8 {
+                file.write(i as u64, Arc::from(vec![i + 3; 1]), WriteOptions::None).await?;
+                reads.push(file.read(i as u64, 1).await?);
+            }
+            Ok::<_, Error>(reads)
+        })
+        .unwrap();
+        let mut expect = vec![0u8; 12];
+        expect.extend_from_slice(&[1; 8]);
+        assert_eq!(r[0], vec![0; 16]);
+        assert_eq!(r[1], expect);
+        expect.resize(30, 0);
+        expect.extend_from_slice(&[2; 2]);
+        assert_eq!(r[2], expect);
+        for i in 0..8u8 {
+            assert_eq!(r[3 + i as usize], vec![i + 3]);
+        }
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn synced_data_survives_reopen() {
+        let rw = test_path("sync_rw");
+        let truncate = test_path("sync_truncate");
+        let (a, b) = (rw.clone(), truncate.clone());
+        let r = block_on(async move {
+            let file = SafeFile::open(a.clone(), AsyncFileOptions::ReadWrite).await?;
+            file.write(0, Arc::from(&b"durable"[..]), WriteOptions::None).await?;
+            file.sync_all().await?;
+            drop(file);
+            let rw = SafeFile::open(a, AsyncFileOptions::OnlyRead).await?.read_to_end().await?;
+            let file = SafeFile::open(b.clone(), AsyncFileOptions::TruncateWrite).await?;
+            // 模拟尚未落地的缓冲数据，同步时先写入文件
+            file.0.set_buff(Arc::from(&b"pending"[..]), 1);
+            file.sync_data().await?;
+            let pending = file.0.buff.load().pending;
+            drop(file);
+            Ok::<_, Error>((rw, pending, fs::read(&b)?))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"durable");
+        assert_eq!(r.1, 0);
+        assert_eq!(r.2, b"pending");
+        let _ = fs::remove_file(rw);
+        let _ = fs::remove_file(truncate);
+    }
+
+    #[test]
+    fn try_open_reports_incompatible_options() {
+        let path = test_path("try_open");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let reader = SafeFile::try_open(copy.clone(), AsyncFileOptions::OnlyRead).await;
+            let writer = SafeFile::try_open(copy.clone(), AsyncFileOptions::TruncateWrite).await;
+            let missing = SafeFile::try_open(copy.join("missing"), AsyncFileOptions::OnlyRead).await;
+            Ok::<_, Error>((
+                reader.map(|r| Arc::ptr_eq(&r.0, &file.0)).ok(),
+                matches!(writer, Err(FileError::Incompatible { ref path }) if *path == copy),
+                missing.err().map(|e| (e.path() == Some(&copy.join("missing")), matches!(e, FileError::Io { .. }))),
+            ))
+        })
+        .unwrap();
+        assert_eq!(r, (Some(true), true, Some((true, true))));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn chunks_concatenate_to_whole_file() {
+        let full = test_path("chunks_full");
+        let empty = test_path("chunks_empty");
+        let content = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&full, &content).unwrap();
+        fs::write(&empty, b"").unwrap();
+        let (a, b) = (full.clone(), empty.clone());
+        let r = block_on(async move {
+            let file = SafeFile::open(a, AsyncFileOptions::OnlyRead).await?;
+            let chunks = file.chunks(300).try_collect::<Vec<_>>().await?;
+            let file = SafeFile::open(b, AsyncFileOptions::OnlyRead).await?;
+            let empty = file.chunks(300).try_collect::<Vec<_>>().await?;
+            Ok::<_, Error>((chunks, empty))
+        })
+        .unwrap();
+        assert_eq!(r.0.iter().map(Vec::len).collect::<Vec<_>>(), vec![300, 300, 300, 100]);
+        assert_eq!(r.0.concat(), content);
+        assert!(r.1.is_empty());
+        let _ = fs::remove_file(full);
+        let _ = fs::remove_file(empty);
+    }
+
+    #[test]
+    fn create_dir_all_is_idempotent() {
+        let root = test_path("create_dir_all");
+        let deep = root.join("a/b/c/d");
+        let file = root.join("file");
+        let (d, f) = (deep.clone(), file.clone());
+        let r = block_on(async move {
+            create_dir_all(d.clone()).await?;
+            create_dir_all(d.clone()).await?;
+            fs::write(&f, b"x")?;
+            // 路径中有文件则返回错误
+            Ok::<_, Error>(create_dir_all(f.join("sub")).await.is_err())
+        })
+        .unwrap();
+        assert!(r);
+        assert!(deep.is_dir());
+        assert!(file.is_file());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mmap_reads_match_syscall_reads() {
+        let mapped = test_path("mmap");
+        let plain = test_path("mmap_plain");
+        let content = (0..100_000u32).map(|i| (i * 7) as u8).collect::<Vec<_>>();
+        fs::write(&mapped, &content).unwrap();
+        fs::write(&plain, &content).unwrap();
+        let (a, b) = (mapped.clone(), plain.clone());
+        let r = block_on(async move {
+            let mapped = SafeFile::open_mmap(a).await?;
+            let plain = SafeFile::open(b, AsyncFileOptions::OnlyRead).await?;
+            let mut pairs = Vec::new();
+            for &(pos, len) in [(0, 10), (4095, 2), (99_990, 100), (200_000, 5), (0, 100_000)].iter() {
+                pairs.push((mapped.read(pos, len).await?, plain.read(pos, len).await?));
+            }
+            Ok::<_, Error>((pairs, mapped.0.mmap.load().is_some()))
+        })
+        .unwrap();
+        assert!(r.1);
+        for (mapped, plain) in r.0 {
+            assert_eq!(mapped, plain);
+        }
+        let _ = fs::remove_file(mapped);
+        let _ = fs::remove_file(plain);
+    }
+
+    #[test]
+    fn copy_progress_increases_to_file_size() {
+        let from = test_path("progress_from");
+        let to = test_path("progress_to");
+        let empty = test_path("progress_empty");
+        let content = (0..10_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&from, &content).unwrap();
+        fs::write(&empty, b"").unwrap();
+        let (a, b, e) = (from.clone(), to.clone(), empty.clone());
+        let r = block_on(async move {
+            let mut calls = Vec::new();
+            let copied = copy_file_with_progress(a.clone(), b.clone(), 3000, |done, total| calls.push((done, total))).await?;
+            let data = fs::read(&b)?;
+            let mut empty_calls = Vec::new();
+            copy_file_with_progress(e, b.clone(), 3000, |done, total| empty_calls.push((done, total))).await?;
+            let zero = copy_file_with_progress(a, b, 0, |_, _| ()).await.map_err(|e| e.kind());
+            Ok::<_, Error>((copied, calls, empty_calls, zero, data))
+        })
+        .unwrap();
+        assert_eq!(r.0, 10_000);
+        assert_eq!(r.1.iter().map(|c| c.0).collect::<Vec<_>>(), vec![3000, 6000, 9000, 10_000]);
+        assert!(r.1.iter().all(|c| c.1 == 10_000));
+        assert_eq!(r.2, vec![(0, 0)]);
+        assert_eq!(r.3, Err(ErrorKind::InvalidInput));
+        assert_eq!(r.4, content);
+        let _ = fs::remove_file(from);
+        let _ = fs::remove_file(to);
+        let _ = fs::remove_file(empty);
+    }
+
+    #[test]
+    fn set_len_shrinks_and_grows() {
+        let rw = test_path("set_len_rw");
+        let truncate = test_path("set_len_truncate");
+        fs::write(&rw, b"0123456789").unwrap();
+        let (a, b) = (rw.clone(), truncate.clone());
+        let r = block_on(async move {
+            let file = SafeFile::open(a, AsyncFileOptions::ReadWrite).await?;
+            // 先读全数据填充缓存，改变长度后读取不会返回旧数据
+            file.read(0, 100).await?;
+            file.set_len(4).await?;
+            let shrunk = (file.read(0, 100).await?, file.len().await?);
+            file.set_len(6).await?;
+            let grown = (file.read(0, 100).await?, file.len().await?);
+            let file = SafeFile::open(b.clone(), AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"buffered"[..]), WriteOptions::None).await?;
+            file.set_len(3).await?;
+            Ok::<_, Error>((shrunk, grown, file.read(0, 100).await?, fs::read(&b)?))
+        })
+        .unwrap();
+        assert_eq!(r.0, (b"0123".to_vec(), 4));
+        assert_eq!(r.1, (b"0123\0\0".to_vec(), 6));
+        assert_eq!(r.2, b"buf");
+        assert_eq!(r.3, b"buf");
+        let _ = fs::remove_file(rw);
+        let _ = fs::remove_file(truncate);
+    }
+
+    #[test]
+    fn exists_does_not_open() {
+        let file = test_path("exists_file");
+        let dir = test_path("exists_dir");
+        let missing = test_path("exists_missing");
+        fs::write(&file, b"x").unwrap();
+        fs::create_dir_all(&dir).unwrap();
+        let (f, d, m) = (file.clone(), dir.clone(), missing.clone());
+        let r = block_on(async move {
+            let file = (exists(f.clone()).await, is_file(f.clone()).await, is_dir(f.clone()).await);
+            let dir = (exists(d.clone()).await, is_file(d.clone()).await, is_dir(d.clone()).await);
+            let missing = (exists(m.clone()).await, is_file(m.clone()).await, is_dir(m.clone()).await);
+            let opened = in_table(&f).await || in_table(&d).await || in_table(&m).await;
+            Ok::<_, Error>((file, dir, missing, opened))
+        })
+        .unwrap();
+        assert_eq!(r, ((true, true, false), (true, false, true), (false, false, false), false));
+        let _ = fs::remove_file(file);
+        let _ = fs::remove_dir(dir);
+    }
+
+    #[test]
+    fn ref_count_tracks_clones() {
+        let path = test_path("ref_count");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let unknown = (is_open(copy.clone()).await, ref_count(copy.clone()).await);
+            let a = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let b = a.clone();
+            let c = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let held = (is_open(copy.clone()).await, ref_count(copy.clone()).await);
+            drop(b);
+            let fewer = ref_count(copy.clone()).await;
+            drop(a);
+            drop(c);
+            let dead = (is_open(copy.clone()).await, ref_count(copy).await);
+            Ok::<_, Error>((unknown, held, fewer, dead))
+        })
+        .unwrap();
+        assert_eq!(r, ((false, 0), (true, 3), 2, (false, 0)));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn force_evict_unshares_handles() {
+        let path = test_path("force_evict");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let old = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let evicted = force_evict(copy.clone()).await;
+            let again = force_evict(copy.clone()).await;
+            let new = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            // 旧句柄仍然有效
+            old.write(0, Arc::from(&b"old"[..]), WriteOptions::Flush).await?;
+            Ok::<_, Error>((evicted, again, Arc::ptr_eq(&old.0, &new.0), new.read(0, 10).await?))
+        })
+        .unwrap();
+        assert_eq!(r, (true, false, false, b"old".to_vec()));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn write_vectored_concatenates_buffers() {
+        let rw = test_path("vectored_rw");
+        let truncate = test_path("vectored_truncate");
+        fs::write(&rw, b"__________").unwrap();
+        let (a, b) = (rw.clone(), truncate.clone());
+        let r = block_on(async move {
+            let bufs: Vec<Arc<[u8]>> = vec![Arc::from(&b"ab"[..]), Arc::from(&b""[..]), Arc::from(&b"cde"[..])];
+            let file = SafeFile::open(a, AsyncFileOptions::ReadWrite).await?;
+            let rw = (file.write_vectored(2, &bufs, WriteOptions::Flush).await?, file.read(0, 100).await?);
+            let file = SafeFile::open(b.clone(), AsyncFileOptions::TruncateWrite).await?;
+            let truncate = file.write_vectored(0, &bufs, WriteOptions::Flush).await?;
+            let none = file.write_vectored(0, &[], WriteOptions::Flush).await?;
+            Ok::<_, Error>((rw, (truncate, fs::read(&b)?), none))
+        })
+        .unwrap();
+        assert_eq!(r.0, (5, b"__abcde___".to_vec()));
+        assert_eq!(r.1, (5, b"abcde".to_vec()));
+        assert_eq!(r.2, 0);
+        let _ = fs::remove_file(rw);
+        let _ = fs::remove_file(truncate);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn write_vectored_reports_write_error() {
+        // 写满的设备在写入任何数据前就失败
+        let file = fs::OpenOptions::new().write(true).open("/dev/full").unwrap();
+        let r = write_vectored_at(&file, 0, &[Arc::from(&b"abc"[..])]);
+        assert_eq!(r.map_err(|e| e.raw_os_error()), Err(Some(libc::ENOSPC)));
+    }
+
+    #[test]
+    fn metadata_cache_invalidated_by_writes() {
+        let path = test_path("metadata_cache");
+        fs::write(&path, b"1234").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let cache = CacheOptions { metadata: true, ..Default::default() };
+            let file = SafeFile::open_with(copy.clone(), AsyncFileOptions::ReadWrite, cache).await?;
+            let first = file.len().await?;
+            // 其它进程的修改不会使缓存失效，直到主动刷新
+            fs::write(&copy, b"123456")?;
+            let cached = file.len().await?;
+            let refreshed = file.refresh_metadata().await?.len();
+            file.write(6, Arc::from(&b"78"[..]), WriteOptions::Flush).await?;
+            let written = file.len().await?;
+            file.set_len(3).await?;
+            Ok::<_, Error>((first, cached, refreshed, written, file.len().await?))
+        })
+        .unwrap();
+        assert_eq!(r, (4, 4, 6, 8, 3));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_timeout_gives_up_on_slow_read() {
+        let path = test_path("read_timeout");
+        fs::write(&path, b"data").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let slow = match file.0.lock {
+                // 持有写锁模拟慢速的后端，读取会一直等待
+                LockType::Rw(ref lock) => {
+                    let _guard = lock.write().await;
+                    file.read_timeout(0, 4, 30).await.map_err(|e| e.kind())
+                }
+                LockType::Lock(_) | LockType::Immutable => Ok(Vec::new()),
+            };
+            // 超时的读被丢弃，之后的读不受影响
+            Ok::<_, Error>((slow, file.read_timeout(0, 4, 1000).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, Err(ErrorKind::TimedOut));
+        assert_eq!(r.1, b"data");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn prefetch_fills_read_cache() {
+        let path = test_path("prefetch");
+        fs::write(&path, b"prefetched").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            file.prefetch(0, 4).await;
+            // 预读在运行时上异步完成
+            for _ in 0..100 {
+                if !file.0.buffered().is_empty() {
+                    break;
+                }
+                FILE_RUNTIME.timeout(10).await;
+            }
+            // 绕过SafeFile改写磁盘内容，命中缓存的读仍返回预读的数据
+            fs::write(&copy, b"overwrite!").unwrap();
+            file.read(0, 4).await
+        })
+        .unwrap();
+        assert_eq!(r, b"pref");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn write_batch_is_all_or_nothing() {
+        let path = test_path("write_batch");
+        fs::write(&path, vec![0u8; 16]).unwrap();
+        let copy = path.clone();
+        let writer = thread::spawn(move || {
+            block_on(async move {
+                let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+                for round in 1..=32u8 {
+                    let writes = vec![
+                        (0, Arc::from(vec![round; 4])),
+                        (8, Arc::from(vec![round; 4])),
+                        // 与第一个范围重叠，以后写入的为准
+                        (2, Arc::from(vec![round + 100; 4])),
+                    ];
+                    file.write_batch(writes, WriteOptions::None).await?;
+                }
+                Ok::<_, Error>(())
+            })
+        });
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let mut snapshots = Vec::new();
+            for _ in 0..64 {
+                snapshots.push(file.read(0, 16).await?);
+            }
+            Ok::<_, Error>(snapshots)
+        })
+        .unwrap();
+        writer.join().unwrap().unwrap();
+        let batch = |round: u8| {
+            let mut buf = vec![0u8; 16];
+            buf[0..2].copy_from_slice(&[round; 2]);
+            buf[2..6].copy_from_slice(&[round + 100; 4]);
+            buf[8..12].copy_from_slice(&[round; 4]);
+            buf
+        };
+        for snapshot in r {
+            assert!(snapshot == vec![0; 16] || (1..=32).any(|round| snapshot == batch(round)), "{:?}", snapshot);
+        }
+        assert_eq!(fs::read(&path).unwrap(), batch(32));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn open_many_keeps_order_and_shares_duplicates() {
+        let paths = (0..20).map(|i| test_path(&format!("open_many_{}", i))).collect::<Vec<_>>();
+        let mut batch = paths.clone();
+        batch.push(paths[3].clone());
+        batch.push(paths[3].clone());
+        batch.push(test_path("open_many_missing").join("child"));
+        let copy = batch.clone();
+        let files = block_on(async move { Ok::<_, Error>(open_many(copy, AsyncFileOptions::ReadWrite).await) }).unwrap();
+        assert_eq!(files.len(), batch.len());
+        for (file, path) in files.iter().zip(batch.iter()).take(22) {
+            assert_eq!(file.as_ref().unwrap().path(), path.as_path());
+        }
+        let (dup, first) = (files[20].as_ref().unwrap(), files[3].as_ref().unwrap());
+        assert!(Arc::ptr_eq(&dup.0, &first.0));
+        assert!(Arc::ptr_eq(&files[21].as_ref().unwrap().0, &first.0));
+        assert!(files[22].is_err());
+        drop(files);
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn io_stats_aggregate_all_handles() {
+        let path = test_path("io_stats");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let other = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            file.write(0, Arc::from(vec![1u8; 100]), WriteOptions::None).await?;
+            other.write(100, Arc::from(vec![2u8; 28]), WriteOptions::None).await?;
+            file.read(0, 64).await?;
+            // 超出文件末尾的读只统计实际读到的字节
+            other.read(100, 64).await?;
+            Ok::<_, Error>((file.io_stats(), other.io_stats()))
+        })
+        .unwrap();
+        assert_eq!(r.0, (92, 128));
+        assert_eq!(r.0, r.1);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn sharded_table_dedups_every_path() {
+        let paths = (0..64).map(|i| test_path(&format!("shard{}", i))).collect::<Vec<_>>();
+        let copy = paths.clone();
+        let r = block_on(async move {
+            let mut shared = Vec::new();
+            let mut files = Vec::new();
+            for path in copy.iter() {
+                let a = SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await?;
+                let b = SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await?;
+                shared.push(Arc::ptr_eq(&a.0, &b.0));
+                files.push(a);
+            }
+            // 所有路径分布到多个分片，每个路径只在自己的分片中
+            let mut used = 0;
+            for shard in OPEN_FILE_MAP.shards() {
+                let tab = shard.lock().await;
+                if copy.iter().any(|p| tab.contains_key(p)) {
+                    used += 1;
+                }
+            }
+            let mut found = true;
+            for path in copy.iter() {
+                found &= in_table(path).await;
+            }
+            Ok::<_, Error>((shared, used, found))
+        })
+        .unwrap();
+        assert!(r.0.iter().all(|shared| *shared));
+        assert!(r.1 > 1);
+        assert!(r.2);
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn open_rejects_incompatible_shared_handle() {
+        let path = test_path("open_conflict");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            // 可读写的句柄能满足只读和只写的请求
+            let reader = SafeFile::open(copy.clone(), AsyncFileOptions::OnlyRead).await?;
+            let writer = SafeFile::open(copy.clone(), AsyncFileOptions::OnlyWrite).await?;
+            let truncate = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await;
+            let append = SafeFile::try_open(copy.clone(), AsyncFileOptions::OnlyAppend).await;
+            Ok::<_, Error>((
+                Arc::ptr_eq(&reader.0, &file.0) && Arc::ptr_eq(&writer.0, &file.0),
+                // open返回的IO错误内部是Incompatible错误
+                truncate.map(|_| ()).map_err(|e| {
+                    let inner = e.get_ref().and_then(|e| e.downcast_ref::<FileError>());
+                    (e.kind(), matches!(inner, Some(FileError::Incompatible { path }) if *path == copy))
+                }),
+                matches!(append, Err(FileError::Incompatible { ref path }) if *path == copy),
+            ))
+        })
+        .unwrap();
+        assert_eq!(r, (true, Err((ErrorKind::AlreadyExists, true)), true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn open_tracked_reports_reuse() {
+        let path = test_path("open_tracked");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let (first, created) = SafeFile::open_tracked(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let (second, reused) = SafeFile::open_tracked(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let shared = Arc::ptr_eq(&first.0, &second.0);
+            drop(first);
+            drop(second);
+            // 所有句柄释放后再次打开会新建句柄
+            let (_, again) = SafeFile::open_tracked(copy, AsyncFileOptions::ReadWrite).await?;
+            Ok::<_, Error>((created, reused, shared, again))
+        })
+        .unwrap();
+        assert_eq!(r, (true, false, true, true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn last_drop_removes_table_entry() {
+        let path = test_path("drop_entry");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let clone = file.clone();
+            drop(file);
+            let held = in_table(&copy).await;
+            drop(clone);
+            // 表被占用时条目在运行时上异步移除
+            let mut removed = false;
+            for _ in 0..100 {
+                removed = !in_table(&copy).await;
+                if removed {
+                    break;
+                }
+                FILE_RUNTIME.timeout(1).await;
+            }
+            // 旧文件释放后不会移除重新打开的条目
+            let reopened = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let kept = in_table(&copy).await;
+            drop(reopened);
+            Ok::<_, Error>((held, removed, kept))
+        })
+        .unwrap();
+        assert_eq!(r, (true, true, true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_persists_newest_buffer_each_round() {
+        let path = test_path("truncate_rounds");
+        let copy = path.clone();
+        let file = block_on(async move { SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await }).unwrap();
+        let barrier = Arc::new(std::sync::Barrier::new(4));
+        let writers = (0..4u8)
+            .map(|t| {
+                let (file, barrier, path) = (file.clone(), barrier.clone(), path.clone());
+                thread::spawn(move || {
+                    let mut rounds = Vec::new();
+                    for round in 0..16u8 {
+                        let copy = file.clone();
+                        let byte = round * 4 + t;
+                        block_on(async move {
+                            copy.write(0, Arc::from(vec![byte; 32 + byte as usize]), WriteOptions::None).await
+                        })
+                        .unwrap();
+                        // 本轮所有写入完成后，磁盘上必须是最新的缓冲数据
+                        barrier.wait();
+                        if t == 0 {
+                            let copy = file.clone();
+                            let buffered = block_on(async move { copy.read(0, 1024).await }).unwrap();
+                            rounds.push((buffered, fs::read(&path).unwrap()));
+                        }
+                        barrier.wait();
+                    }
+                    rounds
+                })
+            })
+            .collect::<Vec<_>>();
+        let rounds = writers.into_iter().flat_map(|w| w.join().unwrap()).collect::<Vec<_>>();
+        assert_eq!(rounds.len(), 16);
+        for (buffered, disk) in rounds {
+            assert_eq!(buffered, disk);
+            assert!(disk.iter().all(|b| *b as usize + 32 == disk.len()));
+        }
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn concurrent_reads_share_data() {
+        let path = test_path("flight_data");
+        fs::write(&path, (0..255u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let reads = (0..8).map(|_| file.read(10, 100));
+            let r = future::join_all(reads).await.into_iter().collect::<Result<Vec<_>>>()?;
+            Ok::<_, Error>((r, file.0.flights.lock().is_empty()))
+        })
+        .unwrap();
+        assert!(r.0.iter().all(|data| *data == (10..110u8).collect::<Vec<_>>()));
+        assert!(r.1);
+        let _ = fs::remove_file(path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn concurrent_reads_share_error_without_poisoning() {
+        let path = test_path("flight_error");
+        let copy = path.clone();
+        let r = block_on(async move {
+            // 只追加的文件不可读，读取返回系统错误
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyAppend).await?;
+            file.write(0, Arc::from(&b"abc"[..]), WriteOptions::Flush).await?;
+            // 共享的错误保留错误码
+            let bad_fd = |r: Result<Vec<u8>>| r.err().and_then(|e| e.raw_os_error()) == Some(libc::EBADF);
+            let reads = (0..4).map(|_| file.read(0, 3));
+            let shared = future::join_all(reads).await.into_iter().map(bad_fd).collect::<Vec<_>>();
+            // 失败的读不会留在表中，之后的读重新发起
+            let cleared = file.0.flights.lock().is_empty();
+            let again = bad_fd(file.read(0, 3).await);
+            Ok::<_, Error>((shared, cleared, again))
+        })
+        .unwrap();
+        assert_eq!(r, (vec![true; 4], true, true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn close_releases_last_handle_only() {
+        let path = test_path("close");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"closed"[..]), WriteOptions::None).await?;
+            // 关闭共享的句柄只释放本句柄
+            file.clone().close().await?;
+            let shared = (in_table(&copy).await, ref_count(copy.clone()).await);
+            file.close().await?;
+            Ok::<_, Error>((shared, in_table(&copy).await, ref_count(copy).await))
+        })
+        .unwrap();
+        assert_eq!(r, ((true, 1), false, 0));
+        assert_eq!(fs::read(&path).unwrap(), b"closed");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn each_option_chooses_lock_and_cache() {
+        use AsyncFileOptions::*;
+
+        let options = vec![OnlyRead, OnlyWrite, OnlyAppend, ReadAppend, ReadWrite, TruncateWrite, TruncateReadWrite];
+        let paths = (0..options.len()).map(|i| test_path(&format!("lock_type{}", i))).collect::<Vec<_>>();
+        for path in paths.iter() {
+            fs::write(path, b"x").unwrap();
+        }
+        let copy = paths.clone();
+        let r = block_on(async move {
+            let mut r = Vec::new();
+            for (path, options) in copy.into_iter().zip(options) {
+                let file = SafeFile::open(path, options).await?;
+                let lock = match file.0.lock {
+                    LockType::Lock(_) => "lock",
+                    LockType::Rw(_) => "rw",
+                    LockType::Immutable => "none",
+                };
+                r.push((lock, file.0.cache.enable));
+            }
+            Ok::<_, Error>(r)
+        })
+        .unwrap();
+        assert_eq!(
+            r,
+            vec![
+                ("none", true),
+                ("rw", false),
+                ("rw", false),
+                ("rw", true),
+                ("rw", true),
+                ("lock", true),
+                ("rw", false),
+            ]
+        );
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn read_only_file_rejects_writes() {
+        let path = test_path("immutable");
+        fs::write(&path, b"fixed").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let denied = |r: Result<usize>| r.map_err(|e| e.kind());
+            Ok::<_, Error>((
+                file.read(0, 16).await?,
+                denied(file.write(0, Arc::from(&b"x"[..]), WriteOptions::None).await),
+                denied(file.append(Arc::from(&b"x"[..])).await),
+                file.set_len(0).await.map_err(|e| e.kind()),
+            ))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"fixed");
+        assert_eq!(r.1, Err(ErrorKind::PermissionDenied));
+        assert_eq!(r.2, Err(ErrorKind::PermissionDenied));
+        assert_eq!(r.3, Err(ErrorKind::PermissionDenied));
+        assert_eq!(fs::read(&path).unwrap(), b"fixed");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn paged_cache_stays_coherent_with_writes() {
+        let path = test_path("paged_cache");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open_read_write_cached(copy.clone(), 4).await?;
+            let mut steps = Vec::new();
+            steps.push((file.read(0, 64).await?, fs::read(&copy)?));
+            // 写入只覆盖一页的部分
+            file.write(2, Arc::from(&b"ab"[..]), WriteOptions::None).await?;
+            steps.push((file.read(0, 64).await?, fs::read(&copy)?));
+            // 写入跨越两页，后一页是文件尾不足一页的页
+            file.write(6, Arc::from(&b"XYZW"[..]), WriteOptions::None).await?;
+            steps.push((file.read(5, 5).await?, fs::read(&copy)?[5..].to_vec()));
+            // 写入超出文件尾，原文件尾所在的页需要失效
+            file.write(12, Arc::from(&b"!!"[..]), WriteOptions::None).await?;
+            steps.push((file.read(0, 64).await?, fs::read(&copy)?));
+            Ok::<_, Error>(steps)
+        })
+        .unwrap();
+        for (read, disk) in r.iter() {
+            assert_eq!(read, disk);
+        }
+        assert_eq!(r[3].0, b"01ab45XYZW\0\0!!");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn remove_files_reports_each_path() {
+        let paths = (0..6).map(|i| test_path(&format!("remove_files{}", i))).collect::<Vec<_>>();
+        // 奇数序号的路径不存在
+        for path in paths.iter().step_by(2) {
+            fs::write(path, b"x").unwrap();
+        }
+        let copy = paths.clone();
+        let r = block_on(async move {
+            let held = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+            let removed = remove_files(copy.clone()).await;
+            Ok::<_, Error>((
+                removed.into_iter().map(|r| r.map_err(|e| e.kind())).collect::<Vec<_>>(),
+                in_table(&copy[0]).await,
+                held,
+            ))
+        })
+        .unwrap();
+        for (i, removed) in r.0.iter().enumerate() {
+            let expected = if i % 2 == 0 { Ok(()) } else { Err(ErrorKind::NotFound) };
+            assert_eq!(*removed, expected);
+        }
+        // 被移除的文件从打开文件表中移除
+        assert!(!r.1);
+        assert!(paths.iter().all(|path| !path.exists()));
+    }
+
+    #[test]
+    fn move_file_falls_back_to_copy_across_devices() {
+        let (src, dst) = (test_path("move_src"), test_path("move_dst"));
+        let missing = test_path("move_missing").join("dst");
+        fs::write(&src, b"moved").unwrap();
+        let (s, d, m) = (src.clone(), dst.clone(), missing.clone());
+        let r = block_on(async move {
+            // 模拟源和目标不在同一文件系统
+            let cross = |_: PathBuf, _: PathBuf| async { Err(Error::from(ErrorKind::CrossesDevices)) };
+            // 复制失败时保留源文件
+            let failed = move_file_with(s.clone(), m, cross).await.map_err(|e| e.kind());
+            let kept = fs::read(&s)?;
+            move_file_with(s, d, cross).await?;
+            Ok::<_, Error>((failed, kept))
+        })
+        .unwrap();
+        assert_eq!(r, (Err(ErrorKind::NotFound), b"moved".to_vec()));
+        assert!(!src.exists());
+        assert!(!missing.exists());
+        assert_eq!(fs::read(&dst).unwrap(), b"moved");
+        let _ = fs::remove_file(dst);
+    }
+
+    #[test]
+    fn move_file_renames_on_same_device() {
+        let (src, dst) = (test_path("rename_src"), test_path("rename_dst"));
+        fs::write(&src, b"renamed").unwrap();
+        let (s, d) = (src.clone(), dst.clone());
+        block_on(async move { move_file(s, d).await }).unwrap();
+        assert!(!src.exists());
+        assert_eq!(fs::read(&dst).unwrap(), b"renamed");
+        let _ = fs::remove_file(dst);
+    }
+
+    #[test]
+    fn copy_file_preserve_keeps_mode_and_times() {
+        let (src, dst) = (test_path("preserve_src"), test_path("preserve_dst"));
+        fs::write(&src, b"preserved").unwrap();
+        let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        fs::File::options().write(true).open(&src).unwrap().set_modified(mtime).unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&src, fs::Permissions::from_mode(0o640)).unwrap();
+        }
+        let (s, d) = (src.clone(), dst.clone());
+        let len = block_on(async move { copy_file_preserve(s, d).await }).unwrap();
+        assert_eq!(len, 9);
+        assert_eq!(fs::read(&dst).unwrap(), b"preserved");
+        let meta = fs::metadata(&dst).unwrap();
+        assert_eq!(meta.modified().unwrap(), mtime);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        }
+        let _ = fs::remove_file(src);
+        let _ = fs::remove_file(dst);
+    }
+
+    #[test]
+    fn write_errors_map_storage_full() {
+        let path = test_path("out_of_space");
+        let copy = path.clone();
+        let file = block_on(async move { SafeFile::open(copy, AsyncFileOptions::ReadWrite).await }).unwrap();
+        // 模拟底层写入返回空间不足
+        let full = file.out_of_space(Error::from(ErrorKind::StorageFull));
+        assert_eq!(full.kind(), ErrorKind::StorageFull);
+        let inner = full.get_ref().and_then(|e| e.downcast_ref::<FileError>());
+        assert!(matches!(inner, Some(FileError::OutOfSpace { path: p }) if *p == path));
+        // 其它错误不变
+        let other = file.out_of_space(Error::from_raw_os_error(5));
+        assert_eq!(other.raw_os_error(), Some(5));
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn follow_streams_appended_data() {
+        let path = test_path("follow");
+        fs::write(&path, b"ab").unwrap();
+        // 延迟后在后台修改文件
+        fn later(path: PathBuf, change: fn(&Path) -> std::io::Result<()>) {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(200));
+                change(&path).unwrap();
+            });
+        }
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let mut follow = Box::pin(file.follow(0));
+            let mut chunks = vec![follow.next().await.transpose()?];
+            later(copy.clone(), |p| {
+                use std::io::Write;
+                fs::OpenOptions::new().append(true).open(p)?.write_all(b"cd")
+            });
+            chunks.push(follow.next().await.transpose()?);
+            // 截断后从文件头重新读取
+            later(copy.clone(), |p| fs::write(p, b"x"));
+            chunks.push(follow.next().await.transpose()?);
+            // 移除后结束
+            later(copy, |p| fs::remove_file(p));
+            chunks.push(follow.next().await.transpose()?);
+            Ok::<_, Error>(chunks)
+        })
+        .unwrap();
+        assert_eq!(r, vec![Some(b"ab".to_vec()), Some(b"cd".to_vec()), Some(b"x".to_vec()), None]);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn write_if_version_detects_conflicts() {
+        for (name, options) in [("cas_truncate", AsyncFileOptions::TruncateWrite), ("cas_rw", AsyncFileOptions::ReadWrite)] {
+            let path = test_path(name);
+            let copy = path.clone();
+            let r = block_on(async move {
+                let file = SafeFile::open(copy, options).await?;
+                // 只覆写文件不可读，读取的是缓冲区中最近一次写入的数据
+                file.write(0, Arc::from(&b"v0"[..]), WriteOptions::None).await?;
+                let (data, version) = file.read_to_end_versioned().await?;
+                let swapped = file.write_if_version(Arc::from(&b"v1"[..]), version).await?;
+                // 版本已变化，之前获取的版本写入失败
+                let stale = file.write_if_version(Arc::from(&b"v2"[..]), version).await.unwrap_err();
+                let (now, current) = file.read_to_end_versioned().await?;
+                Ok::<_, Error>((data, swapped, stale, now, version != current))
+            })
+            .unwrap();
+            assert_eq!(r.0, b"v0");
+            assert_eq!(r.1, 2);
+            let inner = r.2.get_ref().and_then(|e| e.downcast_ref::<FileError>());
+            assert!(matches!(inner, Some(FileError::VersionConflict { .. })));
+            assert_eq!(r.3, b"v1");
+            assert!(r.4);
+            assert_eq!(fs::read(&path).unwrap(), b"v1");
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn version_increments_on_each_write() {
+        let path = test_path("version");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+            let shared = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            let mut versions = vec![file.version()];
+            for buf in [&b"a"[..], &b"bb"[..]] {
+                file.write(0, Arc::from(buf), WriteOptions::None).await?;
+                versions.push(file.version());
+            }
+            // 同一路径的句柄共享版本
+            shared.write(0, Arc::from(&b"ccc"[..]), WriteOptions::None).await?;
+            versions.push(file.version());
+            Ok::<_, Error>(versions)
+        })
+        .unwrap();
+        let base = r[0];
+        assert_eq!(r, vec![base, base + 1, base + 2, base + 3]);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn version_is_sentinel_for_other_files() {
+        let path = test_path("version_sentinel");
+        fs::write(&path, b"data").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let before = file.version();
+            let (_, token) = file.read_to_end_versioned().await?;
+            file.write(0, Arc::from(&b"more"[..]), WriteOptions::None).await?;
+            // 写入不改变哨兵值，哨兵值不能用于条件写入
+            let stale = file.write_if_version(Arc::from(&b"x"[..]), UNVERSIONED).await.is_err();
+            Ok::<_, Error>((before, token, file.version(), stale))
+        })
+        .unwrap();
+        assert_eq!((r.0, r.2, r.3), (UNVERSIONED, UNVERSIONED, true));
+        assert_ne!(r.1, UNVERSIONED);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn write_if_unmodified_since_refuses_newer_files() {
+        let path = test_path("unmodified_since");
+        fs::write(&path, b"----").unwrap();
+        let since = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let later = since + Duration::from_secs(3600);
+        // 修改磁盘上文件的修改时间
+        fn touch(path: &Path, time: SystemTime) -> std::io::Result<()> {
+            fs::File::options().write(true).open(path)?.set_modified(time)
+        }
+        touch(&path, since).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let written = file.write_if_unmodified_since(0, Arc::from(&b"ab"[..]), since).await?;
+            // 其它写入者修改了文件
+            touch(&copy, later)?;
+            let refused = file.write_if_unmodified_since(2, Arc::from(&b"cd"[..]), later - Duration::from_secs(1)).await;
+            Ok::<_, Error>((written, refused.unwrap_err()))
+        })
+        .unwrap();
+        assert_eq!(r.0, 2);
+        let inner = r.1.get_ref().and_then(|e| e.downcast_ref::<FileError>());
+        assert!(matches!(inner, Some(FileError::Modified { modified, .. }) if *modified == later));
+        assert_eq!(fs::read(&path).unwrap(), b"ab--");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn apply_patch_merges_sorted_patches() {
+        let path = test_path("apply_patch");
+        fs::write(&path, b"0123456789abcdef").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let patch = |pos: u64, buf: &[u8]| (pos, Arc::from(buf));
+            // 乱序给出，4和2重叠，6与之相邻，12单独写入
+            let ops = vec![patch(12, b"XY"), patch(4, b"CCC"), patch(2, b"AAA"), patch(6, b"D"), patch(9, b"")];
+            file.apply_patch(&ops).await?;
+            Ok::<_, Error>((file.read_to_end().await?, file.io_stats().1))
+        })
+        .unwrap();
+        // 重叠部分以在ops中靠后的补丁为准
+        assert_eq!(r.0, b"01AAACD789abXYef");
+        // 合并为两次写入，共7字节
+        assert_eq!(r.1, 7);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn cache_stats_count_hits_and_misses() {
+        let path = test_path("cache_stats");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            let empty = file.cache_stats();
+            // 部分读不填充缓存，读到文件尾的全数据读填充缓存
+            file.read(0, 4).await?;
+            file.read(0, 64).await?;
+            file.read(2, 4).await?;
+            // 克隆的句柄共享统计
+            file.clone().read(4, 4).await?;
+            Ok::<_, Error>((empty, file.cache_stats()))
+        })
+        .unwrap();
+        assert_eq!(r.0, CacheStats::default());
+        assert_eq!(r.0.hit_ratio(), 0.0);
+        assert_eq!(r.1, CacheStats { hits: 2, misses: 2 });
+        assert_eq!(r.1.hit_ratio(), 0.5);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn drop_cache_releases_without_losing_data() {
+        let path = test_path("drop_cache");
+        fs::write(&path, b"cached").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            file.read_to_end().await?;
+            file.read(0, 3).await?;
+            file.drop_cache().await?;
+            // 释放后重新从磁盘读取
+            let data = file.read(0, 3).await?;
+            Ok::<_, Error>((data, file.cache_stats()))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"cac");
+        assert_eq!(r.1, CacheStats { hits: 1, misses: 2 });
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncating_handle_rejects_positional_reopen() {
+        let path = test_path("truncate_read_write_reopen");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateReadWrite).await?;
+            // 相同的方式和只读共享句柄及缓存
+            let same = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateReadWrite).await?;
+            let reader = SafeFile::open(copy.clone(), AsyncFileOptions::OnlyRead).await?;
+            // 按位置写不能共享每次写入都会截断文件的句柄
+            let write = SafeFile::try_open(copy.clone(), AsyncFileOptions::ReadWrite).await;
+            let only_write = SafeFile::try_open(copy.clone(), AsyncFileOptions::OnlyWrite).await;
+            Ok::<_, Error>((
+                Arc::ptr_eq(&same.0, &file.0) && Arc::ptr_eq(&reader.0, &file.0),
+                matches!(write, Err(FileError::Incompatible { .. })),
+                matches!(only_write, Err(FileError::Incompatible { .. })),
+            ))
+        })
+        .unwrap();
+        assert_eq!(r, (true, true, true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn concurrent_buffered_reads_see_whole_writes() {
+        let path = test_path("buffered_concurrent");
+        let copy = path.clone();
+        let file = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(vec![0u8; 64]), WriteOptions::None).await?;
+            Ok::<_, Error>(file)
+        })
+        .unwrap();
+        // 读取期间不断替换缓冲数据，每次读到的都是某次写入的全数据
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let file = file.clone();
+                thread::spawn(move || {
+                    block_on(async move {
+                        let mut torn = 0;
+                        for _ in 0..200 {
+                            let data = file.read(0, 64).await?;
+                            if data.len() != 64 || data.iter().any(|&b| b != data[0]) {
+                                torn += 1;
+                            }
+                        }
+                        Ok::<_, Error>(torn)
+                    })
+                })
+            })
+            .collect();
+        let writer = file.clone();
+        let written = block_on(async move {
+            for round in 1..=50u8 {
+                writer.write(0, Arc::from(vec![round; 64]), WriteOptions::None).await?;
+            }
+            Ok::<_, Error>(writer.version())
+        })
+        .unwrap();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap().unwrap(), 0);
+        }
+        assert_eq!(file.0.buffered().to_vec(), vec![50u8; 64]);
+        assert!(written >= 51);
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn buffered_reads_complete_on_first_poll() {
+        use std::pin::pin;
+        use std::task::{Context, Poll, Waker};
+
+        let path = test_path("inline_read");
+        let copy = path.clone();
+        let file = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from((0..100u8).collect::<Vec<_>>()), WriteOptions::Flush).await?;
+            Ok::<_, Error>(file)
+        })
+        .unwrap();
+        let disk = fs::read(&path).unwrap();
+        let mut cx = Context::from_waker(Waker::noop());
+        for (pos, len) in [(0, 10), (95, 10), (100, 4), (40, 60)] {
+            // 与落地的数据一致
+            let start = (pos as usize).min(disk.len());
+            let end = (start + len).min(disk.len());
+            match pin!(file.read(pos, len)).poll(&mut cx) {
+                Poll::Ready(Ok(r)) => assert_eq!(r, &disk[start..end]),
+                r => panic!("read is not ready, pos: {}, result: {:?}", pos, r),
+            }
+        }
+        drop(file);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn cache_whole_reads_small_files_on_open() {
+        let (small, large) = (test_path("cache_whole_small"), test_path("cache_whole_large"));
+        fs::write(&small, vec![1u8; 64]).unwrap();
+        fs::write(&large, vec![2u8; 256]).unwrap();
+        let paths = (small.clone(), large.clone());
+        let r = block_on(async move {
+            let cache = CacheOptions {
+                cache_whole: Some(64),
+                ..CacheOptions::default()
+            };
+            let small = SafeFile::open_with(paths.0, AsyncFileOptions::ReadWrite, cache).await?;
+            let large = SafeFile::open_with(paths.1, AsyncFileOptions::ReadWrite, cache).await?;
+            let first = (small.read(8, 8).await?, large.read(8, 8).await?);
+            let stats = (small.cache_stats(), large.cache_stats());
+            // 写入后缓存随之更新
+            small.write(8, Arc::from(&b"written"[..]), WriteOptions::None).await?;
+            Ok::<_, Error>((first, stats, small.read(8, 8).await?))
+        })
+        .unwrap();
+        assert_eq!((r.0).0, vec![1u8; 8]);
+        assert_eq!((r.0).1, vec![2u8; 8]);
+        assert_eq!((r.1).0, CacheStats { hits: 1, misses: 0 });
+        assert_eq!((r.1).1, CacheStats { hits: 0, misses: 1 });
+        assert_eq!(r.2, b"written\x01");
+        let _ = fs::remove_file(small);
+        let _ = fs::remove_file(large);
+    }
+
+    #[test]
+    fn open_timeout_gives_up_on_slow_open() {
+        let path = test_path("open_timeout");
+        let copy = path.clone();
+        let r = block_on(async move {
+            // 其它任务占位后迟迟不完成打开，模拟缓慢的打开
+            let slow = match SafeFile::lookup(&copy).await {
+                Lookup::Reserved(guard) => guard,
+                Lookup::Found(_) => unreachable!(),
+            };
+            let r = SafeFile::open_timeout(copy.clone(), AsyncFileOptions::ReadWrite, 30).await;
+            // 占位仍被其它任务持有，不会被移除
+            let held = in_table(&copy).await;
+            SafeFile::remove_abandoned(&copy).await;
+            let kept = in_table(&copy).await;
+            // 占位的任务放弃打开后，占位被移除
+            drop(slow);
+            SafeFile::remove_abandoned(&copy).await;
+            (r.map(|_| ()).map_err(|e| e.kind()), held, kept, in_table(&copy).await)
+        });
+        assert_eq!(r, (Err(ErrorKind::TimedOut), true, true, false));
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn evict_prefix_keeps_siblings() {
+        let dir = test_path("evict_dir");
+        let sibling = test_path("evict_dir_sibling");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let (a, b) = (dir.join("a"), dir.join("sub").join("b"));
+        let paths = (dir.clone(), a.clone(), b.clone(), sibling.clone());
+        let r = block_on(async move {
+            let (dir, a, b, sibling) = paths;
+            let _a = SafeFile::open(a.clone(), AsyncFileOptions::ReadWrite).await?;
+            let b_file = SafeFile::open(b.clone(), AsyncFileOptions::TruncateWrite).await?;
+            let _sibling = SafeFile::open(sibling.clone(), AsyncFileOptions::ReadWrite).await?;
+            // 模拟尚未落地的缓冲数据，移除前写入文件
+            b_file.0.set_buff(Arc::from(&b"pending"[..]), 1);
+            // 忽略路径中的"."
+            let count = evict_prefix(dir.join(".")).await;
+            Ok::<_, Error>((count, in_table(&a).await, in_table(&b).await, in_table(&sibling).await))
+        })
+        .unwrap();
+        assert_eq!(r, (2, false, false, true));
+        assert_eq!(fs::read(&b).unwrap(), b"pending");
+        let _ = fs::remove_dir_all(dir);
+        let _ = fs::remove_file(sibling);
+    }
+
+    #[test]
+    fn list_open_paths_skips_closed_files() {
+        let paths: Vec<_> = ["a", "b", "c"].iter().map(|name| test_path(&format!("list_open_{}", name))).collect();
+        let copy = paths.clone();
+        let r = block_on(async move {
+            let mut files = Vec::new();
+            for path in &copy {
+                files.push(SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await?);
+            }
+            let open = list_open_paths().await;
+            // 已关闭的文件不再列出
+            files.pop();
+            Ok::<_, Error>((open, list_open_paths().await))
+        })
+        .unwrap();
+        // 其它测试同时打开的文件也会列出
+        assert!(paths.iter().all(|path| r.0.contains(path)));
+        assert!(paths[..2].iter().all(|path| r.1.contains(path)));
+        assert!(!r.1.contains(&paths[2]));
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn advise_keeps_data_readable() {
+        let path = test_path("advise_read");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            file.advise(0, 0, Advice::Sequential).await?;
+            let data = file.read(0, 10).await?;
+            // 建议只影响内核的页缓存，不影响之后的读
+            file.advise(0, 0, Advice::DontNeed).await?;
+            Ok::<_, Error>((data, file.read(2, 3).await?))
+        })
+        .unwrap();
+        assert_eq!(r, (b"0123456789".to_vec(), b"234".to_vec()));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn preallocate_extends_with_zeros() {
+        let path = test_path("preallocate");
+        fs::write(&path, b"abc").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            // 先读入缓存，预分配后缓存也应加长
+            file.read_to_end().await?;
+            file.preallocate(8).await?;
+            let grown = (file.read(0, 16).await?, file.len().await?);
+            // 不会缩短文件
+            file.preallocate(2).await?;
+            let kept = file.len().await?;
+            file.close().await?;
+            let reader = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            Ok::<_, Error>((grown, kept, reader.preallocate(16).await.map_err(|e| e.kind())))
+        })
+        .unwrap();
+        assert_eq!(r.0, (b"abc\0\0\0\0\0".to_vec(), 8));
+        assert_eq!(r.1, 8);
+        assert_eq!(r.2, Err(ErrorKind::PermissionDenied));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn punch_hole_zeros_cached_range() {
+        let path = test_path("punch_hole");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            // 先读入缓存，释放后缓存中的范围也应置零
+            file.read_to_end().await?;
+            let punched = file.punch_hole(2, 3).await.map_err(|e| e.kind());
+            Ok::<_, Error>((punched, file.read(0, 16).await?, file.len().await?))
+        })
+        .unwrap();
+        match r.0 {
+            Ok(()) => {
+                assert_eq!(r.1, b"01\x00\x00\x0056789");
+                assert_eq!(fs::read(&path).unwrap(), b"01\x00\x00\x0056789");
+            }
+            // 不支持时文件和缓存都不变
+            Err(kind) => {
+                assert_eq!(kind, ErrorKind::Unsupported);
+                assert_eq!(r.1, b"0123456789");
+            }
+        }
+        assert_eq!(r.2, 10);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_honours_pos() {
+        let path = test_path("truncate_pos");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+            // 写入位置之前的部分补零
+            file.write(3, Arc::from(&b"abc"[..]), WriteOptions::Flush).await?;
+            let first = (file.read(0, 16).await?, fs::read(&copy)?);
+            // 每次写入都会先清空文件
+            file.write(1, Arc::from(&b"x"[..]), WriteOptions::Flush).await?;
+            Ok::<_, Error>((first, file.read(0, 16).await?, fs::read(&copy)?))
+        })
+        .unwrap();
+        assert_eq!(r.0 .0, b"\0\0\0abc");
+        assert_eq!(r.0 .1, b"\0\0\0abc");
+        assert_eq!(r.1, b"\0x");
+        assert_eq!(r.2, b"\0x");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_buffers_with_cache_disabled() {
+        let path = test_path("truncate_no_cache");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let disabled = CacheOptions { enable: false, ..Default::default() };
+            let file = SafeFile::open_with(copy, AsyncFileOptions::TruncateWrite, disabled).await?;
+            // 截断写文件总是缓冲最近一次写入的全数据
+            file.write(0, Arc::from(&b"buffered"[..]), WriteOptions::None).await?;
+            Ok::<_, Error>((file.0.cacheable(8), file.read(0, 16).await?))
+        })
+        .unwrap();
+        assert_eq!(r, (true, b"buffered".to_vec()));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_refreshes_live_handles() {
+        let path = test_path("atomic_live");
+        fs::write(&path, b"old").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let live = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            let before = live.read_to_end().await?;
+            let version = live.0.gen.load(Ordering::Acquire);
+            atomic_write(copy.clone(), Arc::from(&b"newer"[..])).await?;
+            // 已有的句柄以新内容刷新缓存，代数随之增加
+            let cached = (live.read_to_end().await?, live.read(2, 8).await?, live.0.gen.load(Ordering::Acquire) > version);
+            let reopened = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+            Ok::<_, Error>((before, cached, reopened.read_to_end().await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, b"old");
+        assert_eq!(r.1, (b"newer".to_vec(), b"wer".to_vec(), true));
+        assert_eq!(r.2, b"newer");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn uncached_read_into_stops_at_eof() {
+        let path = test_path("read_into_eof");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let disabled = CacheOptions { enable: false, ..Default::default() };
+            let file = SafeFile::open_with(copy, AsyncFileOptions::ReadWrite, disabled).await?;
+            let mut buf = [0u8; 8];
+            let head = file.read_into(0, &mut buf).await?;
+            let first = buf;
+            // 读到文件尾则少于缓冲区长度
+            let tail = file.read_into(6, &mut buf).await?;
+            Ok::<_, Error>(((head, first), (tail, buf), file.read_into(20, &mut buf).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, (8, *b"01234567"));
+        assert_eq!(r.1, (4, *b"67894567"));
+        assert_eq!(r.2, 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn append_follows_external_growth() {
+        let path = test_path("append_external");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadAppend).await?;
+            let first = file.append_at(Arc::from(&b"ab"[..])).await?;
+            // 其它进程加长文件后，追加位置取自文件的当前长度
+            fs::OpenOptions::new().append(true).open(&copy)?.write_all(b"cd")?;
+            let second = file.append_at(Arc::from(&b"ef"[..])).await?;
+            Ok::<_, Error>((first, second, file.append_at(Arc::from(Vec::new())).await?))
+        })
+        .unwrap();
+        assert_eq!(r, ((0, 2), (4, 2), (6, 0)));
+        assert_eq!(fs::read(&path).unwrap(), b"abcdef");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_switches_uncached_live_handles() {
+        let path = test_path("atomic_switch");
+        fs::write(&path, b"old").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let disabled = CacheOptions { enable: false, ..Default::default() };
+            let live = SafeFile::open_with(copy.clone(), AsyncFileOptions::ReadWrite, disabled).await?;
+            atomic_write(copy.clone(), Arc::from(&b"newer"[..])).await?;
+            // 未开启缓存的读和写都指向新文件，再次打开仍共享已有的句柄
+            let read = (live.read(0, 16).await?, live.len().await?);
+            live.write(0, Arc::from(&b"N"[..]), WriteOptions::Flush).await?;
+            let reopened = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            Ok::<_, Error>((read, fs::read(&copy)?, Arc::ptr_eq(&live.0, &reopened.0)))
+        })
+        .unwrap();
+        assert_eq!(r, ((b"newer".to_vec(), 5), b"Newer".to_vec(), true));
+        assert_eq!(temp_siblings(&path), 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_truncate_live_handle_keeps_new_contents() {
+        let path = test_path("atomic_truncate");
+        fs::write(&path, b"old").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let live = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateReadWrite).await?;
+            atomic_write(copy.clone(), Arc::from(&b"newer"[..])).await?;
+            let replaced = fs::read(&copy)?;
+            // 截断写切换到新文件后覆盖新文件
+            live.write(0, Arc::from(&b"last"[..]), WriteOptions::Flush).await?;
+            Ok::<_, Error>((replaced, fs::read(&copy)?))
+        })
+        .unwrap();
+        assert_eq!(r, (b"newer".to_vec(), b"last".to_vec()));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn atomic_write_failed_rename_keeps_live_handle() {
+        let path = test_path("atomic_live_fail");
+        fs::write(&path, b"original").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let disabled = CacheOptions { enable: false, ..Default::default() };
+            let live = SafeFile::open_with(copy.clone(), AsyncFileOptions::ReadWrite, disabled).await?;
+            // 模拟临时文件写入后重命名前失败
+            let fail = |_: PathBuf, _: PathBuf| async { Err(Error::other("injected")) };
+            let failed = atomic_write_with(copy.clone(), Arc::from(&b"new"[..]), fail).await.is_err();
+            let read = live.read(0, 16).await?;
+            live.write(0, Arc::from(&b"O"[..]), WriteOptions::Flush).await?;
+            let reopened = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            Ok::<_, Error>((failed, read, fs::read(&copy)?, Arc::ptr_eq(&live.0, &reopened.0)))
+        })
+        .unwrap();
+        assert_eq!(r, (true, b"original".to_vec(), b"Original".to_vec(), true));
+        assert_eq!(temp_siblings(&path), 0);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn atomic_write_remaps_live_mmap() {
+        let path = test_path("atomic_mmap");
+        fs::write(&path, b"old").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let mapped = SafeFile::open_mmap(copy.clone()).await?;
+            atomic_write(copy, Arc::from(&b"newer"[..])).await?;
+            Ok::<_, Error>((mapped.read(0, 16).await?, mapped.0.mmap.load().is_some()))
+        })
+        .unwrap();
+        assert_eq!(r, (b"newer".to_vec(), true));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_into_reads_through_page_cache() {
+        let path = test_path("read_into_paged");
+        fs::write(&path, (0..40u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open_read_write_cached(copy, 16).await?;
+            let mut buf = [0u8; 10];
+            let first = (file.read_into(12, &mut buf).await?, buf.to_vec());
+            // 未命中时读入的页已缓存，再次读取命中页缓存
+            let second = (file.read_into(20, &mut buf).await?, buf.to_vec());
+            let stats = file.cache_stats();
+            Ok::<_, Error>((first, second, (stats.hits, stats.misses), file.io_stats().0))
+        })
+        .unwrap();
+        let expect = |start: u8| (10, (start..start + 10).collect::<Vec<_>>());
+        assert_eq!(r, (expect(12), expect(20), (1, 1), 20));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn open_mmap_rejects_write_only_handle() {
+        let path = test_path("mmap_conflict");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let _writer = SafeFile::open(copy.clone(), AsyncFileOptions::OnlyWrite).await?;
+            let mapped = SafeFile::open_mmap(copy.clone()).await;
+            Ok::<_, Error>(mapped.map(|_| ()).map_err(|e| {
+                let inner = e.get_ref().and_then(|e| e.downcast_ref::<FileError>());
+                (e.kind(), matches!(inner, Some(FileError::Incompatible { path }) if *path == copy))
+            }))
+        })
+        .unwrap();
+        assert_eq!(r, Err((ErrorKind::AlreadyExists, true)));
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn concurrent_incompatible_opens_share_or_fail() {
+        let path = test_path("open_race_mixed");
+        let openers = (0..16)
+            .map(|i| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    // 读写和截断写互不兼容，先完成打开的方式决定其它打开的结果
+                    let options = if i % 2 == 1 { AsyncFileOptions::TruncateWrite } else { AsyncFileOptions::ReadWrite };
+                    block_on(async move { SafeFile::try_open(path, options).await })
+                })
+            })
+            .collect::<Vec<_>>();
+        let results = openers.into_iter().map(|opener| opener.join().unwrap()).collect::<Vec<_>>();
+        let files = results.iter().filter_map(|r| r.as_ref().ok()).collect::<Vec<_>>();
+        assert_eq!(files.len(), 8);
+        assert!(files.iter().all(|file| Arc::ptr_eq(&file.0, &files[0].0)));
+        assert!(results
+            .iter()
+            .filter_map(|r| r.as_ref().err())
+            .all(|e| matches!(e, FileError::Incompatible { path: p } if *p == path)));
+        drop(results);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn truncate_write_rejects_huge_gap() {
+        let path = test_path("truncate_gap");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+            file.write(2, Arc::from(&b"ab"[..]), WriteOptions::Flush).await?;
+            // 补零超过上限时不分配缓冲区，缓冲数据和文件保持不变
+            let mut rejected = Vec::new();
+            for pos in [MAX_TRUNCATE_GAP + 1, u64::MAX] {
+                rejected.push(file.write(pos, Arc::from(&b"x"[..]), WriteOptions::Flush).await.map_err(|e| e.kind()));
+            }
+            Ok::<_, Error>((rejected, file.read(0, 16).await?, fs::read(&copy)?))
+        })
+        .unwrap();
+        assert_eq!(r.0, [Err(ErrorKind::InvalidInput), Err(ErrorKind::InvalidInput)]);
+        assert_eq!(r.1, b"\x00\x00ab");
+        assert_eq!(r.2, b"\x00\x00ab");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn write_if_version_detects_external_changes() {
+        let path = test_path("cas_external");
+        fs::write(&path, b"v0").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy.clone(), AsyncFileOptions::ReadWrite).await?;
+            // 模拟其它进程改变文件长度
+            let (_, version) = file.read_to_end_versioned().await?;
+            fs::write(&copy, b"other")?;
+            let resized = file.write_if_version(Arc::from(&b"v1"[..]), version).await.is_err();
+            // 模拟其它进程写入相同长度的数据，只改变修改时间
+            let (data, version) = file.read_to_end_versioned().await?;
+            let external = fs::OpenOptions::new().write(true).open(&copy)?;
+            external.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(1))?;
+            let touched = file.write_if_version(Arc::from(&b"v2"[..]), version).await.is_err();
+            let (_, version) = file.read_to_end_versioned().await?;
+            let swapped = file.write_if_version(Arc::from(&b"v3"[..]), version).await?;
+            Ok::<_, Error>((resized, data, touched, swapped))
+        })
+        .unwrap();
+        assert_eq!(r, (true, b"other".to_vec(), true, 2));
+        assert_eq!(fs::read(&path).unwrap(), b"v3");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,76 @@
+use std::fs::File;
+use std::io::Result;
+
+/*
+* 进程间建议锁的类型
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OsLock {
+    Shared,    //共享锁
+    Exclusive, //独占锁
+    Unlock,    //解锁
+}
+
+// 在文件上阻塞的获取或释放建议锁，锁属于打开的文件描述，复制的描述符共享同一把锁
+#[cfg(unix)]
+pub(crate) fn flock(file: &File, lock: OsLock) -> Result<()> {
+    use std::io::Error;
+    use std::os::unix::io::AsRawFd;
+
+    let op = match lock {
+        OsLock::Shared => libc::LOCK_SH,
+        OsLock::Exclusive => libc::LOCK_EX,
+        OsLock::Unlock => libc::LOCK_UN,
+    };
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), op) } == 0 {
+            return Ok(());
+        }
+        let e = Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+// 其它平台暂不支持进程间建议锁
+#[cfg(not(unix))]
+pub(crate) fn flock(_file: &File, _lock: OsLock) -> Result<()> {
+    use std::io::{Error, ErrorKind};
+
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "Lock file failed, reason: advisory lock unsupported on this platform",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::tests::test_path;
+    use std::fs::{self, OpenOptions};
+    use std::os::unix::io::AsRawFd;
+
+    // 以非阻塞方式尝试获取独占锁
+    fn try_exclusive(file: &File) -> bool {
+        unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) == 0 }
+    }
+
+    #[test]
+    fn shared_locks_coexist_and_exclude_writers() {
+        let path = test_path("flock");
+        let open = || OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).unwrap();
+        let (a, b, c) = (open(), open(), open());
+        flock(&a, OsLock::Shared).unwrap();
+        flock(&b, OsLock::Shared).unwrap();
+        assert!(!try_exclusive(&c));
+        flock(&a, OsLock::Unlock).unwrap();
+        flock(&b, OsLock::Unlock).unwrap();
+        assert!(try_exclusive(&c));
+        // 复制的描述符共享同一把锁
+        let dup = c.try_clone().unwrap();
+        flock(&dup, OsLock::Unlock).unwrap();
+        assert!(try_exclusive(&a));
+        let _ = fs::remove_file(path);
+    }
+}
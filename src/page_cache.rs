@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use pi_async_rt::lock::spin_lock::SpinLock;
+use pi_hash::XHashMap;
+
+/*
+* 按页缓存文件的数据，写入时使受影响的页失效并增加页的版本，读到的数据只有在页的版本未变时才能填充缓存
+* 文件尾所在的页可能不足一页，不足一页的页表示文件在此结束
+*/
+pub(crate) struct PageCache {
+    page_size: usize,                             //每页的字节数
+    max_size: usize,                              //缓存的最大字节数，超过则不再缓存新的页
+    pages: SpinLock<(XHashMap<u64, Page>, usize)>, //页号到页的映射，及已缓存的字节数
+}
+
+// 缓存的页，失效后只保留版本
+struct Page {
+    data: Option<Arc<[u8]>>,
+    version: u64,
+}
+
+impl PageCache {
+    // 构建指定页大小和缓存上限的页缓存
+    pub(crate) fn new(page_size: usize, max_size: usize) -> Self {
+        PageCache {
+            page_size,
+            max_size,
+            pages: SpinLock::new((XHashMap::default(), 0)),
+        }
+    }
+
+    // 获取指定位置所在页的起始位置
+    pub(crate) fn page_start(&self, pos: u64) -> u64 {
+        pos - pos % self.page_size as u64
+    }
+
+    // 获取每页的字节数
+    pub(crate) fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    // 从缓存中获取指定范围的数据，超出文件尾的部分截断，有页未缓存则返回范围内所有页当前的版本
+    pub(crate) fn get(&self, pos: u64, len: usize) -> Result<Vec<u8>, Vec<u64>> {
+        let first = pos / self.page_size as u64;
+        let last = (pos + len as u64 - 1) / self.page_size as u64;
+        let pages = self.pages.lock();
+        let mut data = Vec::with_capacity(len);
+        for index in first..=last {
+            let page = match pages.0.get(&index).and_then(|page| page.data.as_ref()) {
+                None => {
+                    let versions = (first..=last)
+                        .map(|index| pages.0.get(&index).map(|page| page.version).unwrap_or(0))
+                        .collect();
+                    return Err(versions);
+                }
+                Some(page) => page,
+            };
+            let page_pos = index * self.page_size as u64;
+            let start = (pos.max(page_pos) - page_pos) as usize;
+            let end = ((pos + len as u64).min(page_pos + self.page_size as u64) - page_pos) as usize;
+            data.extend_from_slice(&page[start.min(page.len())..end.min(page.len())]);
+            if page.len() < self.page_size {
+                // 已到文件尾
+                break;
+            }
+        }
+        Ok(data)
+    }
+
+    // 用从指定页开始读到的页对齐的数据填充缓存，只填充版本未变且未缓存的页
+    pub(crate) fn fill(&self, first: u64, versions: &[u64], data: &[u8]) {
+        let mut pages = self.pages.lock();
+        for (offset, chunk) in data.chunks(self.page_size).enumerate() {
+            let index = first + offset as u64;
+            let version = match versions.get(offset) {
+                None => break,
+                Some(version) => *version,
+            };
+            let page = pages.0.entry(index).or_insert(Page { data: None, version: 0 });
+            if page.version != version || page.data.is_some() {
+                continue;
+            }
+            if pages.1 + chunk.len() > self.max_size {
+                break;
+            }
+            pages.0.get_mut(&index).unwrap().data = Some(Arc::from(chunk));
+            pages.1 += chunk.len();
+        }
+    }
+
+    // 获取已缓存的字节数
+    pub(crate) fn cached_size(&self) -> usize {
+        self.pages.lock().1
+    }
+
+    // 使与写入范围重叠的页，以及文件尾不足一页的页失效，并增加这些页的版本
+    // 读取和填充都在持有读锁时进行，写入持有写锁，因此尚未读取过的页不需要记录版本
+    pub(crate) fn invalidate(&self, pos: u64, len: usize) {
+        let first = pos / self.page_size as u64;
+        let last = (pos + len.max(1) as u64 - 1) / self.page_size as u64;
+        let page_size = self.page_size;
+        let mut pages = self.pages.lock();
+        let mut freed = 0;
+        for (index, page) in pages.0.iter_mut() {
+            let short = page.data.as_ref().is_some_and(|data| data.len() < page_size);
+            if (first..=last).contains(index) || short {
+                freed += page.data.take().map(|data| data.len()).unwrap_or(0);
+                page.version += 1;
+            }
+        }
+        pages.1 -= freed;
+    }
+
+    // 使所有页失效，并增加所有页的版本
+    pub(crate) fn clear(&self) {
+        let mut pages = self.pages.lock();
+        for page in pages.0.values_mut() {
+            page.data = None;
+            page.version += 1;
+        }
+        pages.1 = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_returns_cached_range() {
+        let cache = PageCache::new(4, usize::MAX);
+        assert_eq!(cache.get(2, 4), Err(vec![0, 0]));
+        cache.fill(0, &[0, 0, 0], b"abcdefghij");
+        assert_eq!(cache.get(2, 4), Ok(b"cdef".to_vec()));
+        // 文件尾所在的页不足一页，超出文件尾的部分截断
+        assert_eq!(cache.get(6, 100), Ok(b"ghij".to_vec()));
+    }
+
+    #[test]
+    fn invalidate_bumps_versions_and_rejects_stale_fill() {
+        let cache = PageCache::new(4, usize::MAX);
+        cache.fill(0, &[0, 0], b"abcdefgh");
+        cache.invalidate(5, 1);
+        assert_eq!(cache.get(0, 4), Ok(b"abcd".to_vec()));
+        let versions = cache.get(0, 8).unwrap_err();
+        assert_eq!(versions, vec![0, 1]);
+        // 读取期间再次写入，页版本已变，读到的旧数据不会被填充
+        cache.invalidate(4, 1);
+        cache.fill(0, &versions, b"abcdefgh");
+        assert_eq!(cache.get(4, 4), Err(vec![2]));
+        cache.clear();
+        assert_eq!(cache.get(0, 8), Err(vec![1, 3]));
+    }
+
+    #[test]
+    fn fill_stops_at_max_size() {
+        let cache = PageCache::new(4, 6);
+        cache.fill(0, &[0, 0], b"abcdefgh");
+        assert_eq!(cache.get(0, 4), Ok(b"abcd".to_vec()));
+        assert!(cache.get(4, 4).is_err());
+    }
+
+    #[test]
+    fn cached_size_follows_fill_and_invalidate() {
+        let cache = PageCache::new(4, usize::MAX);
+        assert_eq!(cache.cached_size(), 0);
+        cache.fill(0, &[0, 0, 0], b"abcdefghij");
+        assert_eq!(cache.cached_size(), 10);
+        //不足一页的尾页也会一并失效
+        cache.invalidate(4, 1);
+        assert_eq!(cache.cached_size(), 4);
+        cache.clear();
+        assert_eq!(cache.cached_size(), 0);
+    }
+}
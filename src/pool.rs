@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{Error, ErrorKind, Result};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+use pi_async_rt::lock::spin_lock::SpinLock;
+
+use crate::{run_sync, runtime, SafeFile};
+
+// 最小的缓冲区大小级别，4KB
+const MIN_CLASS_SHIFT: u32 = 12;
+// 最大的缓冲区大小级别，4MB，超过则不回收
+const MAX_CLASS_SHIFT: u32 = 22;
+// 每个大小级别最多缓存的缓冲区数量
+const MAX_POOLED: usize = 64;
+
+lazy_static! {
+    // 按大小级别划分的缓冲区池，第i级的缓冲区容量为2^(MIN_CLASS_SHIFT+i)
+    static ref BUFFER_POOL: Vec<SpinLock<Vec<Vec<u8>>>> =
+        (MIN_CLASS_SHIFT..=MAX_CLASS_SHIFT).map(|_| SpinLock::new(Vec::new())).collect();
+}
+
+// 获取可容纳指定长度的大小级别，超过最大级别则返回None
+fn size_class(len: usize) -> Option<usize> {
+    let shift = len.max(1).next_power_of_two().trailing_zeros().max(MIN_CLASS_SHIFT);
+    if shift > MAX_CLASS_SHIFT {
+        None
+    } else {
+        Some((shift - MIN_CLASS_SHIFT) as usize)
+    }
+}
+
+/*
+* 从缓冲区池借出的字节数据，释放时将缓冲区归还缓冲区池
+*/
+#[derive(Debug)]
+pub struct PooledBytes {
+    buf: Vec<u8>,
+    class: Option<usize>,
+}
+
+impl PooledBytes {
+    // 借出容量可容纳指定长度的空缓冲区，不填充内容
+    fn take(len: usize) -> Self {
+        let class = size_class(len);
+        let buf = match class {
+            Some(index) => BUFFER_POOL[index]
+                .lock()
+                .pop()
+                .unwrap_or_else(|| Vec::with_capacity(1 << (MIN_CLASS_SHIFT as usize + index))),
+            None => Vec::with_capacity(len),
+        };
+        PooledBytes { buf, class }
+    }
+
+    // 获取数据的长度
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    // 数据是否为空
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    // 取出数据，取出后缓冲区不再归还缓冲区池
+    pub fn into_vec(mut self) -> Vec<u8> {
+        self.class = None;
+        mem::take(&mut self.buf)
+    }
+}
+
+impl Deref for PooledBytes {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBytes {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl AsRef<[u8]> for PooledBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Drop for PooledBytes {
+    fn drop(&mut self) {
+        if let Some(index) = self.class {
+            let mut pool = BUFFER_POOL[index].lock();
+            if pool.len() < MAX_POOLED && self.buf.capacity() >= 1 << (MIN_CLASS_SHIFT as usize + index) {
+                let mut buf = mem::take(&mut self.buf);
+                buf.clear();
+                pool.push(buf);
+            }
+        }
+    }
+}
+
+impl SafeFile {
+    //从指定位置开始异步读指定字节到从缓冲区池借出的缓冲区，释放时缓冲区归还缓冲区池
+    //未命中缓存时缓冲区移交运行时直接读入，不需要先填零
+    pub async fn read_pooled(&self, pos: u64, len: usize) -> Result<PooledBytes> {
+        let _op = runtime::enter()?;
+        let mut bytes = PooledBytes::take(len);
+        if len == 0 {
+            return Ok(bytes);
+        }
+        if self.with_cached(pos, len, |data| bytes.buf.extend_from_slice(data)).is_some() {
+            self.0.count_read(bytes.len());
+            return Ok(bytes);
+        }
+        if self.0.pages.is_some() {
+            let r = self.read(pos, len).await?;
+            bytes.buf.extend_from_slice(&r);
+            return Ok(bytes);
+        }
+        let _guard = self.read_lock().await;
+        self.0.count_cache(false);
+        let file = self.0.file();
+        let buf = mem::take(&mut bytes.buf);
+        bytes.buf = run_sync(move || {
+            let mut buf = buf;
+            read_spare_at(&file.get_inner()?, pos, &mut buf, len)?;
+            Ok(buf)
+        })
+        .await
+        .map_err(|e| {
+            Error::new(
+                e.kind(),
+                format!("Read file failed, file: {:?}, pos: {}, len: {}, reason: {:?}", self.path(), pos, len, e),
+            )
+        })?;
+        self.0.count_read(bytes.len());
+        Ok(bytes)
+    }
+}
+
+// 从指定位置开始读指定字节追加到缓冲区的剩余容量，读到文件尾则提前返回，unix平台直接读入未初始化的容量
+fn read_spare_at(file: &File, pos: u64, buf: &mut Vec<u8>, len: usize) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::convert::TryFrom;
+        use std::os::unix::io::AsRawFd;
+
+        buf.reserve(len);
+        let end = buf.len() + len;
+        while buf.len() < end {
+            let rest = end - buf.len();
+            let spare = &mut buf.spare_capacity_mut()[..rest];
+            let at = pos + (len - rest) as u64;
+            let at = libc::off_t::try_from(at).map_err(|_| Error::new(ErrorKind::InvalidInput, "pos overflow"))?;
+            let r = unsafe { libc::pread(file.as_raw_fd(), spare.as_mut_ptr() as *mut libc::c_void, spare.len(), at) };
+            if r < 0 {
+                let e = Error::last_os_error();
+                if e.kind() == ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            if r == 0 {
+                break;
+            }
+            // 读入的部分已被初始化
+            unsafe { buf.set_len(buf.len() + r as usize) };
+        }
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        let start = buf.len();
+        buf.resize(start + len, 0);
+        let r = read_full_at(file, pos, &mut buf[start..])?;
+        buf.truncate(start + r);
+        Ok(())
+    }
+}
+
+// 从指定位置开始读满缓冲区，读到文件尾则提前返回，返回读取的字节数
+#[cfg(windows)]
+fn read_full_at(file: &File, pos: u64, buf: &mut [u8]) -> Result<usize> {
+    use std::os::windows::fs::FileExt;
+
+    let mut readed = 0;
+    while readed < buf.len() {
+        match file.seek_read(&mut buf[readed..], pos + readed as u64) {
+            Ok(0) => break,
+            Ok(len) => readed += len,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(readed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+    use std::io::Error;
+
+    #[test]
+    fn size_classes() {
+        assert_eq!(size_class(0), Some(0));
+        assert_eq!(size_class(4096), Some(0));
+        assert_eq!(size_class(4097), Some(1));
+        assert_eq!(size_class(1 << MAX_CLASS_SHIFT), Some((MAX_CLASS_SHIFT - MIN_CLASS_SHIFT) as usize));
+        assert_eq!(size_class((1 << MAX_CLASS_SHIFT) + 1), None);
+    }
+
+    #[test]
+    fn dropped_buffer_is_reused() {
+        let len = 3 << 19;
+        let bytes = PooledBytes::take(len);
+        // 借出的是空缓冲区，只保证容量
+        assert!(bytes.is_empty() && bytes.buf.capacity() >= 1 << 21);
+        let ptr = bytes.buf.as_ptr();
+        drop(bytes);
+        assert_eq!(PooledBytes::take(len).buf.as_ptr(), ptr);
+        // 取出的缓冲区不再归还
+        let vec = PooledBytes::take(len).into_vec();
+        assert_ne!(PooledBytes::take(len).buf.as_ptr(), vec.as_ptr());
+    }
+
+    #[test]
+    fn recycled_buffers_read_fresh_contents() {
+        let path = test_path("pool_read");
+        fs::write(&path, (0..20_000u32).map(|i| (i % 251) as u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let mut reads = Vec::new();
+            // 每次读取后释放，下一次读取复用同一级别的缓冲区
+            for &(pos, len) in [(0, 9000), (9000, 9000), (19_990, 9000), (30_000, 9000)].iter() {
+                reads.push(file.read_pooled(pos, len).await?.to_vec());
+            }
+            Ok::<_, Error>(reads)
+        })
+        .unwrap();
+        let expect = |pos: u32, len: u32| (pos..(pos + len).min(20_000)).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        assert_eq!(r[0], expect(0, 9000));
+        assert_eq!(r[1], expect(9000, 9000));
+        assert_eq!(r[2], expect(19_990, 9000));
+        assert!(r[3].is_empty());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn read_spare_at_appends_until_eof() {
+        let path = test_path("pool_spare");
+        fs::write(&path, b"0123456789").unwrap();
+        let file = File::open(&path).unwrap();
+        let mut buf = b"ab".to_vec();
+        read_spare_at(&file, 4, &mut buf, 3).unwrap();
+        assert_eq!(buf, b"ab456");
+        // 读到文件尾则提前返回
+        read_spare_at(&file, 8, &mut buf, 16).unwrap();
+        assert_eq!(buf, b"ab45689");
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::stream::{self, Stream};
+use pi_async_file::file::AsyncFileOptions;
+
+use crate::{crc32, SafeFile};
+
+// 记录头的字节数，依次为小端的负载长度和负载的CRC32
+const HEADER_SIZE: usize = 8;
+
+/*
+* 只追加的记录日志，每条记录由记录头和负载组成，记录头包括负载长度和负载的CRC32
+* 打开时会截掉文件尾不完整或校验失败的最后一条记录，以便从写入中断中恢复
+*/
+#[derive(Debug, Clone)]
+pub struct RecordLog {
+    file: SafeFile,
+}
+
+impl RecordLog {
+    // 以可读可追加方式打开指定路径的记录日志，不存在则创建
+    pub async fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let file = SafeFile::open(path, AsyncFileOptions::ReadAppend).await?;
+        let log = RecordLog { file };
+        log.recover().await?;
+        Ok(log)
+    }
+
+    // 获取日志文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 追加一条记录，返回记录在文件中的位置
+    pub async fn append(&self, record: &[u8]) -> Result<u64> {
+        let len = u32::try_from(record.len()).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Append record failed, file: {:?}, len: {}, reason: record too large", self.file.path(), record.len()),
+            )
+        })?;
+        let mut frame = Vec::with_capacity(HEADER_SIZE + record.len());
+        frame.extend_from_slice(&len.to_le_bytes());
+        frame.extend_from_slice(&crc32(record).to_le_bytes());
+        frame.extend_from_slice(record);
+        let (pos, _) = self.file.append_at(Arc::from(frame)).await?;
+        Ok(pos)
+    }
+
+    // 将已追加的记录同步到磁盘
+    pub async fn sync(&self) -> Result<()> {
+        self.file.sync_data().await
+    }
+
+    // 从文件头开始依次读取所有记录的位置和负载，读到文件尾或不完整的最后一条记录时结束
+    // 校验失败的完整记录返回InvalidData错误后结束
+    pub fn iter(&self) -> impl Stream<Item = Result<(u64, Vec<u8>)>> {
+        let file = self.file.clone();
+        stream::unfold(Some(0u64), move |state| {
+            let file = file.clone();
+            async move {
+                let pos = state?;
+                let record = match file.len().await {
+                    Ok(size) => read_record(&file, pos, size).await,
+                    Err(e) => Err(e),
+                };
+                match record {
+                    Ok(Record::Valid(payload)) => {
+                        let next = pos + (HEADER_SIZE + payload.len()) as u64;
+                        Some((Ok((pos, payload)), Some(next)))
+                    }
+                    Ok(Record::End) | Ok(Record::Torn) => None,
+                    Ok(Record::Corrupt) => Some((
+                        Err(Error::new(
+                            ErrorKind::InvalidData,
+                            format!("Read record failed, file: {:?}, pos: {}, reason: crc mismatch", file.path(), pos),
+                        )),
+                        None,
+                    )),
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        })
+    }
+
+    // 找到最后一条有效记录的结尾，文件尾有不完整或校验失败的最后一条记录时截掉
+    async fn recover(&self) -> Result<()> {
+        let size = self.file.len().await?;
+        let mut pos = 0;
+        loop {
+            match read_record(&self.file, pos, size).await? {
+                Record::Valid(payload) => pos += (HEADER_SIZE + payload.len()) as u64,
+                Record::End => return Ok(()),
+                Record::Torn => break,
+                Record::Corrupt => {
+                    let len = self.file.read(pos, 4).await?;
+                    let end = pos + HEADER_SIZE as u64 + u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as u64;
+                    if end < size {
+                        // 校验失败的记录不是最后一条，不是写入中断造成的，保留给读取者处理
+                        return Ok(());
+                    }
+                    break;
+                }
+            }
+        }
+        self.file.set_len(pos).await
+    }
+}
+
+// 读取的记录
+enum Record {
+    Valid(Vec<u8>), //有效的记录的负载
+    End,            //已到文件尾
+    Torn,           //不完整的记录
+    Corrupt,        //校验失败的记录
+}
+
+// 读取指定位置的记录，记录头声明的长度超出文件长度时视为不完整的记录，不按声明的长度读取
+async fn read_record(file: &SafeFile, pos: u64, size: u64) -> Result<Record> {
+    let header = file.read(pos, HEADER_SIZE).await?;
+    if header.is_empty() {
+        return Ok(Record::End);
+    }
+    if header.len() < HEADER_SIZE {
+        return Ok(Record::Torn);
+    }
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if pos + (HEADER_SIZE + len) as u64 > size {
+        return Ok(Record::Torn);
+    }
+    let payload = file.read(pos + HEADER_SIZE as u64, len).await?;
+    if payload.len() < len {
+        return Ok(Record::Torn);
+    }
+    if crc32(&payload) != crc {
+        return Ok(Record::Corrupt);
+    }
+    Ok(Record::Valid(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use futures::StreamExt;
+    use std::fs;
+
+    // 打开日志并读取所有记录
+    fn records(path: &Path) -> Vec<Result<(u64, Vec<u8>)>> {
+        let path = path.to_path_buf();
+        block_on(async move {
+            let log = RecordLog::open(path).await?;
+            Ok::<_, Error>(log.iter().collect::<Vec<_>>().await)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn torn_tail_is_truncated_on_open() {
+        let path = test_path("record_log.torn");
+        let copy = path.clone();
+        let pos = block_on(async move {
+            let log = RecordLog::open(copy).await?;
+            let first = log.append(b"one").await?;
+            let second = log.append(b"two").await?;
+            log.sync().await?;
+            Ok::<_, Error>((first, second))
+        })
+        .unwrap();
+        assert_eq!(pos, (0, (HEADER_SIZE + 3) as u64));
+        // 模拟写入中断，最后一条记录不完整
+        let size = fs::metadata(&path).unwrap().len();
+        fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(size - 1).unwrap();
+        let r = records(&path);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].as_ref().unwrap(), &(0, b"one".to_vec()));
+        assert_eq!(fs::metadata(&path).unwrap().len(), pos.1);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn corrupt_record_is_reported() {
+        let path = test_path("record_log.corrupt");
+        let copy = path.clone();
+        block_on(async move {
+            let log = RecordLog::open(copy).await?;
+            log.append(b"one").await?;
+            log.append(b"two").await?;
+            log.sync().await
+        })
+        .unwrap();
+        let mut data = fs::read(&path).unwrap();
+        data[HEADER_SIZE] ^= 1;
+        fs::write(&path, &data).unwrap();
+        // 校验失败的记录不是最后一条，打开时保留
+        let r = records(&path);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].as_ref().unwrap_err().kind(), ErrorKind::InvalidData);
+        assert_eq!(fs::read(&path).unwrap(), data);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn records_survive_reopen() {
+        let path = test_path("record_log.reopen");
+        let copy = path.clone();
+        block_on(async move {
+            let log = RecordLog::open(copy).await?;
+            log.append(b"one").await?;
+            log.append(b"").await?;
+            log.append(b"three").await?;
+            log.sync().await
+        })
+        .unwrap();
+        let r: Vec<_> = records(&path).into_iter().map(Result::unwrap).collect();
+        let second = (HEADER_SIZE + 3) as u64;
+        assert_eq!(
+            r,
+            vec![(0, b"one".to_vec()), (second, Vec::new()), (second + HEADER_SIZE as u64, b"three".to_vec())]
+        );
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn overlong_length_is_torn() {
+        let path = test_path("record_log.overlong");
+        let copy = path.clone();
+        block_on(async move {
+            let log = RecordLog::open(copy).await?;
+            log.append(b"one").await?;
+            log.sync().await
+        })
+        .unwrap();
+        let valid = fs::metadata(&path).unwrap().len();
+        // 记录头声明的长度远超文件长度，按不完整的记录截掉，不按声明的长度分配缓冲区
+        let mut data = fs::read(&path).unwrap();
+        data.extend_from_slice(&u32::MAX.to_le_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(b"tail");
+        fs::write(&path, &data).unwrap();
+        let r = records(&path);
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].as_ref().unwrap(), &(0, b"one".to_vec()));
+        assert_eq!(fs::metadata(&path).unwrap().len(), valid);
+        let _ = fs::remove_file(path);
+    }
+}
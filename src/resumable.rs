@@ -0,0 +1,161 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_lock::Mutex;
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+
+use crate::{atomic_write, remove_file, run_sync, SafeFile};
+
+/*
+* 可断点续写的分块写文件，按顺序写入分块，已连续写入的最大位置落地后记录在同目录的续写文件中
+* 重新打开时从续写文件恢复续写位置，写完后调用finish移除续写文件
+*/
+#[derive(Debug)]
+pub struct ResumableWriter {
+    file: SafeFile,        //写入的文件
+    resume_path: PathBuf,  //记录续写位置的文件的路径
+    next: AtomicU64,       //已连续写入的最大位置，即下一个分块的位置
+    write_lock: Mutex<()>, //分块写入互斥
+}
+
+impl ResumableWriter {
+    // 以读写方式打开指定路径的文件，并从续写文件恢复续写位置，没有续写文件则从头开始写
+    pub async fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let resume_path = resume_path(&path);
+        let read_path = resume_path.clone();
+        let record = run_sync(move || match fs::read(read_path) {
+            Ok(r) => Ok(Some(r)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        })
+        .await?;
+        let next = match record {
+            None => 0,
+            Some(r) => match <[u8; 8]>::try_from(r.as_slice()) {
+                Ok(bytes) => u64::from_le_bytes(bytes),
+                Err(_) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Open resumable file failed, file: {:?}, reason: invalid resume record", resume_path),
+                    ))
+                }
+            },
+        };
+        let file = SafeFile::open(path, AsyncFileOptions::ReadWrite).await?;
+        // 记录的位置总是在数据落地后写入，文件比记录短时只能从文件尾续写
+        let next = next.min(file.len().await?);
+        Ok(ResumableWriter {
+            file,
+            resume_path,
+            next: AtomicU64::new(next),
+            write_lock: Mutex::new(()),
+        })
+    }
+
+    // 获取写入的文件
+    pub fn file(&self) -> &SafeFile {
+        &self.file
+    }
+
+    // 获取下一个分块的位置
+    pub fn next_offset(&self) -> u64 {
+        self.next.load(Ordering::Acquire)
+    }
+
+    // 在指定位置写入分块，落地后记录续写位置，返回下一个分块的位置
+    // 位置超过下一个分块的位置则返回InvalidInput错误，小于则覆盖已写入的部分
+    pub async fn write_chunk(&self, offset: u64, data: Arc<[u8]>) -> Result<u64> {
+        let _guard = self.write_lock.lock().await;
+        let next = self.next.load(Ordering::Acquire);
+        if offset > next {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "Write chunk failed, file: {:?}, offset: {}, next: {}, reason: gap before chunk",
+                    self.file.path(),
+                    offset,
+                    next
+                ),
+            ));
+        }
+        let r = self.file.write(offset, data, WriteOptions::Sync(false)).await?;
+        let end = offset + r as u64;
+        if end > next {
+            atomic_write(self.resume_path.clone(), Arc::from(end.to_le_bytes())).await?;
+            self.next.store(end, Ordering::Release);
+        }
+        Ok(self.next.load(Ordering::Acquire))
+    }
+
+    // 完成写入，落地文件并移除续写文件，返回写入的文件
+    pub async fn finish(self) -> Result<SafeFile> {
+        self.file.sync_all().await?;
+        match remove_file(self.resume_path).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+            _ => Ok(self.file),
+        }
+    }
+}
+
+// 获取指定文件的续写文件的路径
+fn resume_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".resume");
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+
+    #[test]
+    fn reopen_resumes_after_last_chunk() {
+        let path = test_path("resumable");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let writer = ResumableWriter::open(copy.clone()).await?;
+            let next = writer.write_chunk(0, Arc::from(&b"abc"[..])).await?;
+            let gap = writer.write_chunk(next + 1, Arc::from(&b"x"[..])).await.map_err(|e| e.kind());
+            drop(writer);
+            let writer = ResumableWriter::open(copy).await?;
+            let resumed = writer.next_offset();
+            writer.write_chunk(resumed, Arc::from(&b"def"[..])).await?;
+            let file = writer.finish().await?;
+            Ok::<_, Error>((next, gap, resumed, file.read(0, 100).await?))
+        })
+        .unwrap();
+        assert_eq!(r.0, 3);
+        assert_eq!(r.1, Err(ErrorKind::InvalidInput));
+        assert_eq!(r.2, 3);
+        assert_eq!(r.3, b"abcdef");
+        assert!(!resume_path(&path).exists());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn resume_point_never_passes_file_end() {
+        let path = test_path("resumable.short");
+        fs::write(&path, b"abcd").unwrap();
+        // 续写文件记录的位置超过文件长度
+        fs::write(resume_path(&path), 10u64.to_le_bytes()).unwrap();
+        let copy = path.clone();
+        let next = block_on(async move { ResumableWriter::open(copy).await.map(|w| w.next_offset()) }).unwrap();
+        assert_eq!(next, 4);
+        // 无效的续写记录
+        fs::write(resume_path(&path), b"bad").unwrap();
+        let copy = path.clone();
+        let e = block_on(async move { ResumableWriter::open(copy).await.map(|_| ()) }).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+        let _ = fs::remove_file(resume_path(&path));
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use async_lock::Mutex;
+use pi_async_file::file::AsyncFileOptions;
+
+use crate::{force_evict, remove_file, rename, SafeFile};
+
+/*
+* 按大小滚动的追加写文件，当前文件超过指定大小后重命名为带序号后缀的文件并打开新文件
+* 序号越大越旧，最多保留指定数量的滚动文件，更旧的文件会被移除
+*/
+#[derive(Debug)]
+pub struct RotatingWriter {
+    path: PathBuf,         //当前文件的路径
+    max_size: u64,         //当前文件的最大字节数，写入后超过则滚动
+    max_files: usize,      //最多保留的滚动文件数，为0则滚动时直接移除当前文件
+    file: Mutex<SafeFile>, //当前文件
+}
+
+impl RotatingWriter {
+    // 以追加方式打开指定路径的当前文件，同一路径与其它打开的句柄共享
+    pub async fn open<P>(path: P, max_size: u64, max_files: usize) -> Result<Self>
+    where
+        P: AsRef<Path> + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let file = SafeFile::open(path.clone(), AsyncFileOptions::OnlyAppend).await?;
+        Ok(RotatingWriter {
+            path,
+            max_size,
+            max_files,
+            file: Mutex::new(file),
+        })
+    }
+
+    // 获取当前文件的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 获取指定序号的滚动文件的路径
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+        name.push(format!(".{}", index));
+        self.path.with_file_name(name)
+    }
+
+    // 追加写入一条记录，写入后当前文件超过最大字节数则滚动，返回写入的字节数
+    pub async fn write(&self, record: Arc<[u8]>) -> Result<usize> {
+        let mut file = self.file.lock().await;
+        let r = file.append(record).await?;
+        if file.len().await? >= self.max_size {
+            self.rotate(&mut file).await?;
+        }
+        Ok(r)
+    }
+
+    // 滚动当前文件，依次后移已有的滚动文件，移除超出保留数量的文件，再打开新的当前文件
+    async fn rotate(&self, file: &mut SafeFile) -> Result<()> {
+        if self.max_files == 0 {
+            ignore_not_found(remove_file(self.path.clone()).await)?;
+        } else {
+            ignore_not_found(remove_file(self.rotated_path(self.max_files)).await)?;
+            for index in (1..self.max_files).rev() {
+                ignore_not_found(rename(self.rotated_path(index), self.rotated_path(index + 1)).await)?;
+            }
+            rename(self.path.clone(), self.rotated_path(1)).await?;
+        }
+        // 已打开的句柄仍指向被滚动的文件，之后打开当前路径需要创建新的句柄
+        force_evict(&self.path).await;
+        *file = SafeFile::open(self.path.clone(), AsyncFileOptions::OnlyAppend).await?;
+        Ok(())
+    }
+}
+
+// 忽略文件不存在的错误
+fn ignore_not_found(r: Result<()>) -> Result<()> {
+    match r {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        r => r,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use std::fs;
+
+    #[test]
+    fn rotates_and_keeps_max_files() {
+        let path = test_path("rotating");
+        let copy = path.clone();
+        let rotated = block_on(async move {
+            let writer = RotatingWriter::open(copy, 4, 2).await?;
+            for record in ["aaaa", "bbbb", "cccc", "dd"].iter() {
+                writer.write(Arc::from(record.as_bytes())).await?;
+            }
+            Ok::<_, std::io::Error>((writer.rotated_path(1), writer.rotated_path(2), writer.rotated_path(3)))
+        })
+        .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"dd");
+        assert_eq!(fs::read(&rotated.0).unwrap(), b"cccc");
+        assert_eq!(fs::read(&rotated.1).unwrap(), b"bbbb");
+        assert!(!rotated.2.exists());
+        for path in [path, rotated.0, rotated.1].iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn active_file_is_shared_after_rotation() {
+        let path = test_path("rotating_shared");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let writer = RotatingWriter::open(copy.clone(), 4, 0).await?;
+            writer.write(Arc::from(&b"full"[..])).await?;
+            writer.write(Arc::from(&b"ab"[..])).await?;
+            // 滚动后打开当前路径得到与写入器相同的新句柄
+            let (_file, created) = SafeFile::open_tracked(copy, AsyncFileOptions::OnlyAppend).await?;
+            Ok::<_, std::io::Error>((created, writer.rotated_path(1)))
+        })
+        .unwrap();
+        assert!(!r.0);
+        assert_eq!(fs::read(&path).unwrap(), b"ab");
+        // 不保留滚动文件
+        assert!(!r.1.exists());
+        let _ = fs::remove_file(path);
+    }
+}
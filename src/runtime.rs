@@ -0,0 +1,517 @@
+use std::cell::Cell;
+use std::env;
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll, Wake, Waker};
+
+use async_lock::Semaphore;
+use event_listener::Event;
+use pi_async_rt::lock::spin_lock::SpinLock;
+
+use pi_async_rt::rt::multi_thread::{MultiTaskRuntime, MultiTaskRuntimeBuilder, StealableTaskPool};
+use pi_async_rt::rt::AsyncRuntime;
+
+use crate::FILE_RUNTIME;
+
+// 声明异步文件线程数的环境变量
+const THREADS_ENV: &str = "PI_RT_FILE_THREADS";
+// 旧版本声明异步文件线程数的环境变量，兼容保留
+const LEGACY_THREADS_ENV: &str = "_ver";
+
+// 初始化时指定的运行时配置
+static RUNTIME_CONFIG: OnceLock<RuntimeConfig> = OnceLock::new();
+// 调用者注入的运行时
+static INJECTED_RUNTIME: OnceLock<MultiTaskRuntime<()>> = OnceLock::new();
+// 运行时是否已经构建
+static RUNTIME_BUILT: AtomicBool = AtomicBool::new(false);
+// 运行时是否已经关闭
+static RUNTIME_SHUTDOWN: AtomicBool = AtomicBool::new(false);
+// 进行中的文件操作数
+static ACTIVE_OPS: AtomicUsize = AtomicUsize::new(0);
+// 进行中的文件操作全部完成时的通知
+static OPS_DRAINED: Event = Event::new();
+// 同时打开的安全文件数的许可，未指定上限则为None
+static OPEN_PERMITS: OnceLock<Option<Arc<Semaphore>>> = OnceLock::new();
+
+/*
+* 异步文件运行时的配置
+*/
+#[derive(Debug, Clone)]
+pub struct RuntimeConfig {
+    thread_count: Option<usize>, //线程数，未指定则取cpu核数
+    stack_size: usize,           //每个线程的栈空间
+    timeout: u64,                //线程休眠时间，单位ms
+    timer_interval: usize,       //定时器间隔，单位ms
+    retry_count: usize,          //读写遇到暂时性错误时的最大重试次数
+    retry_backoff: u64,          //首次重试前的等待时间，单位ms，之后每次加倍
+    retry_writes: bool,          //写遇到超时、阻塞或资源忙时是否重试，默认只在被中断时重试写
+    max_open_files: Option<usize>, //同时打开的安全文件的最大数量，达到上限时打开会等待已打开的文件关闭，未指定则不限制
+}
+
+impl Default for RuntimeConfig {
+    fn default() -> Self {
+        // 线程池：每个线程1M的栈空间，10ms 休眠，10毫秒的定时器间隔
+        RuntimeConfig {
+            thread_count: None,
+            stack_size: 1024 * 1024,
+            timeout: 10,
+            timer_interval: 10,
+            retry_count: 3,
+            retry_backoff: 2,
+            retry_writes: false,
+            max_open_files: None,
+        }
+    }
+}
+
+impl RuntimeConfig {
+    // 构建默认配置
+    pub fn new() -> Self {
+        RuntimeConfig::default()
+    }
+
+    // 设置线程数
+    pub fn thread_count(mut self, count: usize) -> Self {
+        self.thread_count = Some(count);
+        self
+    }
+
+    // 设置每个线程的栈空间
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = size;
+        self
+    }
+
+    // 设置线程休眠时间，单位ms
+    pub fn timeout(mut self, timeout: u64) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    // 设置定时器间隔，单位ms
+    pub fn timer_interval(mut self, interval: usize) -> Self {
+        self.timer_interval = interval;
+        self
+    }
+
+    // 设置读写遇到暂时性错误时的最大重试次数和首次重试前的等待时间，单位ms，重试次数为0则不重试
+    // 读遇到中断、超时、阻塞或资源忙时重试，写默认只在被中断时重试
+    pub fn retry(mut self, count: usize, backoff: u64) -> Self {
+        self.retry_count = count;
+        self.retry_backoff = backoff;
+        self
+    }
+
+    // 设置写遇到超时、阻塞或资源忙时是否重试，此时写可能已部分或全部生效，只有写入幂等时才应开启
+    pub fn retry_writes(mut self, enable: bool) -> Self {
+        self.retry_writes = enable;
+        self
+    }
+
+    // 设置同时打开的安全文件的最大数量，应小于进程的文件描述符上限
+    // 达到上限时先释放只被本库保留的文件，没有可释放的文件则等待，等待期间定时重新检查，需要启动全局定时器
+    pub fn max_open_files(mut self, limit: usize) -> Self {
+        self.max_open_files = Some(limit);
+        self
+    }
+
+    // 从环境变量读取线程数，未声明则使用默认配置，声明的值无效则返回错误
+    pub fn from_env() -> Result<Self> {
+        let var = env::var(THREADS_ENV).or_else(|_| env::var(LEGACY_THREADS_ENV));
+        match var {
+            Err(_) => Ok(RuntimeConfig::default()),
+            Ok(r) => match r.trim().parse::<usize>() {
+                Ok(count) if count > 0 => Ok(RuntimeConfig::default().thread_count(count)),
+                _ => Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Parse runtime config failed, {}: {:?}, reason: invalid thread count", THREADS_ENV, r),
+                )),
+            },
+        }
+    }
+
+    // 构建运行时
+    fn build(&self) -> MultiTaskRuntime<()> {
+        let count = self.thread_count.unwrap_or_else(num_cpus::get);
+        let pool = StealableTaskPool::with(count, 100000, [1, 1], 3000);
+        MultiTaskRuntimeBuilder::new(pool)
+            .thread_prefix("File-Runtime")
+            .thread_stack_size(self.stack_size)
+            .init_worker_size(count)
+            .set_worker_limit(count, count)
+            .set_timeout(self.timeout)
+            .set_timer_interval(self.timer_interval)
+            .build()
+    }
+}
+
+/*
+* 在首次使用文件运行时之前指定运行时配置，运行时已构建或已指定配置则返回错误
+*/
+pub fn init_runtime(config: RuntimeConfig) -> Result<()> {
+    if config.thread_count == Some(0) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Init file runtime failed, reason: thread count is zero",
+        ));
+    }
+    if config.max_open_files == Some(0) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "Init file runtime failed, reason: max open files is zero",
+        ));
+    }
+    if RUNTIME_BUILT.load(Ordering::SeqCst) {
+        return Err(runtime_started());
+    }
+    if RUNTIME_CONFIG.set(config).is_err() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "Init file runtime failed, reason: already initialized",
+        ));
+    }
+    if RUNTIME_BUILT.load(Ordering::SeqCst) {
+        // 设置配置期间运行时已构建，本次配置不会生效
+        return Err(runtime_started());
+    }
+    Ok(())
+}
+
+/*
+* 在首次使用文件运行时之前指定调用者已有的运行时，之后所有文件操作都在该运行时上执行，优先于运行时配置
+*/
+pub fn set_file_runtime(rt: MultiTaskRuntime<()>) -> Result<()> {
+    if RUNTIME_BUILT.load(Ordering::SeqCst) {
+        return Err(runtime_started());
+    }
+    if INJECTED_RUNTIME.set(rt).is_err() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            "Set file runtime failed, reason: already set",
+        ));
+    }
+    if RUNTIME_BUILT.load(Ordering::SeqCst) {
+        // 设置期间运行时已构建，本次设置不会生效
+        return Err(runtime_started());
+    }
+    Ok(())
+}
+
+// 构建文件运行时，优先使用注入的运行时，其次是初始化时指定的配置，再次是环境变量，环境变量无效则记录警告并使用默认配置
+pub(crate) fn build_runtime() -> MultiTaskRuntime<()> {
+    RUNTIME_BUILT.store(true, Ordering::SeqCst);
+    if let Some(rt) = INJECTED_RUNTIME.get() {
+        return rt.clone();
+    }
+    match RUNTIME_CONFIG.get() {
+        Some(config) => config.build(),
+        None => RuntimeConfig::from_env()
+            .unwrap_or_else(|e| {
+                log::warn!("Build file runtime failed, reason: {:?}, fallback: default config", e);
+                RuntimeConfig::default()
+            })
+            .build(),
+    }
+}
+
+fn runtime_started() -> Error {
+    Error::new(
+        ErrorKind::AlreadyExists,
+        "Init file runtime failed, reason: runtime already started",
+    )
+}
+
+// 是否是可以重试的暂时性错误
+fn is_transient(e: &Error) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::ResourceBusy | ErrorKind::TimedOut
+    )
+}
+
+// 是否是被中断的错误，被中断的操作没有生效，总是可以重试
+fn is_interrupted(e: &Error) -> bool {
+    e.kind() == ErrorKind::Interrupted
+}
+
+// 执行异步文件读，遇到暂时性错误时按运行时配置退避重试，其它错误立即返回
+pub(crate) async fn retry<F, Fut, V>(f: F) -> Result<V>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<V>>,
+{
+    retry_if(f, is_transient).await
+}
+
+// 执行异步文件写，默认只在被中断时重试，运行时配置允许重试写时与读一样遇到暂时性错误时退避重试
+pub(crate) async fn retry_write<F, Fut, V>(f: F) -> Result<V>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<V>>,
+{
+    let all = RUNTIME_CONFIG.get().map(|config| config.retry_writes).unwrap_or(false);
+    retry_if(f, if all { is_transient } else { is_interrupted }).await
+}
+
+// 执行异步文件操作，遇到指定的错误时按运行时配置退避重试，其它错误立即返回
+async fn retry_if<F, Fut, V>(mut f: F, retryable: fn(&Error) -> bool) -> Result<V>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<V>>,
+{
+    let (count, mut backoff) = match RUNTIME_CONFIG.get() {
+        Some(config) => (config.retry_count, config.retry_backoff),
+        None => {
+            let config = RuntimeConfig::default();
+            (config.retry_count, config.retry_backoff)
+        }
+    };
+    let mut retried = 0;
+    loop {
+        match guard(f()).await {
+            Err(e) if retried < count && retryable(&e) => {
+                retried += 1;
+                if backoff > 0 {
+                    FILE_RUNTIME.timeout(backoff as usize).await;
+                }
+                backoff = backoff.saturating_mul(2);
+            }
+            r => return r,
+        }
+    }
+}
+
+/*
+* 只在被底层文件操作自己唤醒后才轮询的文件操作
+* 底层文件操作每次被轮询都会重新派发任务，所在任务被其它事件唤醒时重复轮询会重复执行读写或重命名
+*/
+pub(crate) struct Guarded<F> {
+    future: Pin<Box<F>>,
+    state: Option<Arc<GuardWaker>>, //首次轮询后创建，用于轮询底层文件操作
+}
+
+// 转发底层文件操作唤醒的唤醒器
+struct GuardWaker {
+    woken: AtomicBool,              //底层文件操作是否已唤醒
+    waker: SpinLock<Option<Waker>>, //所在任务最近一次轮询时的唤醒器
+}
+
+impl Wake for GuardWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().clone() {
+            waker.wake();
+        }
+    }
+}
+
+impl<F: Future> Future for Guarded<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        let state = this.state.get_or_insert_with(|| {
+            Arc::new(GuardWaker {
+                woken: AtomicBool::new(true),
+                waker: SpinLock::new(None),
+            })
+        });
+        // 先更新唤醒器再检查唤醒标记，避免丢失两者之间的唤醒
+        *state.waker.lock() = Some(cx.waker().clone());
+        if !state.woken.swap(false, Ordering::AcqRel) {
+            return Poll::Pending;
+        }
+        let waker = Waker::from(state.clone());
+        this.future.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+}
+
+// 保护底层文件操作不被重复轮询
+pub(crate) fn guard<F: Future>(future: F) -> Guarded<F> {
+    Guarded {
+        future: Box::pin(future),
+        state: None,
+    }
+}
+
+// 获取打开安全文件的许可，许可在文件关闭时释放，未指定上限则返回None
+pub(crate) fn open_permits() -> Option<&'static Arc<Semaphore>> {
+    OPEN_PERMITS
+        .get_or_init(|| {
+            RUNTIME_CONFIG
+                .get()
+                .and_then(|config| config.max_open_files)
+                .map(|limit| Arc::new(Semaphore::new(limit)))
+        })
+        .as_ref()
+}
+
+/*
+* 进行中的文件操作，释放时减少进行中的文件操作数
+*/
+pub(crate) struct OpGuard;
+
+impl Drop for OpGuard {
+    fn drop(&mut self) {
+        if ACTIVE_OPS.fetch_sub(1, Ordering::SeqCst) == 1 {
+            OPS_DRAINED.notify(usize::MAX);
+        }
+    }
+}
+
+thread_local! {
+    // 当前线程在文件操作中执行调用者回调的层数
+    static OP_CALLBACKS: Cell<usize> = const { Cell::new(0) };
+}
+
+// 回调期间的标记，回调返回或panic时撤销
+struct CallbackGuard;
+
+impl Drop for CallbackGuard {
+    fn drop(&mut self) {
+        OP_CALLBACKS.with(|n| n.set(n.get() - 1));
+    }
+}
+
+// 在进行中的文件操作里执行调用者的回调，回调中关闭运行时会等待本操作完成而永远无法返回，因此会被拒绝
+pub(crate) fn callback<R>(f: impl FnOnce() -> R) -> R {
+    OP_CALLBACKS.with(|n| n.set(n.get() + 1));
+    let _guard = CallbackGuard;
+    f()
+}
+
+// 当前线程是否正在文件操作中执行调用者的回调
+pub(crate) fn in_callback() -> bool {
+    OP_CALLBACKS.with(|n| n.get() > 0)
+}
+
+// 开始一个文件操作，运行时已关闭则返回错误
+pub(crate) fn enter() -> Result<OpGuard> {
+    // 先增加计数再检查关闭标记，保证关闭时等待的操作不会遗漏
+    ACTIVE_OPS.fetch_add(1, Ordering::SeqCst);
+    let guard = OpGuard;
+    if RUNTIME_SHUTDOWN.load(Ordering::SeqCst) {
+        return Err(Error::other("File runtime failed, reason: runtime shut down"));
+    }
+    Ok(guard)
+}
+
+// 标记运行时已关闭，之后开始的文件操作都会返回错误，返回本次是否关闭
+pub(crate) fn mark_shutdown() -> bool {
+    !RUNTIME_SHUTDOWN.swap(true, Ordering::SeqCst)
+}
+
+// 获取进行中的文件操作数
+pub(crate) fn active_ops() -> usize {
+    ACTIVE_OPS.load(Ordering::SeqCst)
+}
+
+// 等待进行中的文件操作全部完成
+pub(crate) async fn drain() {
+    while active_ops() > 0 {
+        let listener = OPS_DRAINED.listen();
+        // 注册监听后再次检查，避免错过注册前最后一个操作完成的通知
+        if active_ops() == 0 {
+            break;
+        }
+        listener.await;
+    }
+}
+
+/*
+* 文件运行时的运行状态
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RuntimeStats {
+    pub worker_count: usize,  //工作者数量
+    pub pending_tasks: usize, //任务池中待处理的任务数量
+    pub active_ops: usize,    //进行中的文件操作数
+}
+
+/*
+* 获取文件运行时的运行状态，用于诊断IO积压
+*/
+pub fn runtime_stats() -> RuntimeStats {
+    RuntimeStats {
+        worker_count: FILE_RUNTIME.worker_len(),
+        pending_tasks: FILE_RUNTIME.len(),
+        active_ops: active_ops(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::block_on;
+    use std::future;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    // 模拟的后端，前fails次返回指定错误，之后成功，返回成功的结果和调用次数
+    fn flaky(fails: usize, kind: ErrorKind) -> (Result<usize>, usize) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let copy = calls.clone();
+        let r = block_on(async move {
+            retry(|| {
+                let n = copy.fetch_add(1, Ordering::SeqCst);
+                future::ready(if n < fails { Err(Error::from(kind)) } else { Ok(n) })
+            })
+            .await
+        });
+        (r, calls.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let (r, calls) = flaky(2, ErrorKind::Interrupted);
+        assert_eq!(r.ok(), Some(2));
+        assert_eq!(calls, 3);
+        let (r, calls) = flaky(3, ErrorKind::WouldBlock);
+        assert_eq!(r.ok(), Some(3));
+        assert_eq!(calls, 4);
+    }
+
+    #[test]
+    fn gives_up_after_retry_count() {
+        let (r, calls) = flaky(10, ErrorKind::Interrupted);
+        assert_eq!(r.map_err(|e| e.kind()), Err(ErrorKind::Interrupted));
+        assert_eq!(calls, RuntimeConfig::default().retry_count + 1);
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        for kind in [ErrorKind::NotFound, ErrorKind::PermissionDenied].iter() {
+            let (r, calls) = flaky(1, *kind);
+            assert_eq!(r.map_err(|e| e.kind()), Err(*kind));
+            assert_eq!(calls, 1);
+        }
+    }
+
+    #[test]
+    fn writes_retry_only_interrupted_by_default() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let copy = calls.clone();
+        let r = block_on(async move {
+            let write = |kind: ErrorKind| {
+                let calls = copy.clone();
+                retry_write(move || {
+                    let n = calls.fetch_add(1, Ordering::SeqCst);
+                    future::ready(if n != 1 { Err(Error::from(kind)) } else { Ok(n) })
+                })
+            };
+            let interrupted = write(ErrorKind::Interrupted).await.map_err(|e| e.kind());
+            // 超时或阻塞的写可能已生效，默认不重试
+            let blocked = write(ErrorKind::WouldBlock).await.map_err(|e| e.kind());
+            (interrupted, blocked)
+        });
+        assert_eq!(r, (Ok(1), Err(ErrorKind::WouldBlock)));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::{Error, Result};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{run_sync, FileError};
+
+// 写入前检查可用空间的阈值，单次写入的字节数不小于阈值时才检查，为0则不检查
+static SPACE_CHECK_THRESHOLD: AtomicU64 = AtomicU64::new(0);
+
+/*
+* 设置写入前检查可用空间的阈值，单次写入的字节数不小于阈值时，先检查文件所在文件系统的可用空间，不足则返回OutOfSpace错误
+* 为0则不检查，默认为0
+*/
+pub fn set_space_check_threshold(bytes: u64) {
+    SPACE_CHECK_THRESHOLD.store(bytes, Ordering::Relaxed);
+}
+
+// 写入指定字节数前，按阈值检查指定文件所在文件系统的可用空间
+pub(crate) async fn check_space(path: &Path, len: u64) -> Result<()> {
+    let threshold = SPACE_CHECK_THRESHOLD.load(Ordering::Relaxed);
+    if threshold == 0 || len < threshold {
+        return Ok(());
+    }
+    if available_space(path.to_path_buf()).await? < len {
+        return Err(FileError::OutOfSpace {
+            path: path.to_path_buf(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/*
+* 异步获取指定路径所在文件系统中当前用户可用的字节数
+*/
+pub async fn available_space<P>(path: P) -> Result<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let path = path.as_ref().to_path_buf();
+    let copy = path.clone();
+    run_sync(move || statvfs(&copy).map(|(available, _)| available))
+        .await
+        .map_err(|e| Error::new(e.kind(), format!("Query space failed, file: {:?}, reason: {:?}", path, e)))
+}
+
+/*
+* 异步获取指定路径所在文件系统的总字节数
+*/
+pub async fn total_space<P>(path: P) -> Result<u64>
+where
+    P: AsRef<Path> + Send + 'static,
+{
+    let path = path.as_ref().to_path_buf();
+    let copy = path.clone();
+    run_sync(move || statvfs(&copy).map(|(_, total)| total))
+        .await
+        .map_err(|e| Error::new(e.kind(), format!("Query space failed, file: {:?}, reason: {:?}", path, e)))
+}
+
+// 查询指定路径所在文件系统的可用字节数和总字节数
+#[cfg(unix)]
+fn statvfs(path: &Path) -> Result<(u64, u64)> {
+    use std::ffi::CString;
+    use std::io::ErrorKind;
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| Error::new(ErrorKind::InvalidInput, "path contains a nul byte"))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(Error::last_os_error());
+    }
+    let size = stat.f_frsize as u64;
+    Ok((stat.f_bavail as u64 * size, stat.f_blocks as u64 * size))
+}
+
+// 其它平台暂不支持查询文件系统空间
+#[cfg(not(unix))]
+fn statvfs(_path: &Path) -> Result<(u64, u64)> {
+    use std::io::ErrorKind;
+
+    Err(Error::new(
+        ErrorKind::Unsupported,
+        "filesystem space query unsupported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use std::env;
+
+    #[cfg(unix)]
+    #[test]
+    fn available_space_fits_in_total() {
+        let r = block_on(async move {
+            let dir = env::temp_dir();
+            Ok::<_, Error>((available_space(dir.clone()).await?, total_space(dir).await?))
+        })
+        .unwrap();
+        assert!(r.0 > 0);
+        assert!(r.0 <= r.1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn missing_path_reports_not_found() {
+        let path = test_path("space_missing");
+        let e = block_on(async move { available_space(path).await }).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+        assert!(e.to_string().starts_with("Query space failed"));
+    }
+}
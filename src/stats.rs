@@ -0,0 +1,93 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// 累计读取的字节数
+static READ_BYTES: AtomicU64 = AtomicU64::new(0);
+// 累计写入的字节数
+static WRITTEN_BYTES: AtomicU64 = AtomicU64::new(0);
+// 累计打开的文件数
+static OPENED_FILES: AtomicU64 = AtomicU64::new(0);
+// 累计读缓存命中数
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+// 累计读缓存未命中数
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+/*
+* 全局IO统计的快照
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GlobalStats {
+    pub read_bytes: u64,    //累计读取的字节数
+    pub written_bytes: u64, //累计写入的字节数
+    pub opened_files: u64,  //累计打开的文件数，同一路径共享的句柄只计一次
+    pub cache_hits: u64,    //累计读缓存命中数
+    pub cache_misses: u64,  //累计读缓存未命中数
+}
+
+/*
+* 单个文件的读缓存统计的快照
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,   //累计读缓存命中数
+    pub misses: u64, //累计读缓存未命中数
+}
+
+impl CacheStats {
+    // 获取读缓存命中率，没有访问过缓存则为0
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/*
+* 获取全局IO统计的快照
+*/
+pub fn global_io_stats() -> GlobalStats {
+    GlobalStats {
+        read_bytes: READ_BYTES.load(Ordering::Relaxed),
+        written_bytes: WRITTEN_BYTES.load(Ordering::Relaxed),
+        opened_files: OPENED_FILES.load(Ordering::Relaxed),
+        cache_hits: CACHE_HITS.load(Ordering::Relaxed),
+        cache_misses: CACHE_MISSES.load(Ordering::Relaxed),
+    }
+}
+
+/*
+* 重置全局IO统计
+*/
+pub fn reset_global_io_stats() {
+    READ_BYTES.store(0, Ordering::Relaxed);
+    WRITTEN_BYTES.store(0, Ordering::Relaxed);
+    OPENED_FILES.store(0, Ordering::Relaxed);
+    CACHE_HITS.store(0, Ordering::Relaxed);
+    CACHE_MISSES.store(0, Ordering::Relaxed);
+}
+
+// 增加读取的字节数
+pub(crate) fn add_read(len: usize) {
+    READ_BYTES.fetch_add(len as u64, Ordering::Relaxed);
+}
+
+// 增加写入的字节数
+pub(crate) fn add_written(len: usize) {
+    WRITTEN_BYTES.fetch_add(len as u64, Ordering::Relaxed);
+}
+
+// 增加打开的文件数
+pub(crate) fn add_opened() {
+    OPENED_FILES.fetch_add(1, Ordering::Relaxed);
+}
+
+// 记录一次读缓存是否命中
+pub(crate) fn add_cache_access(hit: bool) {
+    if hit {
+        CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,111 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Result};
+use std::ops::Deref;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntime;
+
+use crate::{run_sync, SafeFile, FILE_RUNTIME, TEMP_SEQ};
+
+// 创建临时文件时名称冲突的最大重试次数
+const TEMP_RETRY: usize = 16;
+
+/*
+* 临时安全文件，释放时通过运行时移除文件
+*/
+#[derive(Debug)]
+pub struct TempSafeFile {
+    file: SafeFile,
+}
+
+impl Deref for TempSafeFile {
+    type Target = SafeFile;
+    fn deref(&self) -> &SafeFile {
+        &self.file
+    }
+}
+
+impl Drop for TempSafeFile {
+    fn drop(&mut self) {
+        let path = self.file.path().to_path_buf();
+        let r = FILE_RUNTIME.spawn(async move {
+            if let Err(e) = fs::remove_file(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    log::warn!("Remove temp file failed, file: {:?}, reason: {:?}", path, e);
+                }
+            }
+        });
+        if let Err(e) = r {
+            log::warn!("Remove temp file failed, file: {:?}, reason: {:?}", self.file.path(), e);
+        }
+    }
+}
+
+/*
+* 在系统临时目录下创建唯一命名的可读写临时文件
+*/
+pub async fn temp_file() -> Result<TempSafeFile> {
+    let path = run_sync(|| {
+        let dir = env::temp_dir();
+        let mut retry = 0;
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos())
+                .unwrap_or(0);
+            let path = dir.join(format!(
+                "pi_rt_file.{}.{}.{}",
+                process::id(),
+                TEMP_SEQ.fetch_add(1, Ordering::Relaxed),
+                nanos
+            ));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok::<PathBuf, _>(path),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists && retry < TEMP_RETRY => retry += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    })
+    .await?;
+    match SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await {
+        Ok(file) => Ok(TempSafeFile { file }),
+        Err(e) => {
+            let _ = run_sync(move || fs::remove_file(path)).await;
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::block_on;
+    use pi_async_file::file::WriteOptions;
+    use std::io::Error;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn removed_after_drop() {
+        let (path, data, existed) = block_on(async move {
+            let file = temp_file().await?;
+            file.write(0, Arc::from(&b"temp"[..]), WriteOptions::None).await?;
+            Ok::<_, Error>((file.path().to_path_buf(), file.read(0, 10).await?, file.path().exists()))
+        })
+        .unwrap();
+        assert_eq!(data, b"temp");
+        assert!(existed);
+        // 释放后在运行时上异步移除
+        let removed = (0..100).any(|_| {
+            thread::sleep(Duration::from_millis(10));
+            !path.exists()
+        });
+        assert!(removed, "temp file not removed: {:?}", path);
+    }
+}
@@ -0,0 +1,151 @@
+use std::future::Future;
+use std::io::{Error, ErrorKind, Result, SeekFrom};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncSeek, ReadBuf};
+
+use crate::{offset_pos, SafeFile};
+
+type ReadFuture = Pin<Box<dyn Future<Output = Result<Vec<u8>>> + Send>>;
+
+/*
+* 安全文件的tokio异步读适配器，内部维护读取位置，每次读取后前进
+*/
+pub struct SafeFileReader {
+    file: SafeFile,
+    pos: u64,
+    reading: Option<ReadFuture>,
+}
+
+impl SafeFileReader {
+    // 从文件头开始读取指定的安全文件
+    pub fn new(file: SafeFile) -> Self {
+        SafeFileReader {
+            file,
+            pos: 0,
+            reading: None,
+        }
+    }
+
+    // 获取当前读取位置
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    // 获取内部的安全文件
+    pub fn into_inner(self) -> SafeFile {
+        self.file
+    }
+}
+
+impl AsyncRead for SafeFileReader {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<Result<()>> {
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let this = &mut *self;
+        if this.reading.is_none() {
+            let file = this.file.clone();
+            let pos = this.pos;
+            let len = buf.remaining();
+            this.reading = Some(Box::pin(async move { file.read(pos, len).await }));
+        }
+        let r = match this.reading.as_mut().unwrap().as_mut().poll(cx) {
+            Poll::Pending => return Poll::Pending,
+            Poll::Ready(r) => r,
+        };
+        this.reading = None;
+        let data = r?;
+        // 缓冲区在两次轮询之间可能变小，只前进实际复制的字节数
+        let len = data.len().min(buf.remaining());
+        buf.put_slice(&data[..len]);
+        this.pos += len as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncSeek for SafeFileReader {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> Result<()> {
+        let this = &mut *self;
+        let pos = match position {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(offset) => offset_pos(this.file.get_size(), offset),
+            SeekFrom::Current(offset) => offset_pos(this.pos, offset),
+        };
+        match pos {
+            None => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Seek file failed, file: {:?}, reason: invalid seek to a negative or overflowing position", this.file.path()),
+            )),
+            Some(pos) => {
+                // 定位后放弃未完成的读
+                this.pos = pos;
+                this.reading = None;
+                Ok(())
+            }
+        }
+    }
+
+    fn poll_complete(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<u64>> {
+        Poll::Ready(Ok(self.pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{block_on, test_path};
+    use pi_async_file::file::AsyncFileOptions;
+    use std::fs;
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+    #[test]
+    fn copy_reads_whole_file() {
+        let path = test_path("tokio_copy");
+        let content = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+        fs::write(&path, &content).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let mut reader = SafeFileReader::new(file);
+            let mut out = Vec::new();
+            let n = tokio::io::copy(&mut reader, &mut out).await?;
+            Ok::<_, Error>((n, out, reader.position()))
+        })
+        .unwrap();
+        assert_eq!(r.0, content.len() as u64);
+        assert_eq!(r.1, content);
+        assert_eq!(r.2, content.len() as u64);
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn seek_moves_cursor() {
+        let path = test_path("tokio_seek");
+        fs::write(&path, (0..100u8).collect::<Vec<_>>()).unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            let mut reader = SafeFileReader::new(file);
+            let mut buf = [0; 4];
+            reader.seek(SeekFrom::Start(10)).await?;
+            reader.read_exact(&mut buf).await?;
+            let start = buf;
+            reader.seek(SeekFrom::Current(-2)).await?;
+            reader.read_exact(&mut buf).await?;
+            let current = buf;
+            let end = reader.seek(SeekFrom::End(-4)).await?;
+            reader.read_exact(&mut buf).await?;
+            let negative = reader.seek(SeekFrom::Current(-1000)).await.map_err(|e| e.kind());
+            Ok::<_, Error>((start, current, end, buf, negative))
+        })
+        .unwrap();
+        assert_eq!(r.0, [10, 11, 12, 13]);
+        assert_eq!(r.1, [12, 13, 14, 15]);
+        assert_eq!(r.2, 96);
+        assert_eq!(r.3, [96, 97, 98, 99]);
+        assert_eq!(r.4, Err(ErrorKind::InvalidInput));
+        let _ = fs::remove_file(path);
+    }
+}
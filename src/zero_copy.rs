@@ -0,0 +1,73 @@
+use std::io::Result;
+
+use bytes::Bytes;
+
+use crate::{runtime, SafeFile};
+
+impl SafeFile {
+    //从指定位置开始异步读指定字节，有缓存时直接共享缓存的内存，不复制数据
+    pub async fn read_bytes(&self, pos: u64, len: usize) -> Result<Bytes> {
+        if len == 0 {
+            //无效的字节数，则立即返回
+            return Ok(Bytes::new());
+        }
+        let _op = runtime::enter()?;
+        let data = self.0.buffered();
+        if !data.is_empty() {
+            let start = (pos as usize).min(data.len());
+            let end = start.saturating_add(len).min(data.len());
+            self.0.count_read(end - start);
+            self.0.count_cache(true);
+            return Ok(Bytes::from_owner(data).slice(start..end));
+        }
+        // 未命中缓存时由运行时读入从缓冲区池借出的缓冲区，再复制为共享的内存
+        let r = self.read_pooled(pos, len).await?;
+        Ok(Bytes::copy_from_slice(&r))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{block_on, test_path};
+    use crate::SafeFile;
+    use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+    use std::fs;
+    use std::io::Error;
+    use std::sync::Arc;
+
+    #[test]
+    fn cached_reads_share_memory() {
+        let path = test_path("zero_copy_cached");
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+            file.write(0, Arc::from(&b"shared bytes"[..]), WriteOptions::Flush).await?;
+            let cache = file.0.buffered().as_ptr() as usize;
+            let a = file.read_bytes(7, 5).await?;
+            let b = file.read_bytes(0, 100).await?;
+            Ok::<_, Error>((cache, a.as_ptr() as usize, b.as_ptr() as usize, a, b))
+        })
+        .unwrap();
+        assert_eq!(r.1, r.0 + 7);
+        assert_eq!(r.2, r.0);
+        assert_eq!(&r.3[..], b"bytes");
+        assert_eq!(&r.4[..], b"shared bytes");
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn uncached_reads_copy_range() {
+        let path = test_path("zero_copy_uncached");
+        fs::write(&path, b"0123456789").unwrap();
+        let copy = path.clone();
+        let r = block_on(async move {
+            let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+            Ok::<_, Error>((file.read_bytes(3, 4).await?, file.read_bytes(8, 10).await?, file.read_bytes(0, 0).await?))
+        })
+        .unwrap();
+        assert_eq!(&r.0[..], b"3456");
+        assert_eq!(&r.1[..], b"89");
+        assert!(r.2.is_empty());
+        let _ = fs::remove_file(path);
+    }
+}
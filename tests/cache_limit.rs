@@ -0,0 +1,59 @@
+/*
+* 缓存内存上限的测试，统计的是进程中所有打开的文件，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{cache_memory_usage, set_cache_memory_limit, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn limit_evicts_least_recently_read() {
+    let paths = ["a", "b", "c"]
+        .iter()
+        .map(|name| env::temp_dir().join(format!("pi_rt_file.test.{}.cache_limit_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        fs::write(path, vec![1u8; 100]).unwrap();
+    }
+    let copy = paths.clone();
+    let r = block_on(async move {
+        set_cache_memory_limit(250).await;
+        let mut files = Vec::new();
+        for path in copy {
+            let file = SafeFile::open(path, AsyncFileOptions::ReadWrite).await?;
+            file.read(0, 100).await?;
+            files.push(file);
+        }
+        // 读c后超过上限，释放最久未访问的a
+        let first = cache_memory_usage().await;
+        files[1].read(0, 100).await?;
+        // 重新读a时释放此时最久未访问的c
+        files[0].read(0, 100).await?;
+        files[2].read(0, 100).await?;
+        let stats = files.iter().map(|file| (file.cache_stats().hits, file.cache_stats().misses)).collect::<Vec<_>>();
+        // 降低上限时立即释放
+        set_cache_memory_limit(100).await;
+        let lowered = cache_memory_usage().await;
+        set_cache_memory_limit(0).await;
+        Ok::<_, Error>((first, stats, lowered))
+    })
+    .unwrap();
+    assert_eq!(r, (200, vec![(0, 2), (1, 1), (0, 2)], 100));
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
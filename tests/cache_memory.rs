@@ -0,0 +1,51 @@
+/*
+* 缓存占用内存的测试，统计的是进程中所有打开的文件，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{cache_memory_usage, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn usage_sums_cached_bytes() {
+    let paths = ["whole", "truncate", "paged"]
+        .iter()
+        .map(|name| env::temp_dir().join(format!("pi_rt_file.test.{}.cache_memory_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    fs::write(&paths[0], vec![1u8; 100]).unwrap();
+    fs::write(&paths[2], vec![2u8; 100]).unwrap();
+    let copy = paths.clone();
+    let r = block_on(async move {
+        let empty = cache_memory_usage().await;
+        let whole = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+        whole.read_to_end().await?;
+        let truncate = SafeFile::open(copy[1].clone(), AsyncFileOptions::TruncateWrite).await?;
+        truncate.write(0, Arc::from(vec![3u8; 40]), WriteOptions::Flush).await?;
+        // 按页缓存读到的两页
+        let paged = SafeFile::open_read_write_cached(copy[2].clone(), 16).await?;
+        paged.read(0, 20).await?;
+        let used = cache_memory_usage().await;
+        drop((whole, truncate, paged));
+        Ok::<_, Error>((empty, used, cache_memory_usage().await))
+    })
+    .unwrap();
+    assert_eq!(r, (0, 100 + 40 + 32, 0));
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,85 @@
+/*
+* 释放所有文件读缓存和固定缓存的测试，会影响进程中所有打开的文件，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{drop_all_caches, pinned_paths, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn drops_every_open_cache() {
+    let dir = env::temp_dir();
+    let paths = ["rw", "truncate"]
+        .iter()
+        .map(|name| dir.join(format!("pi_rt_file.test.{}.drop_caches_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    fs::write(&paths[0], b"read write").unwrap();
+    let copy = paths.clone();
+    let r = block_on(async move {
+        let rw = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+        let truncate = SafeFile::open(copy[1].clone(), AsyncFileOptions::TruncateWrite).await?;
+        rw.read_to_end().await?;
+        truncate.write(0, Arc::from(&b"truncate"[..]), WriteOptions::None).await?;
+        drop_all_caches().await?;
+        // 释放后都需要重新读取，截断写文件的数据已落地
+        let before = rw.cache_stats();
+        let data = rw.read_to_end().await?;
+        Ok::<_, Error>((data, rw.cache_stats().misses - before.misses))
+    })
+    .unwrap();
+    assert_eq!(r.0, b"read write");
+    assert_eq!(r.1, 1);
+    assert_eq!(fs::read(&paths[1]).unwrap(), b"truncate");
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[test]
+fn pinned_cache_survives_global_drop() {
+    let dir = env::temp_dir();
+    let paths = ["pinned", "unpinned"]
+        .iter()
+        .map(|name| dir.join(format!("pi_rt_file.test.{}.drop_caches_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        fs::write(path, b"data").unwrap();
+    }
+    let copy = paths.clone();
+    let r = block_on(async move {
+        let pinned = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+        let unpinned = SafeFile::open(copy[1].clone(), AsyncFileOptions::ReadWrite).await?;
+        pinned.pin_cache();
+        let listed = pinned_paths().await;
+        pinned.read_to_end().await?;
+        unpinned.read_to_end().await?;
+        drop_all_caches().await?;
+        pinned.read_to_end().await?;
+        unpinned.read_to_end().await?;
+        pinned.unpin_cache();
+        Ok::<_, Error>((listed, pinned.cache_stats().hits, unpinned.cache_stats().hits, pinned_paths().await))
+    })
+    .unwrap();
+    assert_eq!(r.0, vec![paths[0].clone()]);
+    // 固定的缓存仍然命中，未固定的需要重新读取
+    assert_eq!((r.1, r.2), (1, 0));
+    assert!(r.3.is_empty());
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
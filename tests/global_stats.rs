@@ -0,0 +1,58 @@
+/*
+* 全局IO统计的测试，统计对整个进程生效，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::io::Error;
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{global_io_stats, reset_global_io_stats, GlobalStats, SafeFile, FILE_RUNTIME};
+
+#[test]
+fn totals_span_multiple_files() {
+    let dir = env::temp_dir();
+    let paths = ["a", "b"]
+        .iter()
+        .map(|name| dir.join(format!("pi_rt_file.test.{}.global_stats_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    let copy = paths.clone();
+    reset_global_io_stats();
+    let r = FILE_RUNTIME
+        .block_on(async move {
+            let r = async move {
+                let a = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+                let b = SafeFile::open(copy[1].clone(), AsyncFileOptions::ReadWrite).await?;
+                // 共享句柄不重复统计打开的文件数
+                let _shared = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await?;
+                a.write(0, Arc::from(vec![1u8; 10]), WriteOptions::None).await?;
+                b.write(0, Arc::from(vec![2u8; 20]), WriteOptions::None).await?;
+                // 第一次读填充缓存，第二次读命中缓存
+                a.read(0, 64).await?;
+                a.read(0, 64).await?;
+                b.read(0, 64).await?;
+                Ok::<_, Error>(global_io_stats())
+            };
+            Some(r.await)
+        })
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        r,
+        GlobalStats {
+            read_bytes: 40,
+            written_bytes: 30,
+            opened_files: 2,
+            cache_hits: 1,
+            cache_misses: 2,
+        }
+    );
+    reset_global_io_stats();
+    assert_eq!(global_io_stats(), GlobalStats::default());
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
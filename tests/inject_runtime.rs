@@ -0,0 +1,51 @@
+/*
+* 注入调用者运行时的测试，运行时只能指定一次，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::multi_thread::{MultiTaskRuntimeBuilder, StealableTaskPool};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{set_file_runtime, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn operations_run_on_injected_runtime() {
+    let pool = StealableTaskPool::with(2, 100000, [1, 1], 3000);
+    let rt = MultiTaskRuntimeBuilder::new(pool)
+        .thread_prefix("Injected-Runtime")
+        .init_worker_size(2)
+        .set_worker_limit(2, 2)
+        .build();
+    assert!(set_file_runtime(rt.clone()).is_ok());
+    let again = set_file_runtime(rt.clone());
+    assert_eq!(again.map_err(|e| e.kind()), Err(ErrorKind::AlreadyExists));
+
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.inject", process::id()));
+    let copy = path.clone();
+    let r = block_on(async move {
+        let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+        file.write(0, Arc::from(&b"injected"[..]), WriteOptions::Flush).await?;
+        let data = file.read(0, 8).await?;
+        Ok::<_, Error>((data, thread::current().name().map(String::from)))
+    })
+    .unwrap();
+    assert_eq!(r.0, b"injected");
+    assert!(r.1.unwrap().starts_with("Injected-Runtime"));
+    assert_eq!(FILE_RUNTIME.worker_len(), rt.worker_len());
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,90 @@
+/*
+* 保留打开文件的LRU淘汰测试，保留上限对整个进程生效，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{ref_count, retained_file_count, set_open_file_limit, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME
+        .block_on(async move { Some(future.await) })
+        .unwrap()
+        .unwrap()
+}
+
+// 打开指定路径后立即释放用户的句柄
+async fn touch(path: &Path) -> Result<(), Error> {
+    SafeFile::open(path.to_path_buf(), AsyncFileOptions::ReadWrite).await?;
+    Ok(())
+}
+
+// 获取各路径上本库和用户持有的引用数
+async fn counts(paths: &[PathBuf]) -> Vec<usize> {
+    let mut r = Vec::new();
+    for path in paths {
+        r.push(ref_count(path.clone()).await);
+    }
+    r
+}
+
+#[test]
+fn evicts_least_recently_used_idle_files() {
+    let paths = (0..6)
+        .map(|i| env::temp_dir().join(format!("pi_rt_file.test.{}.keep_alive{}", process::id(), i)))
+        .collect::<Vec<_>>();
+    let copy = paths.clone();
+    let r = block_on(async move {
+        let p = copy;
+        let mut steps = Vec::new();
+        set_open_file_limit(2).await;
+        touch(&p[0]).await?;
+        touch(&p[1]).await?;
+        // 用户释放后仍保留，超过上限时淘汰最久未访问的0
+        steps.push(counts(&p[..3]).await);
+        touch(&p[2]).await?;
+        steps.push(counts(&p[..3]).await);
+        // 再次访问1后，最久未访问的变为2
+        touch(&p[1]).await?;
+        touch(&p[3]).await?;
+        steps.push(counts(&p[..4]).await);
+        // 用户持有的4不会被淘汰，即使它最久未访问
+        let held = SafeFile::open(p[4].clone(), AsyncFileOptions::ReadWrite).await?;
+        touch(&p[5]).await?;
+        touch(&p[0]).await?;
+        steps.push(counts(&p).await);
+        let retained = retained_file_count().await;
+        // 关闭保留后只剩用户持有的文件
+        set_open_file_limit(0).await;
+        steps.push(counts(&p).await);
+        drop(held);
+        Ok::<_, Error>((steps, retained, retained_file_count().await))
+    })
+    .unwrap();
+    assert_eq!(
+        r.0,
+        vec![
+            vec![1, 1, 0],
+            vec![0, 1, 1],
+            vec![0, 1, 0, 1],
+            vec![1, 0, 0, 0, 2, 0],
+            vec![0, 0, 0, 0, 1, 0],
+        ]
+    );
+    assert_eq!(r.1, 2);
+    assert_eq!(r.2, 0);
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
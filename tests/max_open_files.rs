@@ -0,0 +1,60 @@
+/*
+* 限制同时打开的文件数的测试，上限在运行时初始化时配置，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{init_runtime, RuntimeConfig, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 打开指定路径的文件
+fn open(path: PathBuf) -> SafeFile {
+    block_on(async move { SafeFile::open(path, AsyncFileOptions::ReadWrite).await }).unwrap()
+}
+
+#[test]
+fn opens_wait_for_closed_files() {
+    assert!(init_runtime(RuntimeConfig::new().thread_count(2).max_open_files(2)).is_ok());
+    let paths = ["a", "b", "c"]
+        .iter()
+        .map(|name| env::temp_dir().join(format!("pi_rt_file.test.{}.max_open_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    let a = open(paths[0].clone());
+    let b = open(paths[1].clone());
+    // 同一路径共享句柄，不占用新的许可
+    let shared = open(paths[0].clone());
+
+    // 已打开的文件数达到上限，第三个文件等待
+    let (sender, receiver) = mpsc::channel();
+    let path = paths[2].clone();
+    let waiter = thread::spawn(move || sender.send(open(path)).unwrap());
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // 共享的句柄未全部释放时不会关闭文件
+    drop(a);
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(shared);
+    let c = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiter.join().unwrap();
+    assert_eq!(c.path(), paths[2].as_path());
+    drop((b, c));
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,63 @@
+/*
+* 已打开的文件数达到上限且只被本库保留时打开的测试，上限在运行时初始化时配置，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::mem;
+use std::future::Future;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::{startup_global_time_loop, AsyncRuntimeExt};
+use pi_rt_file::{init_runtime, retained_file_count, set_open_file_limit, RuntimeConfig, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 打开指定路径的文件
+fn open(path: PathBuf) -> SafeFile {
+    block_on(async move { SafeFile::open(path, AsyncFileOptions::ReadWrite).await }).unwrap()
+}
+
+#[test]
+fn opens_evict_idle_retained_files() {
+    assert!(init_runtime(RuntimeConfig::new().thread_count(2).max_open_files(1)).is_ok());
+    // 等待许可时定时重新检查，需要全局的定时器
+    mem::forget(startup_global_time_loop(10));
+    block_on(set_open_file_limit(4));
+    let paths = ["a", "b", "c"]
+        .iter()
+        .map(|name| env::temp_dir().join(format!("pi_rt_file.test.{}.max_open_idle_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+
+    // 只被本库保留的文件在打开其它文件时被释放
+    drop(open(paths[0].clone()));
+    assert_eq!(block_on(retained_file_count()), 1);
+    let b = open(paths[1].clone());
+    assert_eq!(block_on(retained_file_count()), 1);
+
+    // 用户仍持有时等待，用户释放后文件只被本库保留，等待的打开将其释放
+    let (sender, receiver) = mpsc::channel();
+    let path = paths[2].clone();
+    let waiter = thread::spawn(move || sender.send(open(path)).unwrap());
+    assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(b);
+    let c = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiter.join().unwrap();
+    assert_eq!(c.path(), paths[2].as_path());
+    assert_eq!(block_on(retained_file_count()), 1);
+    drop(c);
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,53 @@
+/*
+* 并发打开同一新路径的测试，依赖全局的打开文件统计，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::process;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{global_io_stats, ref_count, reset_global_io_stats, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME
+        .block_on(async move { Some(future.await) })
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn concurrent_opens_create_one_file() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.open_race", process::id()));
+    reset_global_io_stats();
+    let barrier = Arc::new(Barrier::new(32));
+    let openers = (0..32)
+        .map(|_| {
+            let path = path.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                block_on(async move { SafeFile::open(path, AsyncFileOptions::ReadWrite).await })
+            })
+        })
+        .collect::<Vec<_>>();
+    let files = openers
+        .into_iter()
+        .map(|opener| opener.join().unwrap().unwrap())
+        .collect::<Vec<_>>();
+    let copy = path.clone();
+    let count = block_on(async move { ref_count(copy).await });
+    // 所有打开共享同一个底层文件
+    assert_eq!(count, 32);
+    assert_eq!(global_io_stats().opened_files, 1);
+    drop(files);
+    let _ = fs::remove_file(path);
+}
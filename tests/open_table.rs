@@ -0,0 +1,49 @@
+/*
+* 打开文件表的计数测试，计数针对整个进程，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{collect, open_file_count, total_entry_count, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn counts_live_and_dropped_handles() {
+    let paths = (0..5)
+        .map(|i| env::temp_dir().join(format!("pi_rt_file.test.{}.count{}", process::id(), i)))
+        .collect::<Vec<_>>();
+    let copy = paths.clone();
+    let r = block_on(async move {
+        let mut live = Vec::new();
+        for (i, path) in copy.into_iter().enumerate() {
+            let file = SafeFile::open(path, AsyncFileOptions::ReadWrite).await?;
+            // 前三个文件保持打开，其余的打开后立即释放，释放时条目即被移除
+            if i < 3 {
+                live.push(file);
+            }
+        }
+        let before = (open_file_count().await, total_entry_count().await);
+        let removed = collect().await;
+        let after = (open_file_count().await, total_entry_count().await);
+        drop(live);
+        Ok::<_, Error>((before, removed, after, open_file_count().await))
+    })
+    .unwrap();
+    assert_eq!(r, ((3, 3), 0, (3, 3), 0));
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,81 @@
+/*
+* 进程间建议锁的测试，由测试程序以子进程的方式再次运行自身来竞争同一把锁
+*/
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process::{self, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{SafeFile, FILE_RUNTIME};
+
+// 子进程竞争的锁文件路径的环境变量
+const CHILD_ENV: &str = "PI_RT_FILE_LOCK_CHILD";
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 子进程：获取独占锁后写入标记文件，只在父进程指定锁文件时执行
+#[test]
+fn lock_child() {
+    let path = match env::var(CHILD_ENV) {
+        Ok(path) => path,
+        Err(_) => return,
+    };
+    let r = block_on(async move {
+        let file = SafeFile::open(path.clone(), AsyncFileOptions::ReadWrite).await?;
+        file.lock_exclusive().await?;
+        fs::write(format!("{}.locked", path), b"")?;
+        file.unlock().await
+    });
+    r.unwrap();
+}
+
+#[test]
+fn exclusive_lock_blocks_child_process() {
+    if env::var(CHILD_ENV).is_ok() {
+        return;
+    }
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.os_lock", process::id()));
+    let marker = path.with_file_name(format!("{}.locked", path.file_name().unwrap().to_string_lossy()));
+    let copy = path.clone();
+    let file = block_on(async move {
+        let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+        file.lock_exclusive().await?;
+        Ok::<_, Error>(file)
+    })
+    .unwrap();
+
+    let mut child = Command::new(env::current_exe().unwrap())
+        .args(["lock_child", "--exact", "--test-threads=1"])
+        .env(CHILD_ENV, &path)
+        .spawn()
+        .unwrap();
+    // 持有独占锁期间，子进程无法获取锁
+    thread::sleep(Duration::from_millis(500));
+    let blocked = child.try_wait().unwrap().is_none() && !marker.exists();
+
+    let start = Instant::now();
+    let copy = file.clone();
+    block_on(async move { copy.unlock().await }).unwrap();
+    let status = child.wait().unwrap();
+    assert!(blocked);
+    assert!(status.success());
+    assert!(marker.exists());
+    assert!(start.elapsed() < Duration::from_secs(10));
+    drop(file);
+    let _ = fs::remove_file(path);
+    let _ = fs::remove_file(marker);
+}
@@ -0,0 +1,59 @@
+/*
+* 预热文件缓存的测试，依赖全局的保留上限和IO统计，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{global_io_stats, prewarm, reset_global_io_stats, set_open_file_limit, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn prewarmed_reads_hit_cache() {
+    let dir = env::temp_dir();
+    let paths = ["a", "b", "c", "missing"]
+        .iter()
+        .map(|name| dir.join(format!("pi_rt_file.test.{}.prewarm_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    for (index, path) in paths[..3].iter().enumerate() {
+        fs::write(path, vec![index as u8; 100]).unwrap();
+    }
+    let copy = paths.clone();
+    let r = block_on(async move {
+        set_open_file_limit(8).await;
+        let warmed = prewarm(copy.clone()).await.into_iter().map(|r| r.map_err(|e| e.kind())).collect::<Vec<_>>();
+        reset_global_io_stats();
+        let mut data = Vec::new();
+        for path in &copy[..3] {
+            let file = SafeFile::open(path.clone(), AsyncFileOptions::OnlyRead).await?;
+            data.push(file.read_to_end().await?);
+        }
+        let stats = global_io_stats();
+        // 只被本库保留的只读句柄会被释放，之后可以按其它方式打开
+        let rw = SafeFile::open(copy[0].clone(), AsyncFileOptions::ReadWrite).await.map(|_| ());
+        Ok::<_, Error>((warmed, data, stats, rw.is_ok()))
+    })
+    .unwrap();
+    assert_eq!(r.0, vec![Ok(()), Ok(()), Ok(()), Err(std::io::ErrorKind::NotFound)]);
+    for (index, data) in r.1.iter().enumerate() {
+        assert_eq!(data, &vec![index as u8; 100]);
+    }
+    // 预热的句柄被保留，再次打开共享句柄并命中缓存
+    assert_eq!((r.2.opened_files, r.2.cache_hits, r.2.cache_misses), (0, 3, 0));
+    assert!(r.3);
+    for path in &paths[..3] {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,50 @@
+/*
+* 未开启保留时预热文件缓存的测试，依赖全局的保留上限和IO统计，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{global_io_stats, prewarm, reset_global_io_stats, retained_file_count, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME
+        .block_on(async move { Some(future.await) })
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn prewarm_retains_without_open_file_limit() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.prewarm_default", process::id()));
+    fs::write(&path, b"data").unwrap();
+    let copy = path.clone();
+    let r = block_on(async move {
+        let warmed = prewarm(vec![copy.clone()]).await.into_iter().map(|r| r.is_ok()).collect::<Vec<_>>();
+        let retained = retained_file_count().await;
+        reset_global_io_stats();
+        let file = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await?;
+        let data = file.read_to_end().await?;
+        let stats = global_io_stats();
+        // 关闭后不再保留
+        file.close().await?;
+        Ok::<_, Error>((warmed, retained, data, stats, retained_file_count().await))
+    })
+    .unwrap();
+    assert_eq!(r.0, vec![true]);
+    assert_eq!(r.1, 1);
+    assert_eq!(r.2, b"data");
+    // 未开启保留时预热的句柄仍被保留，再次打开共享句柄并命中缓存
+    assert_eq!((r.3.opened_files, r.3.cache_hits, r.3.cache_misses), (0, 1, 0));
+    assert_eq!(r.4, 0);
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,86 @@
+/*
+* 读取到调用者缓冲区的基准测试，统计内存分配需要替换全局分配器，因此单独作为一个测试程序
+*/
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::pin::pin;
+use std::process;
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{SafeFile, FILE_RUNTIME};
+
+// 统计当前线程内存分配次数的分配器
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn buffered_read_into_does_not_allocate() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.read_into", process::id()));
+    let copy = path.clone();
+    let file = block_on(async move {
+        let file = SafeFile::open(copy, AsyncFileOptions::TruncateWrite).await?;
+        file.write(0, Arc::from((0..=255u8).collect::<Vec<_>>()), WriteOptions::Flush).await?;
+        Ok::<_, Error>(file)
+    })
+    .unwrap();
+
+    // 截断写文件的缓冲数据可以直接复制，在当前线程轮询即可完成，不会挂起
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut buf = [0u8; 16];
+    let mut total = 0;
+    // 首次读取时可能初始化线程局部的状态，不计入统计
+    let _ = pin!(file.read_into(0, &mut buf)).poll(&mut cx);
+    let start = Instant::now();
+    let before = ALLOCS.with(Cell::get);
+    for i in 0..100_000u64 {
+        let pos = i % 256;
+        let r = pin!(file.read_into(pos, &mut buf)).poll(&mut cx);
+        match r {
+            Poll::Ready(Ok(n)) => {
+                assert_eq!(n, 16.min(256 - pos as usize));
+                assert_eq!(buf[0], pos as u8);
+                total += n;
+            }
+            r => panic!("read into failed, reason: {:?}", r),
+        }
+    }
+    let allocs = ALLOCS.with(Cell::get) - before;
+    println!("read_into: 100000 reads, {} bytes, {:?}", total, start.elapsed());
+    assert_eq!(allocs, 0);
+    drop(file);
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,34 @@
+/*
+* 从环境变量配置文件运行时的测试，环境变量和运行时针对整个进程，因此单独作为一个测试程序
+*/
+use std::env;
+use std::io::ErrorKind;
+
+use pi_rt_file::{RuntimeConfig, FILE_RUNTIME};
+
+#[test]
+fn thread_count_from_env() {
+    // 未声明时使用默认配置
+    env::remove_var("PI_RT_FILE_THREADS");
+    env::remove_var("_ver");
+    assert!(RuntimeConfig::from_env().is_ok());
+
+    // 无效的值返回错误，而不是panic
+    for value in ["abc", "-1", "0", ""].iter() {
+        env::set_var("PI_RT_FILE_THREADS", value);
+        let err = RuntimeConfig::from_env().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("PI_RT_FILE_THREADS"));
+    }
+
+    // 兼容旧的环境变量
+    env::remove_var("PI_RT_FILE_THREADS");
+    env::set_var("_ver", "x");
+    assert_eq!(RuntimeConfig::from_env().unwrap_err().kind(), ErrorKind::InvalidInput);
+    env::remove_var("_ver");
+
+    // 有效的值决定运行时的线程数
+    env::set_var("PI_RT_FILE_THREADS", " 3 ");
+    assert!(RuntimeConfig::from_env().is_ok());
+    assert_eq!(FILE_RUNTIME.worker_len(), 3);
+}
@@ -0,0 +1,40 @@
+/*
+* 环境变量声明的线程数无效时构建文件运行时的测试，环境变量、日志和运行时针对整个进程，因此单独作为一个测试程序
+*/
+use std::env;
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use pi_rt_file::FILE_RUNTIME;
+
+// 记录警告日志的日志器
+struct WarnLogger(Mutex<Vec<String>>);
+
+impl Log for WarnLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            self.0.lock().unwrap().push(record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: WarnLogger = WarnLogger(Mutex::new(Vec::new()));
+
+#[test]
+fn invalid_thread_count_warns_and_uses_default() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Warn);
+    env::remove_var("_ver");
+    env::set_var("PI_RT_FILE_THREADS", "abc");
+    // 无效的值不会panic，而是记录警告后按cpu核数构建
+    assert_eq!(FILE_RUNTIME.worker_len(), num_cpus::get());
+    let logs = LOGGER.0.lock().unwrap();
+    assert_eq!(logs.len(), 1);
+    assert!(logs[0].contains("PI_RT_FILE_THREADS") && logs[0].contains("abc"));
+}
@@ -0,0 +1,16 @@
+/*
+* 初始化文件运行时的测试，运行时只能初始化一次，因此单独作为一个测试程序
+*/
+use std::io::ErrorKind;
+
+use pi_rt_file::{init_runtime, RuntimeConfig, FILE_RUNTIME};
+
+#[test]
+fn init_runtime_before_first_use() {
+    let zero = init_runtime(RuntimeConfig::new().thread_count(0));
+    assert_eq!(zero.map_err(|e| e.kind()), Err(ErrorKind::InvalidInput));
+    assert!(init_runtime(RuntimeConfig::new().thread_count(2).stack_size(512 * 1024)).is_ok());
+    let again = init_runtime(RuntimeConfig::new().thread_count(4));
+    assert_eq!(again.map_err(|e| e.kind()), Err(ErrorKind::AlreadyExists));
+    assert_eq!(FILE_RUNTIME.worker_len(), 2);
+}
@@ -0,0 +1,64 @@
+/*
+* 文件运行时运行状态的测试，需要阻塞运行时的工作者，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::{AsyncRuntime, AsyncRuntimeExt};
+use pi_rt_file::{runtime_stats, CacheOptions, SafeFile, FILE_RUNTIME};
+
+#[test]
+fn slow_operations_show_pending_and_active() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.runtime_stats", process::id()));
+    fs::write(&path, vec![7u8; 1024 * 1024]).unwrap();
+    let copy = path.clone();
+    let file = FILE_RUNTIME
+        .block_on(async move {
+            // 关闭读缓存，每次读取都需要底层读
+            let cache = CacheOptions {
+                enable: false,
+                ..Default::default()
+            };
+            Some(SafeFile::open_with(copy, AsyncFileOptions::OnlyRead, cache).await)
+        })
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let idle = runtime_stats();
+    assert!(idle.worker_count > 0);
+    // 先派发一批慢任务阻塞所有工作者，之后的任务都只能在任务池中等待，读任务开始后其底层读也排在慢任务之后
+    let (mut pending, mut active) = (0, 0);
+    let now = Instant::now();
+    while now.elapsed() < Duration::from_secs(5) && (pending == 0 || active == 0) {
+        for _ in 0..idle.worker_count * 4 {
+            FILE_RUNTIME
+                .spawn(async move {
+                    thread::sleep(Duration::from_millis(20));
+                })
+                .unwrap();
+        }
+        pending = pending.max(runtime_stats().pending_tasks);
+        for _ in 0..16 {
+            let file = file.clone();
+            FILE_RUNTIME
+                .spawn(async move {
+                    let _ = file.read(0, 1024 * 1024).await;
+                })
+                .unwrap();
+        }
+        for _ in 0..100 {
+            let stats = runtime_stats();
+            pending = pending.max(stats.pending_tasks);
+            active = active.max(stats.active_ops);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+    assert!(pending > 0);
+    assert!(active > 0);
+    drop(file);
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,53 @@
+/*
+* 关闭文件运行时的测试，关闭对整个进程生效，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{shutdown, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME
+        .block_on(async move { Some(future.await) })
+        .unwrap()
+        .unwrap()
+}
+
+#[test]
+fn shutdown_flushes_and_rejects_later_ops() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.shutdown", process::id()));
+    let copy = path.clone();
+    let r = block_on(async move {
+        let file = SafeFile::open(copy.clone(), AsyncFileOptions::TruncateWrite).await?;
+        file.write(0, Arc::from(&b"first"[..]), WriteOptions::None).await?;
+        file.write(0, Arc::from(&b"second"[..]), WriteOptions::None).await?;
+        // 关闭时将截断写文件的缓冲数据同步到磁盘
+        let closed = shutdown(true).await;
+        let disk = fs::read(&copy)?;
+        let later = file.write(0, Arc::from(&b"third"[..]), WriteOptions::None).await;
+        let reopen = SafeFile::open(copy, AsyncFileOptions::OnlyRead).await;
+        let rejected = |e: Error| e.to_string().contains("shut down");
+        Ok::<_, Error>((
+            closed.is_ok(),
+            disk,
+            later.map_err(rejected),
+            reopen.map(|_| ()).map_err(rejected),
+        ))
+    })
+    .unwrap();
+    assert_eq!(r, (true, b"second".to_vec(), Err(true), Err(true)));
+    // 关闭后FILE_RUNTIME的工作线程仍可执行其它任务
+    assert_eq!(block_on(async move { 1 }), 1);
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,51 @@
+/*
+* 在文件操作的回调中关闭文件运行时的测试，关闭对整个进程生效，因此单独作为一个测试程序
+*/
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{copy_file_with_progress, shutdown, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn shutdown_inside_callback_is_rejected() {
+    let paths = ["from", "to", "after"]
+        .iter()
+        .map(|name| env::temp_dir().join(format!("pi_rt_file.test.{}.shutdown_callback_{}", process::id(), name)))
+        .collect::<Vec<_>>();
+    fs::write(&paths[0], b"data").unwrap();
+    let copy = paths.clone();
+    let r = block_on(async move {
+        // 回调中请求的关闭会等待进行中的复制自身完成，因此被拒绝
+        let mut inside = None;
+        copy_file_with_progress(copy[0].clone(), copy[1].clone(), 2, |_, _| {
+            inside.get_or_insert_with(|| shutdown(true));
+        })
+        .await?;
+        let rejected = inside.unwrap().await.map_err(|e| e.kind());
+        // 运行时未被关闭，之后的操作正常执行，在回调外关闭则等待进行中的操作完成后返回
+        let file = SafeFile::open(copy[2].clone(), AsyncFileOptions::TruncateWrite).await?;
+        file.write(0, Arc::from(&b"after"[..]), WriteOptions::None).await?;
+        let closed = shutdown(true).await;
+        Ok::<_, Error>((rejected, closed.is_ok(), fs::read(&copy[2])?))
+    })
+    .unwrap();
+    assert_eq!(r, (Err(ErrorKind::Deadlock), true, b"after".to_vec()));
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
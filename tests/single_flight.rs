@@ -0,0 +1,67 @@
+/*
+* 并发的相同读共享一次底层读的测试，依赖整个进程的读系统调用计数，因此单独作为一个测试程序
+*/
+#![cfg(target_os = "linux")]
+
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+
+use futures::future::join_all;
+use pi_async_file::file::AsyncFileOptions;
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{CacheOptions, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+// 获取本进程累计的读系统调用次数，读取统计本身也会产生读系统调用
+fn read_syscalls() -> u64 {
+    let io = fs::read_to_string("/proc/self/io").unwrap();
+    io.lines()
+        .find_map(|line| line.strip_prefix("syscr: "))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[test]
+fn identical_reads_invoke_backend_once() {
+    let path = env::temp_dir().join(format!("pi_rt_file.test.{}.single_flight", process::id()));
+    fs::write(&path, vec![3u8; 4096]).unwrap();
+    let copy = path.clone();
+    let file = block_on(async move {
+        // 关闭读缓存，每次读取都需要底层读
+        let cache = CacheOptions {
+            enable: false,
+            ..Default::default()
+        };
+        SafeFile::open_with(copy, AsyncFileOptions::OnlyRead, cache).await
+    })
+    .unwrap();
+    // 两次统计之间的固定开销
+    let overhead = {
+        let before = read_syscalls();
+        read_syscalls() - before
+    };
+    let before = read_syscalls();
+    let reader = file.clone();
+    let r = block_on(async move {
+        let reads = (0..16).map(|_| reader.read(100, 1000));
+        join_all(reads).await.into_iter().collect::<Result<Vec<_>, Error>>()
+    })
+    .unwrap();
+    let used = read_syscalls() - before - overhead;
+    assert!(r.iter().all(|data| *data == vec![3u8; 1000]));
+    assert_eq!(used, 1);
+    drop(file);
+    let _ = fs::remove_file(path);
+}
@@ -0,0 +1,50 @@
+/*
+* 写入前按阈值检查可用空间的测试，阈值是全局的，因此单独作为一个测试程序
+*/
+#![cfg(unix)]
+
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Error;
+use std::process;
+use std::sync::Arc;
+
+use pi_async_file::file::{AsyncFileOptions, WriteOptions};
+use pi_async_rt::rt::AsyncRuntimeExt;
+use pi_rt_file::{set_space_check_threshold, SafeFile, FILE_RUNTIME};
+
+// 在FILE_RUNTIME上执行异步任务并返回结果，任务中panic会使block_on无法返回，因此断言都在任务外进行
+fn block_on<F, T>(future: F) -> T
+where
+    F: Future<Output = T> + Send + 'static,
+    T: Send + 'static,
+{
+    FILE_RUNTIME.block_on(async move { Some(future.await) }).unwrap().unwrap()
+}
+
+#[test]
+fn threshold_decides_when_to_query_space() {
+    let dir = env::temp_dir().join(format!("pi_rt_file.test.{}.space_check", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("file");
+    let copy = path.clone();
+    let r = block_on(async move {
+        let file = SafeFile::open(copy, AsyncFileOptions::ReadWrite).await?;
+        // 移除文件所在目录后，查询可用空间会失败，以此判断写入前是否检查了空间
+        fs::remove_file(file.path())?;
+        fs::remove_dir(file.path().parent().unwrap())?;
+        let buf: Arc<[u8]> = Arc::from(vec![1u8; 8]);
+        set_space_check_threshold(16);
+        let below = file.write(0, buf.clone(), WriteOptions::None).await.map_err(|e| e.to_string());
+        set_space_check_threshold(8);
+        let reached = file.write(0, buf.clone(), WriteOptions::None).await.map_err(|e| e.to_string());
+        set_space_check_threshold(0);
+        let disabled = file.write(0, buf, WriteOptions::None).await.map_err(|e| e.to_string());
+        Ok::<_, Error>((below, reached, disabled))
+    })
+    .unwrap();
+    assert_eq!(r.0, Ok(8));
+    assert!(r.1.unwrap_err().starts_with("Query space failed"));
+    assert_eq!(r.2, Ok(8));
+}